@@ -0,0 +1,132 @@
+//! The streaming loop's `Vec` read buffer against splitting each read off a `BytesMut`
+//! The `Vec` stays: it is faster at the default read size, and the loop never keeps a read
+//! Run with `cargo bench --bench read_buffer`
+
+use std::io::{self, Cursor, Read};
+
+use bytes::BytesMut;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+/// How many reads fit in the space that is taken at a time
+const READS: usize = 64;
+
+/// Each read is split off as its own `BytesMut`, which can be frozen and kept without copying it
+struct ReadBuffer {
+    buf: BytesMut,
+    size: usize,
+}
+
+impl ReadBuffer {
+    fn new(size: usize) -> Self {
+        ReadBuffer {
+            buf: BytesMut::new(),
+            size,
+        }
+    }
+
+    fn read_from(&mut self, src: &mut impl Read) -> io::Result<BytesMut> {
+        if self.buf.len() < self.size {
+            // Takes back the space of the reads before if nothing holds on to them
+            self.buf.clear();
+            self.buf.reserve(self.size * READS);
+            self.buf.resize(self.size * READS, 0);
+        }
+        let n = src.read(&mut self.buf[..self.size])?;
+        Ok(self.buf.split_to(n))
+    }
+}
+
+/// A megabyte of something that looks like a device printing lines
+fn input() -> Vec<u8> {
+    b"temp=21.5 hum=40 \x07\n"
+        .iter()
+        .copied()
+        .cycle()
+        .take(1 << 20)
+        .collect()
+}
+
+/// Every read is changed in place and then looked at, as the streaming loop does
+fn in_place(c: &mut Criterion) {
+    let input = input();
+    let mut group = c.benchmark_group("in_place");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    for size in [64, 4096] {
+        group.bench_with_input(BenchmarkId::new("vec", size), &size, |b, &size| {
+            b.iter(|| {
+                let mut src = Cursor::new(&input);
+                let mut buf = vec![0; size];
+                let mut sum = 0u64;
+                loop {
+                    let n = src.read(&mut buf[..size]).unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    buf[..n].iter_mut().for_each(|b| *b &= 0x7f);
+                    sum += buf[..n].iter().map(|&b| b as u64).sum::<u64>();
+                }
+                black_box(sum)
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("bytes_mut", size), &size, |b, &size| {
+            b.iter(|| {
+                let mut src = Cursor::new(&input);
+                let mut reads = ReadBuffer::new(size);
+                let mut sum = 0u64;
+                loop {
+                    let mut buf = reads.read_from(&mut src).unwrap();
+                    if buf.is_empty() {
+                        break;
+                    }
+                    buf.iter_mut().for_each(|b| *b &= 0x7f);
+                    sum += buf.iter().map(|&b| b as u64).sum::<u64>();
+                }
+                black_box(sum)
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Every read is kept, as something handed on to another thread or several writers would be
+fn kept(c: &mut Criterion) {
+    let input = input();
+    let mut group = c.benchmark_group("kept");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    for size in [64, 4096] {
+        group.bench_with_input(BenchmarkId::new("vec", size), &size, |b, &size| {
+            b.iter(|| {
+                let mut src = Cursor::new(&input);
+                let mut buf = vec![0; size];
+                let mut kept = Vec::new();
+                loop {
+                    let n = src.read(&mut buf[..size]).unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    kept.push(buf[..n].to_vec());
+                }
+                black_box(kept)
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("bytes_mut", size), &size, |b, &size| {
+            b.iter(|| {
+                let mut src = Cursor::new(&input);
+                let mut reads = ReadBuffer::new(size);
+                let mut kept = Vec::new();
+                loop {
+                    let buf = reads.read_from(&mut src).unwrap();
+                    if buf.is_empty() {
+                        break;
+                    }
+                    kept.push(buf.freeze());
+                }
+                black_box(kept)
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, in_place, kept);
+criterion_main!(benches);
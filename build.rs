@@ -0,0 +1,20 @@
+use std::process::Command;
+
+fn main() {
+    // Build info for --version, the git hash is left out when building outside a checkout
+    // Not named OXTERM_, cargo run sets these for the binary too, where they would be taken for options
+    let hash = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|out| out.status.success())
+        .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_owned())
+        .unwrap_or_default();
+    println!("cargo:rustc-env=BUILD_GIT_HASH={}", hash);
+    println!(
+        "cargo:rustc-env=BUILD_TARGET={}",
+        std::env::var("TARGET").unwrap()
+    );
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs/heads");
+}
@@ -0,0 +1,110 @@
+use std::fmt;
+
+use crate::ConvertFrom;
+
+/// Which side of the safe range a value fell out of, with the limit it crossed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Violation {
+    Below(f64),
+    Above(f64),
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::Below(limit) => write!(f, "below {}", limit),
+            Violation::Above(limit) => write!(f, "above {}", limit),
+        }
+    }
+}
+
+/// Whether `value` is outside the range, the limits themselves are in range
+pub fn check(value: f64, below: Option<f64>, above: Option<f64>) -> Option<Violation> {
+    match (below, above) {
+        (Some(limit), _) if value < limit => Some(Violation::Below(limit)),
+        (_, Some(limit)) if value > limit => Some(Violation::Above(limit)),
+        _ => None,
+    }
+}
+
+/// Watches the decoded values for ones outside `--alert-below`/`--alert-above`
+/// Values can be split across any number of reads
+pub struct Alerts {
+    convert: ConvertFrom,
+    below: Option<f64>,
+    above: Option<f64>,
+    carry: Vec<u8>,
+    index: u64,
+    pub count: u64,
+}
+
+impl Alerts {
+    pub fn new(convert: ConvertFrom, below: Option<f64>, above: Option<f64>) -> Self {
+        Alerts {
+            convert,
+            below,
+            above,
+            carry: Vec::new(),
+            index: 0,
+            count: 0,
+        }
+    }
+
+    /// Call `alert` with the sample number, value and violation of every out of range value in `data`
+    pub fn feed(&mut self, data: &[u8], mut alert: impl FnMut(u64, f64, Violation)) {
+        self.carry.extend_from_slice(data);
+        let mut values = self.carry.chunks_exact(self.convert.width());
+        for value in &mut values {
+            let value = self.convert.number(value);
+            if let Some(violation) = check(value, self.below, self.above) {
+                self.count += 1;
+                alert(self.index, value, violation);
+            }
+            self.index += 1;
+        }
+        let used = self.carry.len() - values.remainder().len();
+        self.carry.drain(..used);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_limits_themselves_are_in_range() {
+        assert_eq!(check(5.0, Some(5.0), Some(10.0)), None);
+        assert_eq!(check(10.0, Some(5.0), Some(10.0)), None);
+        assert_eq!(
+            check(4.9, Some(5.0), Some(10.0)),
+            Some(Violation::Below(5.0))
+        );
+        assert_eq!(check(10.1, None, Some(10.0)), Some(Violation::Above(10.0)));
+        assert_eq!(check(-1e9, None, Some(10.0)), None);
+        assert_eq!(Violation::Above(10.0).to_string(), "above 10");
+    }
+
+    #[test]
+    fn alerts_fire_at_the_samples_out_of_range() {
+        let values: Vec<u8> = [20i16, 35, 41, 40, -3, 12]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let mut alerts = Alerts::new(ConvertFrom::SHR, Some(0.0), Some(40.0));
+        let mut fired = Vec::new();
+        // Split mid-value
+        for chunk in values.chunks(3) {
+            alerts.feed(chunk, |index, value, violation| {
+                fired.push((index, value, violation))
+            });
+        }
+        assert_eq!(
+            fired,
+            [
+                (2, 41.0, Violation::Above(40.0)),
+                (4, -3.0, Violation::Below(0.0))
+            ]
+        );
+        assert_eq!(alerts.count, 2);
+    }
+}
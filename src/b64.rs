@@ -0,0 +1,79 @@
+use std::io::{self, Write};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use crate::Encoder;
+
+pub fn encode(data: &[u8]) -> String {
+    STANDARD.encode(data)
+}
+
+/// Write a frame as one line of base64
+pub fn line(frame: &[u8], out: &mut Vec<u8>) {
+    out.extend_from_slice(encode(frame).as_bytes());
+    out.push(b'\n');
+}
+
+/// Writes every received line as one line of base64, without its line ending
+pub struct Base64Writer {
+    carry: Vec<u8>,
+}
+
+impl Base64Writer {
+    pub fn new() -> Self {
+        Base64Writer { carry: Vec::new() }
+    }
+}
+
+impl Encoder for Base64Writer {
+    fn write(&mut self, data: &[u8], out: &mut dyn Write) -> io::Result<()> {
+        self.carry.extend_from_slice(data);
+        let mut used = 0;
+        while let Some(i) = self.carry[used..].iter().position(|&b| b == b'\n') {
+            let record = &self.carry[used..used + i];
+            let record = record.strip_suffix(b"\r").unwrap_or(record);
+            writeln!(out, "{}", encode(record))?;
+            used += i + 1;
+        }
+        self.carry.drain(..used);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_sequences_encode_with_padding() {
+        // The test vectors of RFC 4648
+        assert_eq!(encode(b""), "");
+        assert_eq!(encode(b"f"), "Zg==");
+        assert_eq!(encode(b"fo"), "Zm8=");
+        assert_eq!(encode(b"foo"), "Zm9v");
+        assert_eq!(encode(b"foob"), "Zm9vYg==");
+        assert_eq!(encode(b"fooba"), "Zm9vYmE=");
+        assert_eq!(encode(b"foobar"), "Zm9vYmFy");
+        assert_eq!(encode(&[0x00, 0xff, 0xfe]), "AP/+");
+    }
+
+    #[test]
+    fn frames_are_one_line_each() {
+        let mut out = Vec::new();
+        line(&[0xfb, 0xff], &mut out);
+        line(&[], &mut out);
+        assert_eq!(out, b"+/8=\n\n");
+    }
+
+    #[test]
+    fn each_line_is_encoded_once_it_is_complete() {
+        let mut writer = Base64Writer::new();
+        let mut out = Vec::new();
+        writer.write(b"fo", &mut out).unwrap();
+        assert!(out.is_empty());
+        writer.write(b"o\r\n\nfoob", &mut out).unwrap();
+        writer.write(b"ar\n", &mut out).unwrap();
+        assert_eq!(out, b"Zm9v\n\nZm9vYmFy\n");
+    }
+}
@@ -0,0 +1,86 @@
+use std::io;
+use std::time::{Duration, Instant};
+
+use serialport::{ClearBuffer, SerialPort};
+
+use crate::histogram::Histogram;
+
+/// The rates tried by `--detect-baud`, slowest first
+pub const COMMON_RATES: [u32; 11] = [
+    1200, 2400, 4800, 9600, 19200, 38400, 57600, 115200, 230400, 460800, 921600,
+];
+
+/// How long to listen at each rate
+const WINDOW: Duration = Duration::from_millis(500);
+
+/// What was received while listening at one baud rate
+#[derive(Debug, Clone, Copy)]
+pub struct Candidate {
+    pub baud_rate: u32,
+    pub bytes: usize,
+    pub score: f64,
+}
+
+/// How much `data` looks like text, between 0 and 1
+/// At the wrong rate a text stream turns into mostly unprintable bytes
+pub fn score(data: &[u8]) -> f64 {
+    let mut histogram = Histogram::new();
+    histogram.add(data);
+    if histogram.total() == 0 {
+        return 0.0;
+    }
+    histogram.printable_ratio()
+}
+
+/// Listen at each of `rates` in turn, returning the candidates best first
+pub fn detect(port: &mut dyn SerialPort, rates: &[u32]) -> io::Result<Vec<Candidate>> {
+    let mut buf = [0; 256];
+    let mut candidates = Vec::new();
+    for &baud_rate in rates {
+        port.set_baud_rate(baud_rate)?;
+        port.clear(ClearBuffer::Input)?;
+
+        let mut data = Vec::new();
+        let start = Instant::now();
+        while start.elapsed() < WINDOW {
+            match port.read(&mut buf) {
+                Ok(n) => data.extend_from_slice(&buf[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {
+                    std::thread::sleep(crate::IDLE_SLEEP)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        candidates.push(Candidate {
+            baud_rate,
+            bytes: data.len(),
+            score: score(&data),
+        });
+    }
+    candidates.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap()
+            .then(b.bytes.cmp(&a.bytes))
+    });
+    Ok(candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_scores_higher_than_garbage() {
+        let good = score(b"temp=21.5 humidity=40%\r\ntemp=21.6 humidity=41%\r\n");
+        // What 9600 baud text looks like when read at 115200
+        let garbage = score(&[0x80, 0xf8, 0x00, 0xfe, 0x78, 0x86, 0xe0, 0x00, 0x9e, 0x66]);
+        assert!(good > 0.9, "{}", good);
+        assert!(garbage < 0.3, "{}", garbage);
+    }
+
+    #[test]
+    fn nothing_received_scores_zero() {
+        assert_eq!(score(b""), 0.0);
+    }
+}
@@ -0,0 +1,130 @@
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+pub const BEL: u8 = 0x07;
+
+/// How long the screen stays inverted for a visual bell
+const FLASH: Duration = Duration::from_millis(100);
+
+/// What happens to the BEL bytes a device sends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bell {
+    /// Passed through, so the terminal beeps
+    Audible,
+    /// Taken out, and the screen flashes instead
+    Visual,
+    /// Taken out
+    Ignore,
+}
+
+impl FromStr for Bell {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "audible" => Bell::Audible,
+            "visual" => Bell::Visual,
+            "ignore" => Bell::Ignore,
+            _ => return Err("Bell should be audible, visual or ignore"),
+        })
+    }
+}
+
+impl Bell {
+    /// Take the BEL bytes out of `data` unless they are passed through
+    /// Returns how many bytes are left at the start of `data` and how many bells there were
+    pub fn apply(self, data: &mut [u8]) -> (usize, usize) {
+        if self == Bell::Audible {
+            return (data.len(), 0);
+        }
+        let mut kept = 0;
+        for i in 0..data.len() {
+            if data[i] != BEL {
+                data[kept] = data[i];
+                kept += 1;
+            }
+        }
+        (kept, data.len() - kept)
+    }
+}
+
+/// Flashes the terminal by inverting it for a moment, the escapes go to stderr
+#[derive(Default)]
+pub struct Flash {
+    until: Option<Instant>,
+}
+
+impl Flash {
+    /// Start a flash at `now`, or make the one showing last longer
+    pub fn ring(&mut self, now: Instant) {
+        if self.until.is_none() {
+            eprint!("\x1b[?5h");
+        }
+        self.until = Some(now + FLASH);
+    }
+
+    /// End the flash once it has been showing long enough
+    pub fn poll(&mut self, now: Instant) {
+        if self.until.is_some_and(|until| now >= until) {
+            self.end();
+        }
+    }
+
+    fn end(&mut self) {
+        if self.until.take().is_some() {
+            eprint!("\x1b[?5l");
+        }
+    }
+}
+
+impl Drop for Flash {
+    fn drop(&mut self) {
+        self.end();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA: &[u8] = b"\x07ready\x07\x07 ok\n\x07";
+
+    fn applied(bell: Bell) -> (Vec<u8>, usize) {
+        let mut data = DATA.to_vec();
+        let (kept, rung) = bell.apply(&mut data);
+        data.truncate(kept);
+        (data, rung)
+    }
+
+    #[test]
+    fn audible_passes_the_bells_through() {
+        assert_eq!(applied(Bell::Audible), (DATA.to_vec(), 0));
+    }
+
+    #[test]
+    fn visual_and_ignore_take_the_bells_out_and_count_them() {
+        assert_eq!(applied(Bell::Visual), (b"ready ok\n".to_vec(), 4));
+        assert_eq!(applied(Bell::Ignore), (b"ready ok\n".to_vec(), 4));
+        let mut none = *b"quiet";
+        assert_eq!(Bell::Ignore.apply(&mut none), (5, 0));
+    }
+
+    #[test]
+    fn a_flash_lasts_from_the_last_bell() {
+        let start = Instant::now();
+        let mut flash = Flash::default();
+        flash.ring(start);
+        flash.ring(start + FLASH / 2);
+        flash.poll(start + FLASH);
+        assert!(flash.until.is_some());
+        flash.poll(start + FLASH * 2);
+        assert!(flash.until.is_none());
+    }
+
+    #[test]
+    fn modes_are_parsed_ignoring_case() {
+        assert_eq!("Visual".parse(), Ok(Bell::Visual));
+        assert_eq!("IGNORE".parse(), Ok(Bell::Ignore));
+        assert!("loud".parse::<Bell>().is_err());
+    }
+}
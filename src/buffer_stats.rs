@@ -0,0 +1,158 @@
+use std::fmt;
+
+/// How many equal slices of the buffer the read sizes are sorted into
+const BUCKETS: usize = 4;
+
+/// How full each read left the buffer, for tuning `--capacity` and `--max-read-size`
+pub struct BufferStats {
+    size: usize,
+    reads: u64,
+    total: u64,
+    min: usize,
+    max: usize,
+    /// Partial reads by how full they were, full reads are counted separately
+    buckets: [u64; BUCKETS],
+    full: u64,
+}
+
+impl BufferStats {
+    /// For reads of at most `size` bytes
+    pub fn new(size: usize) -> Self {
+        BufferStats {
+            size: size.max(1),
+            reads: 0,
+            total: 0,
+            min: usize::MAX,
+            max: 0,
+            buckets: [0; BUCKETS],
+            full: 0,
+        }
+    }
+
+    /// Count one read of `n` bytes, reads that timed out with nothing aren't counted
+    pub fn add(&mut self, n: usize) {
+        if n == 0 {
+            return;
+        }
+        self.reads += 1;
+        self.total += n as u64;
+        self.min = self.min.min(n);
+        self.max = self.max.max(n);
+        if n >= self.size {
+            self.full += 1;
+        } else {
+            self.buckets[n * BUCKETS / self.size] += 1;
+        }
+    }
+
+    pub fn min(&self) -> Option<usize> {
+        (self.reads > 0).then_some(self.min)
+    }
+
+    pub fn max(&self) -> Option<usize> {
+        (self.reads > 0).then_some(self.max)
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        (self.reads > 0).then(|| self.total as f64 / self.reads as f64)
+    }
+
+    /// Whether most reads filled the buffer, so data may be waiting (or lost) behind it
+    pub fn saturated(&self) -> bool {
+        self.full * 2 > self.reads
+    }
+}
+
+impl fmt::Display for BufferStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (min, mean, max) = match (self.min(), self.mean(), self.max()) {
+            (Some(min), Some(mean), Some(max)) => (min, mean, max),
+            _ => return write!(f, "Buffer stats: nothing was read"),
+        };
+        write!(
+            f,
+            "Buffer stats: {} reads into {} bytes, min {} mean {:.1} max {}",
+            self.reads, self.size, min, mean, max
+        )?;
+        let percent = |count: u64| count as f64 * 100.0 / self.reads as f64;
+        for (i, &count) in self.buckets.iter().enumerate() {
+            write!(
+                f,
+                "\n  {:>3}-{:>3}% full {:>10} ({:.1}%)",
+                i * 100 / BUCKETS,
+                (i + 1) * 100 / BUCKETS,
+                count,
+                percent(count)
+            )?;
+        }
+        write!(
+            f,
+            "\n     100% full {:>10} ({:.1}%)",
+            self.full,
+            percent(self.full)
+        )?;
+        if self.saturated() {
+            write!(
+                f,
+                "\nMost reads filled the buffer, a larger --capacity may keep up better"
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn min_mean_and_max_of_the_read_sizes() {
+        let mut stats = BufferStats::new(64);
+        for n in [10, 0, 64, 3, 0, 31] {
+            stats.add(n);
+        }
+        assert_eq!(stats.min(), Some(3));
+        assert_eq!(stats.max(), Some(64));
+        assert_eq!(stats.mean(), Some(27.0));
+        assert_eq!(stats.buckets, [2, 1, 0, 0]);
+        assert_eq!(stats.full, 1);
+        assert!(!stats.saturated());
+    }
+
+    #[test]
+    fn nothing_read_has_no_stats() {
+        let stats = BufferStats::new(64);
+        assert_eq!((stats.min(), stats.mean(), stats.max()), (None, None, None));
+        assert_eq!(stats.to_string(), "Buffer stats: nothing was read");
+    }
+
+    #[test]
+    fn mostly_full_reads_suggest_a_larger_buffer() {
+        let mut stats = BufferStats::new(8);
+        for n in [8, 8, 4] {
+            stats.add(n);
+        }
+        assert!(stats.saturated());
+        let text = stats.to_string();
+        assert!(
+            text.starts_with("Buffer stats: 3 reads into 8 bytes, min 4 mean 6.7 max 8\n"),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains("\n   50- 75% full          1 (33.3%)"),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains("\n     100% full          2 (66.7%)"),
+            "{}",
+            text
+        );
+        assert!(
+            text.ends_with("a larger --capacity may keep up better"),
+            "{}",
+            text
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use std::io::{self, BufWriter, LineWriter, Write};
+use std::str::FromStr;
+
+/// When written data is passed on to the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Buffering {
+    /// After every newline, for watching a terminal
+    Line,
+    /// Once `--capacity` bytes have built up, for files and pipes
+    Block,
+    /// Straight away
+    None,
+}
+
+impl Buffering {
+    /// Line buffered when a person is watching the output, block buffered otherwise
+    pub fn default_for(terminal: bool) -> Self {
+        if terminal {
+            Buffering::Line
+        } else {
+            Buffering::Block
+        }
+    }
+}
+
+impl FromStr for Buffering {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "line" => Buffering::Line,
+            "block" => Buffering::Block,
+            "none" => Buffering::None,
+            _ => return Err("Buffering should be line, block or none"),
+        })
+    }
+}
+
+/// An output buffered the way `Buffering` says
+pub enum Buffered<W: Write> {
+    Line(LineWriter<W>),
+    Block(BufWriter<W>),
+    None(W),
+}
+
+impl<W: Write> Buffered<W> {
+    pub fn new(buffering: Buffering, capacity: usize, inner: W) -> Self {
+        match buffering {
+            Buffering::Line => Buffered::Line(LineWriter::with_capacity(capacity, inner)),
+            Buffering::Block => Buffered::Block(BufWriter::with_capacity(capacity, inner)),
+            Buffering::None => Buffered::None(inner),
+        }
+    }
+}
+
+impl<W: Write> Write for Buffered<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Buffered::Line(w) => w.write(buf),
+            Buffered::Block(w) => w.write(buf),
+            Buffered::None(w) => {
+                let n = w.write(buf)?;
+                w.flush()?;
+                Ok(n)
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Buffered::Line(w) => w.flush(),
+            Buffered::Block(w) => w.flush(),
+            Buffered::None(w) => w.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Keeps what reached it, and how often it was flushed
+    #[derive(Default, Clone)]
+    struct Recorder {
+        written: Rc<RefCell<Vec<u8>>>,
+        flushes: Rc<RefCell<usize>>,
+    }
+
+    impl Write for Recorder {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            *self.flushes.borrow_mut() += 1;
+            Ok(())
+        }
+    }
+
+    fn recorded(recorder: &Recorder) -> Vec<u8> {
+        recorder.written.borrow().clone()
+    }
+
+    #[test]
+    fn line_buffering_passes_on_each_complete_line() {
+        let recorder = Recorder::default();
+        let mut out = Buffered::new(Buffering::Line, 64, recorder.clone());
+        out.write_all(b"part").unwrap();
+        assert_eq!(recorded(&recorder), b"");
+        out.write_all(b"ial\nnext").unwrap();
+        assert_eq!(recorded(&recorder), b"partial\n");
+        out.flush().unwrap();
+        assert_eq!(recorded(&recorder), b"partial\nnext");
+    }
+
+    #[test]
+    fn block_buffering_waits_for_the_capacity() {
+        let recorder = Recorder::default();
+        let mut out = Buffered::new(Buffering::Block, 8, recorder.clone());
+        out.write_all(b"one\ntwo").unwrap();
+        assert_eq!(recorded(&recorder), b"");
+        // Full, so what was held goes out and the new data waits in its place
+        out.write_all(b"\nthree\n").unwrap();
+        assert_eq!(recorded(&recorder), b"one\ntwo");
+        assert_eq!(*recorder.flushes.borrow(), 0);
+        drop(out);
+        assert_eq!(recorded(&recorder), b"one\ntwo\nthree\n");
+    }
+
+    #[test]
+    fn no_buffering_writes_and_flushes_straight_away() {
+        let recorder = Recorder::default();
+        let mut out = Buffered::new(Buffering::None, 64, recorder.clone());
+        out.write_all(b"a").unwrap();
+        out.write_all(b"b").unwrap();
+        assert_eq!(recorded(&recorder), b"ab");
+        assert_eq!(*recorder.flushes.borrow(), 2);
+    }
+
+    #[test]
+    fn the_default_depends_on_who_is_watching() {
+        assert_eq!(Buffering::default_for(true), Buffering::Line);
+        assert_eq!(Buffering::default_for(false), Buffering::Block);
+        assert_eq!("LINE".parse(), Ok(Buffering::Line));
+        assert_eq!("none".parse(), Ok(Buffering::None));
+        assert!("full".parse::<Buffering>().is_err());
+    }
+}
@@ -0,0 +1,103 @@
+use std::fmt;
+
+use crate::escape;
+
+/// Parse a `--map` spec of comma separated `FROM=TO` pairs
+/// FROM is a byte in decimal or 0xNN, TO is a string which may use escapes
+pub fn parse_map(spec: &str) -> Result<Vec<(u8, Vec<u8>)>, String> {
+    spec.split(',')
+        .map(|pair| {
+            let (from, to) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("map entry \"{}\" should look like FROM=TO", pair))?;
+            Ok((escape::parse_byte(from.trim())?, escape::unescape(to)?))
+        })
+        .collect()
+}
+
+/// Replaces received bytes with strings, bytes without an entry pass through unchanged
+pub struct ByteMap {
+    table: [Option<Vec<u8>>; 256],
+}
+
+impl ByteMap {
+    /// Later entries for the same byte replace earlier ones
+    pub fn new(entries: &[(u8, Vec<u8>)]) -> Self {
+        let mut table: [Option<Vec<u8>>; 256] = std::array::from_fn(|_| None);
+        for (from, to) in entries {
+            table[*from as usize] = Some(to.clone());
+        }
+        ByteMap { table }
+    }
+
+    /// Append `data` to `out` with every mapped byte substituted
+    pub fn apply(&self, data: &[u8], out: &mut Vec<u8>) {
+        for &b in data {
+            match &self.table[b as usize] {
+                Some(to) => out.extend_from_slice(to),
+                None => out.push(b),
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ByteMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entries = self
+            .table
+            .iter()
+            .enumerate()
+            .filter_map(|(b, to)| Some((b, String::from_utf8_lossy(to.as_ref()?))));
+        f.debug_map().entries(entries).finish()
+    }
+}
+
+/// Clear the high bit of every byte, where 7 bit data read as 8 bits carries its parity
+pub fn strip_parity(data: &mut [u8]) {
+    for b in data {
+        *b &= 0x7f;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_specs_parse() {
+        assert_eq!(
+            parse_map("0x02=<STX>,3=<ETX>"),
+            Ok(vec![(2, b"<STX>".to_vec()), (3, b"<ETX>".to_vec())])
+        );
+        assert_eq!(parse_map("65=\\n"), Ok(vec![(65, b"\n".to_vec())]));
+        assert!(parse_map("0x02").is_err());
+        assert!(parse_map("256=x").is_err());
+    }
+
+    #[test]
+    fn the_table_holds_the_last_entry_per_byte() {
+        let map = ByteMap::new(&[(1, b"a".to_vec()), (2, b"b".to_vec()), (1, b"c".to_vec())]);
+        assert_eq!(map.table[1].as_deref(), Some(&b"c"[..]));
+        assert_eq!(map.table[2].as_deref(), Some(&b"b"[..]));
+        assert_eq!(map.table.iter().filter(|to| to.is_some()).count(), 2);
+    }
+
+    #[test]
+    fn mapped_bytes_are_substituted_and_the_rest_pass_through() {
+        let map = ByteMap::new(&parse_map("0x02=<STX>,0x03=<ETX>,0=").unwrap());
+        let mut out = Vec::new();
+        map.apply(b"\x02temp=21\x00\x03\n", &mut out);
+        assert_eq!(out, b"<STX>temp=21<ETX>\n");
+    }
+
+    #[test]
+    fn strip_parity_clears_the_high_bit_of_every_byte() {
+        // "Hi!\r\n" sent as 7E1 and read as 8 data bits, the parity lands in the top bit
+        let mut data = [0x48, 0xe9, 0xa1, 0x0d, 0x8a, 0xff, 0x80];
+        strip_parity(&mut data);
+        assert_eq!(data, [b'H', b'i', b'!', b'\r', b'\n', 0x7f, 0x00]);
+        let mut ascii = *b"plain text";
+        strip_parity(&mut ascii);
+        assert_eq!(&ascii, b"plain text");
+    }
+}
@@ -0,0 +1,97 @@
+use std::io::{self, Write};
+
+/// Whether a terminal of this `$TERM` can be asked to set the clipboard
+/// The Linux console and dumb terminals ignore OSC 52, and without a `$TERM` there is nothing to ask
+pub fn supported(term: Option<&str>) -> bool {
+    !matches!(term, None | Some("") | Some("dumb") | Some("linux"))
+}
+
+/// Where a copy went
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Copied {
+    /// Set on the system clipboard
+    System,
+    /// Handed to the terminal with OSC 52, which may ignore it
+    Terminal,
+}
+
+/// The system clipboard through arboard, with the terminal (OSC 52) as the fallback
+/// for when there is none to reach, e.g. over ssh or without a display server
+pub struct Clipboard {
+    system: Option<arboard::Clipboard>,
+}
+
+impl Clipboard {
+    /// Connect to the system clipboard, kept for the session as on X11 a copy only lasts as long as its owner
+    pub fn connect() -> Self {
+        Clipboard {
+            system: arboard::Clipboard::new().ok(),
+        }
+    }
+
+    /// Copy `text`, or `None` when neither the system clipboard nor the terminal `term_name` can take it
+    pub fn copy(
+        &mut self,
+        term: &mut impl Write,
+        term_name: Option<&str>,
+        text: &str,
+    ) -> io::Result<Option<Copied>> {
+        if let Some(system) = &mut self.system {
+            if system.set_text(text).is_ok() {
+                return Ok(Some(Copied::System));
+            }
+        }
+        if !supported(term_name) {
+            return Ok(None);
+        }
+        osc52(term, text)?;
+        Ok(Some(Copied::Terminal))
+    }
+}
+
+/// Put `text` on the clipboard through the terminal with an OSC 52 sequence
+/// This works over ssh and without a display server, as long as the terminal allows it
+pub fn osc52(term: &mut impl Write, text: &str) -> io::Result<()> {
+    write!(
+        term,
+        "\x1b]52;c;{}\x07",
+        crate::b64::encode(text.as_bytes())
+    )?;
+    term.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_real_terminals_are_asked_to_copy() {
+        assert!(supported(Some("xterm-256color")));
+        assert!(!supported(Some("dumb")));
+        assert!(!supported(Some("linux")));
+        assert!(!supported(Some("")));
+        assert!(!supported(None));
+    }
+
+    #[test]
+    fn osc52_sends_the_text_as_base64() {
+        let mut term = Vec::new();
+        osc52(&mut term, "hi").unwrap();
+        assert_eq!(term, b"\x1b]52;c;aGk=\x07");
+    }
+
+    #[test]
+    fn without_a_system_clipboard_the_terminal_is_asked() {
+        let mut clipboard = Clipboard { system: None };
+        let mut term = Vec::new();
+        assert_eq!(
+            clipboard.copy(&mut term, Some("xterm"), "hi").unwrap(),
+            Some(Copied::Terminal)
+        );
+        assert_eq!(term, b"\x1b]52;c;aGk=\x07");
+
+        let mut term = Vec::new();
+        assert_eq!(clipboard.copy(&mut term, Some("dumb"), "hi").unwrap(), None);
+        assert!(term.is_empty());
+    }
+}
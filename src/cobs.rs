@@ -0,0 +1,131 @@
+/// Decode one COBS frame (without its zero delimiter) into `out`
+/// Returns false if the frame is malformed, `out` is then left as it was
+pub fn decode(frame: &[u8], out: &mut Vec<u8>) -> bool {
+    let len = out.len();
+    let mut i = 0;
+    while i < frame.len() {
+        let code = frame[i] as usize;
+        if code == 0 || i + code > frame.len() {
+            out.truncate(len);
+            return false;
+        }
+        out.extend_from_slice(&frame[i + 1..i + code]);
+        i += code;
+        // A full 254 byte run has no zero after it, and the final zero is the delimiter
+        if code < 0xff && i < frame.len() {
+            out.push(0);
+        }
+    }
+    true
+}
+
+/// Splits a stream on zero bytes and decodes each frame, frames can span any number of reads
+pub struct CobsDecoder {
+    frame: Vec<u8>,
+    decoded: Vec<u8>,
+    pub invalid: usize,
+}
+
+impl CobsDecoder {
+    pub fn new() -> Self {
+        CobsDecoder {
+            frame: Vec::new(),
+            decoded: Vec::new(),
+            invalid: 0,
+        }
+    }
+
+    /// Hand the payload of every frame completed by `data` to `frame`
+    pub fn feed(&mut self, data: &[u8], mut frame: impl FnMut(&[u8])) {
+        for &b in data {
+            if b != 0 {
+                self.frame.push(b);
+                continue;
+            }
+            // Back to back delimiters are an empty frame, not an error
+            if !self.frame.is_empty() {
+                self.decoded.clear();
+                if decode(&self.frame, &mut self.decoded) {
+                    frame(&self.decoded);
+                } else {
+                    self.invalid += 1;
+                }
+            }
+            self.frame.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decoded(frame: &[u8]) -> Option<Vec<u8>> {
+        let mut out = Vec::new();
+        decode(frame, &mut out).then_some(out)
+    }
+
+    #[test]
+    fn known_frames_decode() {
+        // The examples from the COBS paper and Wikipedia, without the delimiter
+        assert_eq!(decoded(&[0x01, 0x01]), Some(vec![0x00]));
+        assert_eq!(decoded(&[0x01, 0x01, 0x01]), Some(vec![0x00, 0x00]));
+        assert_eq!(
+            decoded(&[0x01, 0x02, 0x11, 0x01]),
+            Some(vec![0x00, 0x11, 0x00])
+        );
+        assert_eq!(
+            decoded(&[0x03, 0x11, 0x22, 0x02, 0x33]),
+            Some(vec![0x11, 0x22, 0x00, 0x33])
+        );
+        assert_eq!(
+            decoded(&[0x05, 0x11, 0x22, 0x33, 0x44]),
+            Some(vec![0x11, 0x22, 0x33, 0x44])
+        );
+        assert_eq!(
+            decoded(&[0x02, 0x11, 0x01, 0x01, 0x01]),
+            Some(vec![0x11, 0x00, 0x00, 0x00])
+        );
+    }
+
+    #[test]
+    fn full_254_byte_runs_have_no_zero_after_them() {
+        let run: Vec<u8> = (0x01..=0xfe).collect();
+        assert_eq!(decoded(&[&[0xff][..], &run].concat()), Some(run.clone()));
+        assert_eq!(
+            decoded(&[&[0x01, 0xff][..], &run].concat()),
+            Some([&[0x00][..], &run].concat())
+        );
+        let longer: Vec<u8> = (0x01..=0xff).collect();
+        assert_eq!(
+            decoded(&[&[0xff][..], &run, &[0x02, 0xff]].concat()),
+            Some(longer)
+        );
+    }
+
+    #[test]
+    fn malformed_frames_are_rejected() {
+        // A code running past the end of the frame
+        assert_eq!(decoded(&[0x05, 0x11, 0x22]), None);
+        let mut out = vec![0xaa];
+        assert!(!decode(&[0x03, 0x11], &mut out));
+        assert_eq!(out, [0xaa]);
+    }
+
+    #[test]
+    fn frames_are_split_out_of_the_stream_across_reads() {
+        let mut cobs = CobsDecoder::new();
+        let mut frames = Vec::new();
+        let stream = [
+            0x03, 0x11, 0x22, 0x02, 0x33, 0x00, 0x00, 0x05, 0x11, 0x00, 0x01, 0x01,
+        ];
+        for chunk in stream.chunks(4) {
+            cobs.feed(chunk, |frame| frames.push(frame.to_vec()));
+        }
+        assert_eq!(frames, [vec![0x11, 0x22, 0x00, 0x33]]);
+        assert_eq!(cobs.invalid, 1);
+        // The last frame is complete once its delimiter arrives
+        cobs.feed(&[0x00], |frame| frames.push(frame.to_vec()));
+        assert_eq!(frames[1], [0x00]);
+    }
+}
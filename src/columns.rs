@@ -0,0 +1,116 @@
+use std::io::{self, Write};
+
+use crate::Encoder;
+
+/// Lines up the fields of delimited text lines, each column is as wide as the widest value seen in it
+/// Partial lines are held back until their newline arrives
+pub struct ColumnWriter {
+    delimiter: u8,
+    widths: Vec<usize>,
+    carry: Vec<u8>,
+}
+
+impl ColumnWriter {
+    pub fn new(delimiter: u8) -> Self {
+        ColumnWriter {
+            delimiter,
+            widths: Vec::new(),
+            carry: Vec::new(),
+        }
+    }
+
+    /// Pad the fields of one line (without its newline), widening the columns as needed
+    pub fn align(&mut self, line: &[u8]) -> String {
+        let line = String::from_utf8_lossy(line);
+        let line = line.strip_suffix('\r').unwrap_or(&line);
+        let delimiter = char::from(self.delimiter);
+
+        let fields: Vec<&str> = line.split(delimiter).map(str::trim).collect();
+        if self.widths.len() < fields.len() {
+            self.widths.resize(fields.len(), 0);
+        }
+        let mut aligned = String::new();
+        for (i, field) in fields.iter().enumerate() {
+            let width = &mut self.widths[i];
+            *width = (*width).max(field.chars().count());
+            if i > 0 {
+                aligned.push(delimiter);
+                aligned.push(' ');
+            }
+            aligned.push_str(&format!("{:>1$}", field, *width));
+        }
+        aligned
+    }
+}
+
+impl Encoder for ColumnWriter {
+    fn write(&mut self, data: &[u8], out: &mut dyn Write) -> io::Result<()> {
+        self.carry.extend_from_slice(data);
+
+        // Taken out while its lines are aligned rather than copying each line, as aligning needs self
+        let mut carry = std::mem::take(&mut self.carry);
+        let mut used = 0;
+        let mut result = Ok(());
+        while let Some(i) = carry[used..].iter().position(|&b| b == b'\n') {
+            result = writeln!(out, "{}", self.align(&carry[used..used + i]));
+            if result.is_err() {
+                break;
+            }
+            used += i + 1;
+        }
+
+        carry.drain(..used);
+        self.carry = carry;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aligned(reads: &[&[u8]]) -> String {
+        let mut writer = ColumnWriter::new(b',');
+        let mut out = Vec::new();
+        for data in reads {
+            writer.write(data, &mut out).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn columns_widen_to_the_widest_value_so_far() {
+        assert_eq!(
+            aligned(&[b"1,22,333\n", b"4444,5,6\n", b"7,8,9\n"]),
+            "1, 22, 333\n4444,  5,   6\n   7,  8,   9\n"
+        );
+    }
+
+    #[test]
+    fn rows_with_more_fields_add_columns() {
+        assert_eq!(
+            aligned(&[b"a,b\n", b"c , dd ,eee\r\n", b"f\n"]),
+            "a, b\nc, dd, eee\nf\n"
+        );
+    }
+
+    #[test]
+    fn partial_lines_wait_for_their_newline() {
+        let mut writer = ColumnWriter::new(b'\t');
+        let mut out = Vec::new();
+        writer.write(b"x\tyy", &mut out).unwrap();
+        assert!(out.is_empty());
+        writer.write(b"y\nxx\tz\n", &mut out).unwrap();
+        assert_eq!(out, b"x\t yyy\nxx\t   z\n");
+    }
+
+    #[test]
+    fn the_output_does_not_depend_on_how_the_reads_are_split() {
+        let data: &[u8] = b"1,22,333\r\n4444,5,6\n7,8\n,,\n";
+        let whole = aligned(&[data]);
+        let bytes: Vec<&[u8]> = data.chunks(1).collect();
+        assert_eq!(aligned(&bytes), whole);
+        let uneven: Vec<&[u8]> = data.chunks(5).collect();
+        assert_eq!(aligned(&uneven), whole);
+    }
+}
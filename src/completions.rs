@@ -0,0 +1,175 @@
+use std::str::FromStr;
+
+use crate::options::Known;
+
+/// The shells a completion script can be written for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl FromStr for Shell {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "bash" => Shell::Bash,
+            "zsh" => Shell::Zsh,
+            "fish" => Shell::Fish,
+            _ => return Err("Completions can be written for bash, zsh or fish"),
+        })
+    }
+}
+
+/// What kind of value an option takes, from its placeholder in the help
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Value {
+    None,
+    Port,
+    Path,
+    Other,
+}
+
+fn value(option: &Known) -> Value {
+    match option.value {
+        None => Value::None,
+        Some(_) if option.name == "--port" => Value::Port,
+        Some("PATH") => Value::Path,
+        Some(_) => Value::Other,
+    }
+}
+
+/// A completion script for `shell` covering `options`, port names come from `oxterm --list-ports`
+pub fn script(shell: Shell, options: &[Known]) -> String {
+    match shell {
+        Shell::Bash => bash(options),
+        Shell::Zsh => zsh(options),
+        Shell::Fish => fish(options),
+    }
+}
+
+fn names<'a>(options: &[Known<'a>], kind: Value) -> Vec<&'a str> {
+    options
+        .iter()
+        .filter(|o| value(o) == kind)
+        .map(|o| o.name)
+        .collect()
+}
+
+fn bash(options: &[Known]) -> String {
+    let all: Vec<_> = options.iter().map(|o| o.name).collect();
+    format!(
+        r#"_oxterm() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}" prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    case "$prev" in
+        -p|--port)
+            COMPREPLY=($(compgen -W "$(oxterm --list-ports 2>/dev/null)" -- "$cur"))
+            return ;;
+        {paths})
+            COMPREPLY=($(compgen -f -- "$cur"))
+            return ;;
+        {others})
+            return ;;
+    esac
+    COMPREPLY=($(compgen -W "{all}" -- "$cur"))
+}}
+complete -F _oxterm oxterm
+"#,
+        paths = names(options, Value::Path).join("|"),
+        others = names(options, Value::Other).join("|"),
+        all = all.join(" "),
+    )
+}
+
+fn zsh(options: &[Known]) -> String {
+    let mut script = String::from("#compdef oxterm\n\n_arguments \\\n");
+    for option in options {
+        let placeholder = option.value.unwrap_or("").replace(':', "\\:");
+        let line = match value(option) {
+            Value::None => format!("'{}'", option.name),
+            Value::Port => format!(
+                "'{}=[{}]:port:($(oxterm --list-ports 2>/dev/null))'",
+                option.name, placeholder
+            ),
+            Value::Path => format!("'{}=[{}]:file:_files'", option.name, placeholder),
+            Value::Other => format!("'{}=[{}]:{}: '", option.name, placeholder, placeholder),
+        };
+        script.push_str(&format!("    {} \\\n", line));
+    }
+    script.push_str("    && return 0\n");
+    script
+}
+
+fn fish(options: &[Known]) -> String {
+    let mut script = String::new();
+    for option in options {
+        let name = option.name.trim_start_matches('-');
+        let line = match value(option) {
+            Value::None => format!("complete -c oxterm -l {}", name),
+            Value::Port => format!(
+                "complete -c oxterm -l {} -x -a '(oxterm --list-ports 2>/dev/null)'",
+                name
+            ),
+            Value::Path => format!("complete -c oxterm -l {} -r -F", name),
+            Value::Other => format!(
+                "complete -c oxterm -l {} -x -d '{}'",
+                name,
+                option.value.unwrap_or("")
+            ),
+        };
+        script.push_str(&line);
+        script.push('\n');
+    }
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options;
+
+    #[test]
+    fn shells_are_parsed_ignoring_case() {
+        assert_eq!("Bash".parse(), Ok(Shell::Bash));
+        assert_eq!("zsh".parse(), Ok(Shell::Zsh));
+        assert_eq!("FISH".parse(), Ok(Shell::Fish));
+        assert!("tcsh".parse::<Shell>().is_err());
+    }
+
+    #[test]
+    fn bash_completes_every_option_and_ports_and_files() {
+        let options = options::known(crate::HELP);
+        let script = script(Shell::Bash, &options);
+        for name in [
+            "--port",
+            "--baud-rate",
+            "--output-file",
+            "--quiet",
+            "--completions",
+        ] {
+            assert!(script.contains(name), "{} is missing", name);
+        }
+        assert!(script.contains("oxterm --list-ports"));
+        assert!(script.contains("complete -F _oxterm oxterm"));
+        // --log takes a path, --baud-rate a number
+        let paths = script.lines().find(|l| l.contains("--log")).unwrap();
+        assert!(!paths.contains("--baud-rate"));
+    }
+
+    #[test]
+    fn zsh_and_fish_describe_the_values() {
+        let options = options::known(crate::HELP);
+        let zsh = script(Shell::Zsh, &options);
+        assert!(zsh.starts_with("#compdef oxterm"));
+        assert!(zsh.contains("'--baud-rate=[NUMBER]:NUMBER: '"));
+        assert!(zsh.contains("'--output-file=[PATH]:file:_files'"));
+        let fish = script(Shell::Fish, &options);
+        assert!(fish.contains("complete -c oxterm -l quiet\n"));
+        assert!(
+            fish.contains("complete -c oxterm -l port -x -a '(oxterm --list-ports 2>/dev/null)'")
+        );
+        assert_eq!(fish.lines().count(), options.len());
+    }
+}
@@ -0,0 +1,140 @@
+use std::io::Write;
+use std::str::FromStr;
+
+use crc::{Crc, CRC_16_IBM_3740, CRC_32_ISO_HDLC};
+
+use crate::Endian;
+
+const CRC16_CCITT: Crc<u16> = Crc::<u16>::new(&CRC_16_IBM_3740);
+const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+/// The CRC carried at the end of every frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// CRC-16/CCITT-FALSE, initial value 0xffff
+    Crc16Ccitt,
+    /// The CRC-32 of Ethernet and zlib
+    Crc32,
+}
+
+impl Algorithm {
+    /// Bytes the CRC takes at the end of the frame
+    pub fn size(self) -> usize {
+        match self {
+            Algorithm::Crc16Ccitt => 2,
+            Algorithm::Crc32 => 4,
+        }
+    }
+
+    pub fn checksum(self, data: &[u8]) -> u32 {
+        match self {
+            Algorithm::Crc16Ccitt => u32::from(CRC16_CCITT.checksum(data)),
+            Algorithm::Crc32 => CRC32.checksum(data),
+        }
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "crc16-ccitt" | "crc16" => Algorithm::Crc16Ccitt,
+            "crc32" => Algorithm::Crc32,
+            _ => return Err("CRC should be crc16-ccitt or crc32"),
+        })
+    }
+}
+
+/// Checks the trailing CRC of each frame and counts the bad ones
+pub struct FrameCheck {
+    algorithm: Algorithm,
+    endian: Endian,
+    pub frames: usize,
+    pub bad: usize,
+}
+
+impl FrameCheck {
+    pub fn new(algorithm: Algorithm, endian: Endian) -> Self {
+        FrameCheck {
+            algorithm,
+            endian,
+            frames: 0,
+            bad: 0,
+        }
+    }
+
+    /// Split off the payload and say whether its CRC matches, a frame too short to hold one is bad
+    pub fn check<'a>(&mut self, frame: &'a [u8]) -> (&'a [u8], bool) {
+        self.frames += 1;
+        let size = self.algorithm.size();
+        if frame.len() < size {
+            self.bad += 1;
+            return (frame, false);
+        }
+        let (payload, crc) = frame.split_at(frame.len() - size);
+        let mut received = 0u32;
+        let mut add = |&b: &u8| received = received << 8 | u32::from(b);
+        match self.endian {
+            Endian::Little => crc.iter().rev().for_each(&mut add),
+            Endian::Big => crc.iter().for_each(&mut add),
+        }
+        let ok = received == self.algorithm.checksum(payload);
+        self.bad += !ok as usize;
+        (payload, ok)
+    }
+
+    /// Write the frame's payload as one line of hex (or base64) ending in OK or BAD
+    pub fn annotate(&mut self, frame: &[u8], base64: bool, out: &mut Vec<u8>) {
+        let (payload, ok) = self.check(frame);
+        if base64 {
+            write!(out, "{} ", crate::b64::encode(payload)).unwrap();
+        } else {
+            for b in payload {
+                write!(out, "{:02x} ", b).unwrap();
+            }
+        }
+        out.extend_from_slice(if ok { b"OK\n" } else { b"BAD\n" });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums_match_the_check_values() {
+        assert_eq!(Algorithm::Crc16Ccitt.checksum(b"123456789"), 0x29b1);
+        assert_eq!(Algorithm::Crc32.checksum(b"123456789"), 0xcbf4_3926);
+        assert_eq!("CRC16".parse(), Ok(Algorithm::Crc16Ccitt));
+        assert!("crc8".parse::<Algorithm>().is_err());
+    }
+
+    #[test]
+    fn good_and_corrupted_frames_are_told_apart() {
+        let mut check = FrameCheck::new(Algorithm::Crc16Ccitt, Endian::Big);
+        assert_eq!(check.check(b"123456789\x29\xb1"), (&b"123456789"[..], true));
+        assert_eq!(
+            check.check(b"123456780\x29\xb1"),
+            (&b"123456780"[..], false)
+        );
+        assert_eq!(check.check(b"\x29"), (&b"\x29"[..], false));
+        assert_eq!((check.frames, check.bad), (3, 2));
+
+        let mut check = FrameCheck::new(Algorithm::Crc32, Endian::Little);
+        assert!(check.check(b"123456789\x26\x39\xf4\xcb").1);
+        assert!(!check.check(b"123456789\xcb\xf4\x39\x26").1);
+    }
+
+    #[test]
+    fn frames_are_annotated_ok_or_bad() {
+        let mut check = FrameCheck::new(Algorithm::Crc16Ccitt, Endian::Big);
+        let mut out = Vec::new();
+        check.annotate(b"123456789\x29\xb1", false, &mut out);
+        check.annotate(b"12\x00\x00", true, &mut out);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "31 32 33 34 35 36 37 38 39 OK\nMTI= BAD\n"
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use std::io::{self, Write};
+
+use crate::{ConvertFrom, Encoder};
+
+/// Formats decoded values as comma separated rows of `group` values each
+/// Values can span reads so leftover bytes are carried into the next write
+pub struct CsvWriter {
+    convert: ConvertFrom,
+    group: usize,
+    timestamp: Option<String>,
+    label: Option<String>,
+    carry: Vec<u8>,
+    column: usize,
+}
+
+impl CsvWriter {
+    pub fn new(
+        convert: ConvertFrom,
+        group: usize,
+        timestamp: Option<String>,
+        label: Option<String>,
+    ) -> Self {
+        CsvWriter {
+            convert,
+            group: group.max(1),
+            timestamp,
+            label,
+            carry: Vec::new(),
+            column: 0,
+        }
+    }
+
+    pub fn header(&self, names: &[String], out: &mut dyn Write) -> io::Result<()> {
+        if self.timestamp.is_some() {
+            out.write_all(b"timestamp,")?;
+        }
+        if self.label.is_some() {
+            out.write_all(b"label,")?;
+        }
+        writeln!(out, "{}", names.join(","))
+    }
+}
+
+impl Encoder for CsvWriter {
+    fn write(&mut self, data: &[u8], out: &mut dyn Write) -> io::Result<()> {
+        self.carry.extend_from_slice(data);
+
+        let mut values = self.carry.chunks_exact(self.convert.width());
+        for value in &mut values {
+            if self.column > 0 {
+                out.write_all(b",")?;
+            } else {
+                if let Some(format) = &self.timestamp {
+                    write!(out, "{},", crate::timestamp(format))?;
+                }
+                if let Some(label) = &self.label {
+                    write!(out, "{},", label)?;
+                }
+            }
+            self.convert.write_value(value, out)?;
+            self.column += 1;
+            if self.column == self.group {
+                out.write_all(b"\n")?;
+                self.column = 0;
+            }
+        }
+
+        let used = self.carry.len() - values.remainder().len();
+        self.carry.drain(..used);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn floats(values: &[f32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn header_names_the_timestamp_and_label_columns() {
+        let writer = CsvWriter::new(
+            ConvertFrom::FLT,
+            2,
+            Some(String::from("%H")),
+            Some("a".into()),
+        );
+        let mut out = Vec::new();
+        writer
+            .header(&[String::from("x"), String::from("y")], &mut out)
+            .unwrap();
+        assert_eq!(out, b"timestamp,label,x,y\n");
+
+        let writer = CsvWriter::new(ConvertFrom::FLT, 2, None, None);
+        let mut out = Vec::new();
+        writer.header(&[String::from("x")], &mut out).unwrap();
+        assert_eq!(out, b"x\n");
+    }
+
+    #[test]
+    fn flt_values_are_grouped_into_rows_across_reads() {
+        let mut writer = CsvWriter::new(ConvertFrom::FLT, 3, None, None);
+        let data = floats(&[1.5, -2.0, 0.25, 4.0, 5.5]);
+        let mut out = Vec::new();
+        writer.write(&data[..6], &mut out).unwrap();
+        writer.write(&data[6..], &mut out).unwrap();
+        assert_eq!(out, b"1.5,-2,0.25\n4,5.5");
+        writer.write(&floats(&[6.0]), &mut out).unwrap();
+        assert_eq!(out, b"1.5,-2,0.25\n4,5.5,6\n");
+    }
+
+    #[test]
+    fn rows_start_with_the_timestamp_and_label() {
+        // A format without any fields stamps every row the same
+        let mut writer = CsvWriter::new(
+            ConvertFrom::FLT,
+            2,
+            Some(String::from("now")),
+            Some("dev".into()),
+        );
+        let mut out = Vec::new();
+        writer
+            .write(&floats(&[1.0, 2.0, 3.0, 4.0]), &mut out)
+            .unwrap();
+        assert_eq!(out, b"now,dev,1,2\nnow,dev,3,4\n");
+    }
+}
@@ -0,0 +1,179 @@
+use std::io::{self, stdout, Read, Write};
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, Clear, ClearType};
+use crossterm::{cursor, queue};
+
+use crate::format::{Format, Number};
+use crate::terminal::TerminalGuard;
+use crate::Args;
+
+/// The latest value of every field of a `--format` record and when it arrived
+pub struct Dashboard {
+    fields: Vec<(String, Option<(String, Instant)>)>,
+}
+
+impl Dashboard {
+    pub fn new(names: &[&str]) -> Self {
+        Dashboard {
+            fields: names.iter().map(|name| (name.to_string(), None)).collect(),
+        }
+    }
+
+    /// Take the values of a decoded record received at `now`
+    pub fn update(&mut self, values: &[(&str, Number)], now: Instant) {
+        for (name, value) in values {
+            if let Some((_, latest)) = self.fields.iter_mut().find(|(n, _)| n == name) {
+                *latest = Some((value.to_string(), now));
+            }
+        }
+    }
+
+    /// One line per field with its latest value and how long ago that arrived
+    pub fn rows(&self, now: Instant) -> Vec<String> {
+        let width = self
+            .fields
+            .iter()
+            .map(|(name, _)| name.chars().count())
+            .max()
+            .unwrap_or(0);
+        self.fields
+            .iter()
+            .map(|(name, latest)| match latest {
+                Some((value, at)) => format!(
+                    "{:>width$}  {:<16} {:.1}s ago",
+                    name,
+                    value,
+                    (now - *at).as_secs_f64(),
+                    width = width
+                ),
+                None => format!("{:>width$}  {:<16} never", name, "-", width = width),
+            })
+            .collect()
+    }
+}
+
+/// Show the fields of the `--format` records received on the port in place, until Ctrl-C or q
+pub fn run<P: Read>(mut port: P, args: &Args, format: &Format) -> io::Result<()> {
+    let _guard = TerminalGuard::fullscreen()?;
+    let mut term = stdout();
+    let mut dashboard = Dashboard::new(&format.names());
+    let mut buf = vec![0; args.capacity];
+    let mut carry = Vec::new();
+    let mut drawn = Instant::now() - Duration::from_secs(1);
+    let mut dirty = true;
+
+    while !crate::STOP.load(std::sync::atomic::Ordering::Relaxed) {
+        if event::poll(Duration::from_millis(10))? {
+            if let Event::Key(key) = event::read()? {
+                let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                match key.code {
+                    _ if key.kind == KeyEventKind::Release => {}
+                    KeyCode::Char('c') if ctrl => break,
+                    KeyCode::Char('q') | KeyCode::Esc => break,
+                    _ => {}
+                }
+            }
+            dirty = true;
+        }
+
+        match port.read(&mut buf[..args.read_size()]) {
+            Ok(n) => {
+                carry.extend_from_slice(&buf[..n]);
+                let now = Instant::now();
+                let mut records = carry.chunks_exact(format.width());
+                for record in &mut records {
+                    dashboard.update(&format.decode(record, args.endian, args.raw_values), now);
+                    dirty = true;
+                }
+                let used = carry.len() - records.remainder().len();
+                carry.drain(..used);
+            }
+            Err(ref e) if crate::severity(e) == crate::Severity::Recoverable => {}
+            Err(e) => return Err(e),
+        }
+
+        // Redrawn at least once a second so the ages keep counting
+        let now = Instant::now();
+        if dirty || now - drawn >= Duration::from_secs(1) {
+            draw(&mut term, args, &dashboard.rows(now))?;
+            drawn = now;
+            dirty = false;
+        }
+    }
+    Ok(())
+}
+
+fn draw(term: &mut impl Write, args: &Args, rows: &[String]) -> io::Result<()> {
+    let (width, height) = terminal::size()?;
+    let width = width as usize;
+    let title = format!("{} | Ctrl-C or q quits", args.settings());
+    let title: String = format!("{:width$}", title, width = width)
+        .chars()
+        .take(width)
+        .collect();
+    queue!(
+        term,
+        cursor::MoveTo(0, 0),
+        SetAttribute(Attribute::Reverse),
+        Print(title),
+        SetAttribute(Attribute::Reset),
+    )?;
+    for row in 1..height {
+        queue!(term, cursor::MoveTo(0, row), Clear(ClearType::CurrentLine))?;
+        if let Some(line) = rows.get(row as usize - 1) {
+            let line: String = line.chars().take(width).collect();
+            queue!(term, Print(line))?;
+        }
+    }
+    term.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Endian;
+
+    #[test]
+    fn decoded_records_update_the_fields_in_place() {
+        let format =
+            crate::format::parse("u8 id; u16 temp *0.5; u8 flags { ok:0, err:1 }").unwrap();
+        let start = Instant::now();
+        let mut dashboard = Dashboard::new(&format.names());
+        assert_eq!(
+            dashboard.rows(start),
+            [
+                "  id  -                never",
+                "temp  -                never",
+                "  ok  -                never",
+                " err  -                never",
+            ]
+        );
+
+        dashboard.update(&format.decode(&[1, 0, 50, 0b01], Endian::Big, false), start);
+        let later = start + Duration::from_millis(1500);
+        dashboard.update(&format.decode(&[2, 0, 51, 0b10], Endian::Big, false), later);
+        assert_eq!(
+            dashboard.rows(later + Duration::from_millis(500)),
+            [
+                "  id  2                0.5s ago",
+                "temp  25.5             0.5s ago",
+                "  ok  0                0.5s ago",
+                " err  1                0.5s ago",
+            ]
+        );
+    }
+
+    #[test]
+    fn values_for_unknown_fields_are_ignored_and_the_others_go_stale() {
+        let start = Instant::now();
+        let mut dashboard = Dashboard::new(&["a", "b"]);
+        dashboard.update(&[("a", Number::Int(1)), ("zzz", Number::Int(9))], start);
+        assert_eq!(
+            dashboard.rows(start + Duration::from_secs(3)),
+            ["a  1                3.0s ago", "b  -                never"]
+        );
+    }
+}
@@ -0,0 +1,90 @@
+/// Keeps only every Nth value or line of a stream, values and lines can be split over any number of reads
+pub struct Decimate {
+    every: usize,
+    /// Bytes per value, or None to work on lines
+    width: Option<usize>,
+    index: usize,
+    offset: usize,
+    keeping: bool,
+}
+
+impl Decimate {
+    /// Keep one of every `every` values that are `width` bytes wide
+    pub fn values(every: usize, width: usize) -> Self {
+        Decimate::new(every, Some(width))
+    }
+
+    /// Keep one of every `every` newline terminated lines
+    pub fn lines(every: usize) -> Self {
+        Decimate::new(every, None)
+    }
+
+    fn new(every: usize, width: Option<usize>) -> Self {
+        Decimate {
+            every: every.max(1),
+            width,
+            index: 0,
+            offset: 0,
+            keeping: false,
+        }
+    }
+
+    /// Append the kept part of `data` to `out`, the first value or line is always kept
+    pub fn apply(&mut self, data: &[u8], out: &mut Vec<u8>) {
+        for &b in data {
+            if self.offset == 0 {
+                self.keeping = self.index.is_multiple_of(self.every);
+                self.index += 1;
+            }
+            if self.keeping {
+                out.push(b);
+            }
+            self.offset = match self.width {
+                Some(width) => (self.offset + 1) % width,
+                None if b == b'\n' => 0,
+                None => self.offset + 1,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_value_in_n_is_kept() {
+        let mut d = Decimate::values(3, 2);
+        let mut out = Vec::new();
+        d.apply(&[0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6], &mut out);
+        assert_eq!(out, [0, 0, 3, 3, 6, 6]);
+    }
+
+    #[test]
+    fn values_split_over_reads_are_kept_whole() {
+        let mut d = Decimate::values(2, 4);
+        let mut out = Vec::new();
+        for chunk in [1u8, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3].chunks(3) {
+            d.apply(chunk, &mut out);
+        }
+        assert_eq!(out, [1, 1, 1, 1, 3, 3, 3, 3]);
+    }
+
+    #[test]
+    fn one_line_in_n_is_kept() {
+        let mut d = Decimate::lines(2);
+        let mut out = Vec::new();
+        d.apply(b"a\nbb\nc", &mut out);
+        d.apply(b"cc\ndddd\ne\n", &mut out);
+        assert_eq!(out, b"a\nccc\ne\n");
+    }
+
+    #[test]
+    fn every_zero_or_one_keeps_everything() {
+        for every in [0, 1] {
+            let mut out = Vec::new();
+            Decimate::lines(every).apply(b"a\nb\n", &mut out);
+            assert_eq!(out, b"a\nb\n");
+        }
+    }
+}
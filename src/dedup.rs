@@ -0,0 +1,88 @@
+use std::time::{Duration, Instant};
+
+/// How often a run of the same error is summed up
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// Collapses a run of identical errors into `last error repeated N times`, at most once a second
+pub struct Dedup {
+    last: Option<String>,
+    repeats: u64,
+    reported: Instant,
+}
+
+impl Dedup {
+    pub fn new(start: Instant) -> Self {
+        Dedup {
+            last: None,
+            repeats: 0,
+            reported: start,
+        }
+    }
+
+    /// The lines to print for `message` happening at `now`, often none
+    pub fn message(&mut self, message: String, now: Instant) -> Vec<String> {
+        let mut lines = Vec::new();
+        if self.last.as_ref() == Some(&message) {
+            self.repeats += 1;
+            if now - self.reported >= INTERVAL {
+                lines.extend(self.repeated(now));
+            }
+        } else {
+            lines.extend(self.repeated(now));
+            lines.push(message.clone());
+            self.last = Some(message);
+            self.reported = now;
+        }
+        lines
+    }
+
+    /// The count of repeats not yet printed, if there are any
+    pub fn repeated(&mut self, now: Instant) -> Option<String> {
+        if self.repeats == 0 {
+            return None;
+        }
+        self.reported = now;
+        let repeats = std::mem::replace(&mut self.repeats, 0);
+        Some(format!("last error repeated {} times", repeats))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_burst_of_one_error_is_collapsed() {
+        let start = Instant::now();
+        let mut dedup = Dedup::new(start);
+        let mut printed = Vec::new();
+        // 2500 errors over 2.5s
+        for i in 0..2500 {
+            let now = start + Duration::from_millis(i);
+            printed.extend(dedup.message(String::from("Error: timed out"), now));
+        }
+        printed.extend(dedup.repeated(start + Duration::from_millis(2500)));
+        assert_eq!(
+            printed,
+            [
+                "Error: timed out",
+                "last error repeated 1000 times",
+                "last error repeated 1000 times",
+                "last error repeated 499 times",
+            ]
+        );
+    }
+
+    #[test]
+    fn distinct_errors_are_kept_separate() {
+        let start = Instant::now();
+        let mut dedup = Dedup::new(start);
+        let mut message = |text: &str| dedup.message(String::from(text), start);
+        assert_eq!(message("a"), ["a"]);
+        assert!(message("a").is_empty());
+        assert!(message("a").is_empty());
+        assert_eq!(message("b"), ["last error repeated 2 times", "b"]);
+        assert_eq!(message("a"), ["a"]);
+        assert_eq!(dedup.repeated(start), None);
+    }
+}
@@ -0,0 +1,90 @@
+use serialport::{SerialPort, SerialPortInfo};
+
+use crate::signals::Signals;
+
+/// Everything the port will tell us about itself, one `name: value` line each
+/// `info` is what the OS listed for it, with the USB descriptor if there is one
+pub fn describe(port: &mut dyn SerialPort, info: Option<&SerialPortInfo>) -> Vec<String> {
+    let show = |value: serialport::Result<String>| value.unwrap_or_else(|e| format!("? ({})", e));
+    let mut lines = vec![
+        format!(
+            "Port: {}",
+            info.map_or_else(|| port.name().unwrap_or_default(), crate::picker::describe)
+        ),
+        format!(
+            "Baud rate: {}",
+            show(port.baud_rate().map(|b| b.to_string()))
+        ),
+        format!(
+            "Data bits: {}",
+            show(port.data_bits().map(|d| format!("{:?}", d)))
+        ),
+        format!(
+            "Parity: {}",
+            show(port.parity().map(|p| format!("{:?}", p)))
+        ),
+        format!(
+            "Stop bits: {}",
+            show(port.stop_bits().map(|s| format!("{:?}", s)))
+        ),
+        format!(
+            "Flow control: {}",
+            show(port.flow_control().map(|f| format!("{:?}", f)))
+        ),
+    ];
+    lines.push(format!(
+        "Waiting: {} bytes to read, {} bytes to write",
+        show(port.bytes_to_read().map(|n| n.to_string())),
+        show(port.bytes_to_write().map(|n| n.to_string()))
+    ));
+    lines.push(format!("Signals: {}", Signals::read(port)));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockPort;
+    use serialport::{Parity, SerialPortType, UsbPortInfo};
+
+    #[test]
+    fn the_settings_lines_and_usb_details_are_listed() {
+        let mut port = MockPort::with_input(b"abc");
+        {
+            let mut state = port.state();
+            state.baud_rate = 57600;
+            state.parity = Parity::Even;
+            state.cts = true;
+            state.cd = true;
+        }
+        let info = SerialPortInfo {
+            port_name: String::from("/dev/ttyUSB0"),
+            port_type: SerialPortType::UsbPort(UsbPortInfo {
+                vid: 0x0403,
+                pid: 0x6001,
+                serial_number: Some(String::from("A10K")),
+                manufacturer: Some(String::from("FTDI")),
+                product: Some(String::from("FT232R")),
+            }),
+        };
+        assert_eq!(
+            describe(&mut port, Some(&info)),
+            [
+                "Port: /dev/ttyUSB0 (USB 0403:6001 FTDI FT232R serial A10K)",
+                "Baud rate: 57600",
+                "Data bits: Eight",
+                "Parity: Even",
+                "Stop bits: One",
+                "Flow control: None",
+                "Waiting: 3 bytes to read, 0 bytes to write",
+                "Signals: CTS high, DSR low, RI low, CD high",
+            ]
+        );
+    }
+
+    #[test]
+    fn without_os_details_the_port_gives_its_own_name() {
+        let lines = describe(&mut MockPort::default(), None);
+        assert_eq!(lines[0], "Port: mock");
+    }
+}
@@ -0,0 +1,57 @@
+use std::ffi::OsString;
+
+use crate::options;
+
+/// Every option can also be set through `OXTERM_<NAME>`, e.g. `OXTERM_BAUD_RATE` for `--baud-rate`
+const PREFIX: &str = "OXTERM_";
+
+/// Shorter names for the settings people reach for most
+const ALIASES: &[(&str, &str)] = &[("BAUD", "--baud-rate")];
+
+/// The option an `OXTERM_` variable sets, `OXTERM_MAX_BYTES` sets `--max-bytes`
+pub fn option(var: &str) -> Option<String> {
+    let name = var.strip_prefix(PREFIX)?;
+    if let Some((_, option)) = ALIASES.iter().find(|(alias, _)| *alias == name) {
+        return Some(option.to_string());
+    }
+    Some(format!("--{}", name.to_ascii_lowercase().replace('_', "-")))
+}
+
+/// The arguments the `OXTERM_` variables amount to, sorted so they don't depend on the environment's order
+/// A switch is turned on by 1, true, yes or on and left off by 0, false, no, off or nothing
+pub fn args(
+    vars: impl Iterator<Item = (OsString, OsString)>,
+    help: &str,
+) -> Result<Vec<String>, String> {
+    let known = options::known(help);
+    let mut vars: Vec<(String, String)> = vars
+        .filter_map(|(var, value)| Some((var.into_string().ok()?, value)))
+        .filter(|(var, _)| var.starts_with(PREFIX))
+        .map(|(var, value)| {
+            let value = value
+                .into_string()
+                .map_err(|_| format!("{} isn't valid UTF-8", var))?;
+            Ok((var, value))
+        })
+        .collect::<Result<_, String>>()?;
+    vars.sort();
+
+    let mut args = Vec::new();
+    for (var, value) in vars {
+        let name = option(&var).unwrap();
+        let option = known
+            .iter()
+            .find(|k| k.name == name)
+            .ok_or_else(|| format!("{} doesn't match any option", var))?;
+        if option.value.is_some() {
+            args.push(format!("{}={}", name, value));
+            continue;
+        }
+        match value.to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => args.push(name),
+            "" | "0" | "false" | "no" | "off" => {}
+            _ => return Err(format!("{} is a switch, set it to 1 or 0", var)),
+        }
+    }
+    Ok(args)
+}
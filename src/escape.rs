@@ -0,0 +1,74 @@
+/// Decode the backslash escapes used for strings sent to the port
+/// Supports \n \r \t \0 \e (escape) \\ and \xNN for arbitrary bytes
+pub fn unescape(s: &str) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut utf8 = [0; 4];
+            out.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push(b'\n'),
+            Some('r') => out.push(b'\r'),
+            Some('t') => out.push(b'\t'),
+            Some('0') => out.push(0),
+            Some('e') => out.push(0x1b),
+            Some('\\') => out.push(b'\\'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                let byte = Some(&hex)
+                    .filter(|h| h.len() == 2 && h.chars().all(|c| c.is_ascii_hexdigit()))
+                    .and_then(|h| u8::from_str_radix(h, 16).ok())
+                    .ok_or_else(|| format!("invalid \\x escape \"\\x{}\"", hex))?;
+                out.push(byte);
+            }
+            Some(c) => return Err(format!("unknown escape \"\\{}\"", c)),
+            None => return Err(String::from("trailing backslash")),
+        }
+    }
+    Ok(out)
+}
+
+/// Parse a single byte given in decimal or as 0xNN
+pub fn parse_byte(s: &str) -> Result<u8, String> {
+    let parsed = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    parsed.map_err(|_| format!("\"{}\" is not a byte (0 to 255 or 0x00 to 0xff)", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unescape_decodes_every_escape() {
+        assert_eq!(
+            unescape("a\\n\\r\\t\\0\\e\\\\\\x7F\\xff").unwrap(),
+            b"a\n\r\t\0\x1b\\\x7f\xff"
+        );
+        assert_eq!(unescape("é").unwrap(), "é".as_bytes());
+    }
+
+    #[test]
+    fn unescape_rejects_bad_escapes() {
+        assert!(unescape("\\q").is_err());
+        assert!(unescape("\\x4").is_err());
+        assert!(unescape("\\xzz").is_err());
+        assert!(unescape("end\\").is_err());
+    }
+
+    #[test]
+    fn bytes_are_decimal_or_hex() {
+        assert_eq!(parse_byte("17"), Ok(0x11));
+        assert_eq!(parse_byte("0x13"), Ok(0x13));
+        assert_eq!(parse_byte("0XfF"), Ok(0xff));
+        assert!(parse_byte("256").is_err());
+        assert!(parse_byte("0x100").is_err());
+        assert!(parse_byte("-1").is_err());
+        assert!(parse_byte("x").is_err());
+    }
+}
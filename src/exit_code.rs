@@ -0,0 +1,81 @@
+/// How a session ended, as the exit status a script can check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    /// Quit, a limit was reached or the input ran out
+    Success,
+    /// Bad arguments, or anything else not covered below
+    Error,
+    /// The port couldn't be opened
+    OpenFailed,
+    /// Reading stopped working, like the port being unplugged
+    ReadFailed,
+    /// Writing the output stopped working, like the disk filling up
+    WriteFailed,
+    /// The idle timeout or duration ran out before any data arrived, with the status chosen for it
+    Timeout(u8),
+}
+
+/// What --timeout-exit-code is when not given
+pub const TIMEOUT: u8 = 5;
+
+impl ExitCode {
+    pub fn status(self) -> i32 {
+        match self {
+            ExitCode::Success => 0,
+            ExitCode::Error => 1,
+            ExitCode::OpenFailed => 2,
+            ExitCode::ReadFailed => 3,
+            ExitCode::WriteFailed => 4,
+            ExitCode::Timeout(status) => status as i32,
+        }
+    }
+}
+
+/// How a capture ended, from the failure that stopped it (if any),
+/// whether a time limit stopped it and how many bytes it got
+/// `timeout` is the status for running out of time with nothing
+pub fn ending(failure: Option<ExitCode>, timed_out: bool, bytes: usize, timeout: u8) -> ExitCode {
+    match failure {
+        Some(code) => code,
+        None if timed_out && bytes == 0 => ExitCode::Timeout(timeout),
+        None => ExitCode::Success,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_ending_has_its_own_status() {
+        let statuses: Vec<_> = [
+            ExitCode::Success,
+            ExitCode::Error,
+            ExitCode::OpenFailed,
+            ExitCode::ReadFailed,
+            ExitCode::WriteFailed,
+            ExitCode::Timeout(TIMEOUT),
+        ]
+        .iter()
+        .map(|code| code.status())
+        .collect();
+        assert_eq!(statuses, [0, 1, 2, 3, 4, 5]);
+        assert_eq!(ExitCode::Timeout(0).status(), 0);
+    }
+
+    #[test]
+    fn running_out_of_time_only_counts_without_data() {
+        assert_eq!(ending(None, false, 0, 7), ExitCode::Success);
+        assert_eq!(ending(None, true, 0, 7), ExitCode::Timeout(7));
+        assert_eq!(ending(None, true, 10, 7), ExitCode::Success);
+        // A failure wins over the timeout
+        assert_eq!(
+            ending(Some(ExitCode::ReadFailed), true, 0, 7),
+            ExitCode::ReadFailed
+        );
+        assert_eq!(
+            ending(Some(ExitCode::WriteFailed), false, 10, 7),
+            ExitCode::WriteFailed
+        );
+    }
+}
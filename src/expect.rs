@@ -0,0 +1,207 @@
+use std::io::{self, Read, Write};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use regex::bytes::Regex;
+
+use crate::escape::unescape;
+use crate::script::Eol;
+
+/// How long `expect` waits when the line doesn't say
+const DEFAULT_TIMEOUT: Duration = Duration::from_millis(5000);
+
+/// One line of an expect script
+#[derive(Debug)]
+pub enum Step {
+    Send(Vec<u8>),
+    Expect(Regex, Duration),
+}
+
+/// Parse an expect script made of `send TEXT` and `expect REGEX [TIMEOUT_MS]` lines
+/// TEXT takes the escapes of --macro and gets `eol` appended, blank lines and `#` comments are skipped
+pub fn parse(text: &str, eol: Eol) -> Result<Vec<Step>, String> {
+    let mut steps = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim_start();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |e: String| format!("line {}: {}", number + 1, e);
+        let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match command {
+            "send" => {
+                let mut bytes = unescape(rest).map_err(error)?;
+                bytes.extend_from_slice(eol.bytes());
+                steps.push(Step::Send(bytes));
+            }
+            "expect" => {
+                let rest = rest.trim_end();
+                // A trailing number is the timeout, anything else is part of the pattern
+                let (pattern, timeout) = match rest.rsplit_once(' ') {
+                    Some((pattern, ms)) if ms.parse::<u64>().is_ok() => {
+                        (pattern, Duration::from_millis(ms.parse().unwrap()))
+                    }
+                    _ => (rest, DEFAULT_TIMEOUT),
+                };
+                if pattern.is_empty() {
+                    return Err(error(String::from("expect needs a pattern")));
+                }
+                let re = Regex::new(pattern).map_err(|e| error(e.to_string()))?;
+                steps.push(Step::Expect(re, timeout));
+            }
+            _ => {
+                return Err(error(format!(
+                    "unknown command \"{}\", expected send or expect",
+                    command
+                )))
+            }
+        }
+    }
+    Ok(steps)
+}
+
+/// Run the steps against the port, copying everything received to `out`
+/// Fails with a message naming the pattern when an expect times out
+pub fn run<P: Read + Write>(
+    port: &mut P,
+    steps: &[Step],
+    out: &mut impl Write,
+) -> Result<(), String> {
+    let mut buf = [0; 1024];
+    // Received since the last match, so a match never reuses old data
+    let mut pending = Vec::new();
+    for step in steps {
+        match step {
+            Step::Send(bytes) => {
+                port.write_all(bytes).map_err(|e| e.to_string())?;
+                port.flush().map_err(|e| e.to_string())?;
+            }
+            Step::Expect(re, timeout) => {
+                let deadline = Instant::now() + *timeout;
+                loop {
+                    if let Some(end) = re.find(&pending).map(|m| m.end()) {
+                        pending.drain(..end);
+                        break;
+                    }
+                    if Instant::now() >= deadline {
+                        return Err(format!(
+                            "timed out after {}ms waiting for /{}/",
+                            timeout.as_millis(),
+                            re
+                        ));
+                    }
+                    match port.read(&mut buf) {
+                        Ok(0) => sleep(Duration::from_millis(5)),
+                        Ok(n) => {
+                            pending.extend_from_slice(&buf[..n]);
+                            out.write_all(&buf[..n]).map_err(|e| e.to_string())?;
+                            out.flush().map_err(|e| e.to_string())?;
+                        }
+                        Err(e) if e.kind() == io::ErrorKind::TimedOut => {}
+                        Err(e) => return Err(e.to_string()),
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Answers each command it is sent with the reply it was given for it
+    struct Responder {
+        replies: Vec<(&'static [u8], &'static [u8])>,
+        received: Vec<u8>,
+        unread: VecDeque<u8>,
+    }
+
+    impl Read for Responder {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.unread.is_empty() {
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+            // A byte at a time, so patterns are matched across reads
+            buf[0] = self.unread.pop_front().unwrap();
+            Ok(1)
+        }
+    }
+
+    impl Write for Responder {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.received.extend_from_slice(buf);
+            for (command, reply) in &self.replies {
+                if buf == *command {
+                    self.unread.extend(*reply);
+                }
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn responder() -> Responder {
+        Responder {
+            replies: vec![
+                (b"AT\r", b"AT\r\nOK\r\n"),
+                (b"AT+NAME=dev\r", b"+NAME:dev\r\nOK\r\n"),
+            ],
+            received: Vec::new(),
+            unread: VecDeque::new(),
+        }
+    }
+
+    #[test]
+    fn scripts_parse_into_steps() {
+        let steps = parse(
+            "# provisioning\nsend AT\\x21\n\n  expect ^OK 250\nexpect a b\n",
+            Eol::Lf,
+        )
+        .unwrap();
+        assert!(matches!(&steps[0], Step::Send(b) if b == b"AT!\n"));
+        assert!(
+            matches!(&steps[1], Step::Expect(re, t) if re.as_str() == "^OK" && t.as_millis() == 250)
+        );
+        assert!(
+            matches!(&steps[2], Step::Expect(re, t) if re.as_str() == "a b" && *t == DEFAULT_TIMEOUT)
+        );
+        assert_eq!(
+            parse("send a\nwait 5\n", Eol::Lf).unwrap_err(),
+            "line 2: unknown command \"wait\", expected send or expect"
+        );
+        assert!(parse("expect", Eol::Lf).is_err());
+        assert!(parse("expect (", Eol::Lf).is_err());
+    }
+
+    #[test]
+    fn a_two_step_exchange_runs_to_the_end() {
+        let steps = parse(
+            "send AT\nexpect OK\nsend AT+NAME=dev\nexpect \\+NAME:(\\w+)\nexpect OK",
+            Eol::Cr,
+        )
+        .unwrap();
+        let mut port = responder();
+        let mut out = Vec::new();
+        assert_eq!(run(&mut port, &steps, &mut out), Ok(()));
+        assert_eq!(port.received, b"AT\rAT+NAME=dev\r");
+        // Reading stops at the last match
+        assert_eq!(out, b"AT\r\nOK\r\n+NAME:dev\r\nOK");
+    }
+
+    #[test]
+    fn an_old_match_is_not_reused() {
+        // The second OK has to come from a second reply
+        let steps = parse("send AT\nexpect OK\nexpect OK 50", Eol::Cr).unwrap();
+        let mut port = responder();
+        assert_eq!(
+            run(&mut port, &steps, &mut Vec::new()),
+            Err(String::from("timed out after 50ms waiting for /OK/"))
+        );
+    }
+}
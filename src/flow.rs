@@ -0,0 +1,271 @@
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serialport::{ClearBuffer, DataBits, FlowControl, Parity, SerialPort, StopBits};
+
+/// Whether the device asked us to pause, and what was written since
+/// Shared by a port and its clones so an XOFF read by one holds back the writes of all of them
+#[derive(Default)]
+struct State {
+    paused: bool,
+    pending: Vec<u8>,
+}
+
+/// XON/XOFF flow control done by hand so the flow characters can be anything
+/// Receiving XOFF holds back everything written until XON arrives,
+/// and neither character is passed on to the reader
+pub struct SoftFlow<P> {
+    inner: P,
+    xon: u8,
+    xoff: u8,
+    state: Arc<Mutex<State>>,
+}
+
+impl<P: Read + Write> SoftFlow<P> {
+    pub fn new(inner: P, xon: u8, xoff: u8) -> Self {
+        SoftFlow {
+            inner,
+            xon,
+            xoff,
+            state: Arc::default(),
+        }
+    }
+
+    /// Another handle on the same port writing through `inner`, paused whenever this one is
+    pub fn share<Q: Read + Write>(&self, inner: Q) -> SoftFlow<Q> {
+        SoftFlow {
+            inner,
+            xon: self.xon,
+            xoff: self.xoff,
+            state: Arc::clone(&self.state),
+        }
+    }
+
+    /// Update the pause state from received bytes, removing the flow characters
+    /// Returns how many data bytes are left at the start of `data`
+    pub fn receive(&mut self, data: &mut [u8]) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let mut kept = 0;
+        for i in 0..data.len() {
+            match data[i] {
+                b if b == self.xoff => state.paused = true,
+                b if b == self.xon => state.paused = false,
+                b => {
+                    data[kept] = b;
+                    kept += 1;
+                }
+            }
+        }
+        kept
+    }
+
+    /// Send what was held back once the device is ready for it
+    /// Every write goes through here with the state locked, so they stay in order
+    fn send_pending(inner: &mut P, state: &mut State) -> io::Result<()> {
+        if !state.paused && !state.pending.is_empty() {
+            inner.write_all(&state.pending)?;
+            state.pending.clear();
+        }
+        Ok(())
+    }
+}
+
+impl<P: Read + Write> Read for SoftFlow<P> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.inner.read(buf)?;
+            let kept = self.receive(&mut buf[..n]);
+            SoftFlow::send_pending(&mut self.inner, &mut self.state.lock().unwrap())?;
+            // Only flow characters arrived, so there is nothing to hand out yet
+            if kept > 0 || n == 0 {
+                return Ok(kept);
+            }
+        }
+    }
+}
+
+impl<P: Read + Write> Write for SoftFlow<P> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.state.lock().unwrap();
+        if state.paused {
+            state.pending.extend_from_slice(buf);
+            return Ok(buf.len());
+        }
+        SoftFlow::send_pending(&mut self.inner, &mut state)?;
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        SoftFlow::send_pending(&mut self.inner, &mut self.state.lock().unwrap())?;
+        self.inner.flush()
+    }
+}
+
+impl SerialPort for SoftFlow<Box<dyn SerialPort>> {
+    fn name(&self) -> Option<String> {
+        self.inner.name()
+    }
+    fn baud_rate(&self) -> serialport::Result<u32> {
+        self.inner.baud_rate()
+    }
+    fn data_bits(&self) -> serialport::Result<DataBits> {
+        self.inner.data_bits()
+    }
+    fn flow_control(&self) -> serialport::Result<FlowControl> {
+        Ok(FlowControl::Software)
+    }
+    fn parity(&self) -> serialport::Result<Parity> {
+        self.inner.parity()
+    }
+    fn stop_bits(&self) -> serialport::Result<StopBits> {
+        self.inner.stop_bits()
+    }
+    fn timeout(&self) -> Duration {
+        self.inner.timeout()
+    }
+    fn set_baud_rate(&mut self, baud_rate: u32) -> serialport::Result<()> {
+        self.inner.set_baud_rate(baud_rate)
+    }
+    fn set_data_bits(&mut self, data_bits: DataBits) -> serialport::Result<()> {
+        self.inner.set_data_bits(data_bits)
+    }
+    fn set_flow_control(&mut self, flow_control: FlowControl) -> serialport::Result<()> {
+        self.inner.set_flow_control(flow_control)
+    }
+    fn set_parity(&mut self, parity: Parity) -> serialport::Result<()> {
+        self.inner.set_parity(parity)
+    }
+    fn set_stop_bits(&mut self, stop_bits: StopBits) -> serialport::Result<()> {
+        self.inner.set_stop_bits(stop_bits)
+    }
+    fn set_timeout(&mut self, timeout: Duration) -> serialport::Result<()> {
+        self.inner.set_timeout(timeout)
+    }
+    fn write_request_to_send(&mut self, level: bool) -> serialport::Result<()> {
+        self.inner.write_request_to_send(level)
+    }
+    fn write_data_terminal_ready(&mut self, level: bool) -> serialport::Result<()> {
+        self.inner.write_data_terminal_ready(level)
+    }
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+        self.inner.read_clear_to_send()
+    }
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+        self.inner.read_data_set_ready()
+    }
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+        self.inner.read_ring_indicator()
+    }
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+        self.inner.read_carrier_detect()
+    }
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        self.inner.bytes_to_read()
+    }
+    fn bytes_to_write(&self) -> serialport::Result<u32> {
+        let pending = self.state.lock().unwrap().pending.len();
+        Ok(self.inner.bytes_to_write()? + pending as u32)
+    }
+    fn clear(&self, buffer_to_clear: ClearBuffer) -> serialport::Result<()> {
+        self.inner.clear(buffer_to_clear)
+    }
+    fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
+        Ok(Box::new(self.share(self.inner.try_clone()?)))
+    }
+    fn set_break(&self) -> serialport::Result<()> {
+        self.inner.set_break()
+    }
+    fn clear_break(&self) -> serialport::Result<()> {
+        self.inner.clear_break()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads come from `input`, writes are collected into a buffer shared with the test
+    #[derive(Default, Clone)]
+    struct Mock {
+        input: Vec<u8>,
+        written: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Read for Mock {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.input.len());
+            buf[..n].copy_from_slice(&self.input[..n]);
+            self.input.drain(..n);
+            Ok(n)
+        }
+    }
+
+    impl Write for Mock {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn flow_characters_are_taken_out_of_the_data() {
+        let mut flow = SoftFlow::new(Mock::default(), b'Q', b'S');
+        let mut data = *b"aSbQc";
+        let kept = flow.receive(&mut data);
+        assert_eq!(&data[..kept], b"abc");
+        assert!(!flow.state.lock().unwrap().paused);
+        let mut data = *b"Sx";
+        flow.receive(&mut data);
+        assert!(flow.state.lock().unwrap().paused);
+    }
+
+    #[test]
+    fn writes_wait_between_xoff_and_xon() {
+        let port = Mock::default();
+        let written = Arc::clone(&port.written);
+        let mut flow = SoftFlow::new(port, 0x11, 0x13);
+        flow.write_all(b"one ").unwrap();
+        flow.receive(&mut [0x13]);
+        flow.write_all(b"two ").unwrap();
+        flow.flush().unwrap();
+        assert_eq!(*written.lock().unwrap(), b"one ");
+        flow.receive(&mut [0x11]);
+        flow.write_all(b"three").unwrap();
+        assert_eq!(*written.lock().unwrap(), b"one two three");
+    }
+
+    #[test]
+    fn reading_xon_sends_what_was_held_back() {
+        let port = Mock {
+            input: b"\x13data\x11".to_vec(),
+            ..Mock::default()
+        };
+        let written = Arc::clone(&port.written);
+        let mut flow = SoftFlow::new(port, 0x11, 0x13);
+        let mut buf = [0; 1];
+        assert_eq!(flow.read(&mut buf).unwrap(), 1);
+        flow.write_all(b"held").unwrap();
+        let mut rest = Vec::new();
+        flow.read_to_end(&mut rest).unwrap();
+        assert_eq!([&buf[..], &rest].concat(), b"data");
+        assert_eq!(*written.lock().unwrap(), b"held");
+    }
+
+    #[test]
+    fn shared_handles_pause_together() {
+        let port = Mock::default();
+        let written = Arc::clone(&port.written);
+        let mut reader = SoftFlow::new(port.clone(), b'+', b'-');
+        let mut writer = reader.share(port);
+        reader.receive(&mut [b'-']);
+        writer.write_all(b"from the other handle").unwrap();
+        assert!(written.lock().unwrap().is_empty());
+        reader.receive(&mut [b'+']);
+        writer.flush().unwrap();
+        assert_eq!(*written.lock().unwrap(), b"from the other handle");
+    }
+}
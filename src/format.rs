@@ -0,0 +1,563 @@
+use std::convert::TryInto;
+use std::ffi::OsStr;
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::{Encoder, Endian};
+
+/// The type of one field of a `--format` record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    F32,
+    /// This many raw bytes, shown as base64
+    B64(usize),
+}
+
+impl Kind {
+    fn parse(s: &str) -> Option<Self> {
+        Some(match s.to_ascii_lowercase().as_str() {
+            "u8" => Kind::U8,
+            "i8" => Kind::I8,
+            "u16" => Kind::U16,
+            "i16" => Kind::I16,
+            "u32" => Kind::U32,
+            "i32" => Kind::I32,
+            "f32" => Kind::F32,
+            // The size follows the name, see `parse_field`
+            "b64" => Kind::B64(0),
+            _ => return None,
+        })
+    }
+
+    pub fn width(self) -> usize {
+        match self {
+            Kind::U8 | Kind::I8 => 1,
+            Kind::U16 | Kind::I16 => 2,
+            Kind::U32 | Kind::I32 | Kind::F32 => 4,
+            Kind::B64(size) => size,
+        }
+    }
+
+    /// What a C compiler aligns the field to
+    fn align(self) -> usize {
+        match self {
+            Kind::B64(_) => 1,
+            _ => self.width(),
+        }
+    }
+
+    /// The value held in `bytes`, which must be exactly `width()` long
+    pub fn read(self, bytes: &[u8], endian: Endian) -> Number {
+        if let Kind::B64(_) = self {
+            // Bytes in the order they came, --endian is about numbers
+            return Number::Text(crate::b64::encode(bytes));
+        }
+        let mut word = [0; 4];
+        word[..bytes.len()].copy_from_slice(bytes);
+        if endian == Endian::Big {
+            word[..bytes.len()].reverse();
+        }
+        let w2 = word[..2].try_into().unwrap();
+        match self {
+            Kind::U8 => Number::Int(word[0].into()),
+            Kind::I8 => Number::Int((word[0] as i8).into()),
+            Kind::U16 => Number::Int(u16::from_le_bytes(w2).into()),
+            Kind::I16 => Number::Int(i16::from_le_bytes(w2).into()),
+            Kind::U32 => Number::Int(u32::from_le_bytes(word).into()),
+            Kind::I32 => Number::Int(i32::from_le_bytes(word).into()),
+            Kind::F32 => Number::Float(f32::from_le_bytes(word).into()),
+            Kind::B64(_) => unreachable!("b64 fields are read above"),
+        }
+    }
+}
+
+/// A decoded field value, integers stay integers until they are scaled
+#[derive(Debug, Clone, PartialEq)]
+pub enum Number {
+    Int(i64),
+    Float(f64),
+    /// A b64 field
+    Text(String),
+}
+
+impl Number {
+    /// None for text, which can't be scaled
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Number::Int(n) => Some(*n as f64),
+            Number::Float(x) => Some(*x),
+            Number::Text(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Number::Int(n) => write!(f, "{}", n),
+            Number::Float(x) => write!(f, "{}", x),
+            Number::Text(s) => f.write_str(s),
+        }
+    }
+}
+
+/// A named range of bits of an integer field, `low` to `high` inclusive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bits {
+    pub name: String,
+    pub low: u32,
+    pub high: u32,
+}
+
+impl Bits {
+    pub fn extract(&self, value: i64) -> i64 {
+        let width = self.high - self.low + 1;
+        (value >> self.low) & ((1i64 << width) - 1)
+    }
+}
+
+/// Parse `ready:0, error:1-2` into bit ranges that fit in `bits` bits
+fn parse_bits(spec: &str, bits: u32) -> Result<Vec<Bits>, String> {
+    let mut ranges = Vec::new();
+    for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (name, range) = part.split_once(':').ok_or_else(|| {
+            format!(
+                "bitfield \"{}\" should look like NAME:BIT or NAME:LOW-HIGH",
+                part
+            )
+        })?;
+        let bit = |s: &str| {
+            s.trim()
+                .parse::<u32>()
+                .map_err(|_| format!("\"{}\" is not a bit number", s.trim()))
+        };
+        let (low, high) = match range.split_once('-') {
+            Some((low, high)) => (bit(low)?, bit(high)?),
+            None => (bit(range)?, bit(range)?),
+        };
+        if low > high {
+            return Err(format!(
+                "bitfield \"{}\" has its bits the wrong way round",
+                part
+            ));
+        }
+        if high >= bits {
+            return Err(format!(
+                "bitfield \"{}\" doesn't fit in a {} bit field",
+                part, bits
+            ));
+        }
+        ranges.push(Bits {
+            name: name.trim().to_string(),
+            low,
+            high,
+        });
+    }
+    if ranges.is_empty() {
+        return Err("the bitfield list is empty".to_string());
+    }
+    Ok(ranges)
+}
+
+/// One field of a record, `value * scale + offset` when either is given
+/// A field with bit ranges is shown as one value per range instead
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub kind: Kind,
+    pub name: String,
+    pub scale: Option<f64>,
+    pub offset: Option<f64>,
+    pub bits: Vec<Bits>,
+}
+
+impl Field {
+    /// The value to show for the raw `bytes` of this field
+    pub fn value(&self, bytes: &[u8], endian: Endian, raw: bool) -> Number {
+        let value = self.kind.read(bytes, endian);
+        match value.as_f64() {
+            Some(x) if !raw && (self.scale.is_some() || self.offset.is_some()) => {
+                Number::Float(x * self.scale.unwrap_or(1.0) + self.offset.unwrap_or(0.0))
+            }
+            _ => value,
+        }
+    }
+}
+
+/// The fields of one fixed size binary record, in the order they are received
+#[derive(Debug, Clone, PartialEq)]
+pub struct Format {
+    pub fields: Vec<Field>,
+}
+
+impl Format {
+    /// Bytes in one record
+    pub fn width(&self) -> usize {
+        self.fields.iter().map(|f| f.kind.width()).sum()
+    }
+
+    /// Bytes in a `#[repr(C)]` struct of the same fields, where each is aligned to its own size
+    pub fn c_width(&self) -> usize {
+        let mut width = 0usize;
+        let mut align = 1;
+        for field in &self.fields {
+            let size = field.kind.align();
+            width = width.next_multiple_of(size) + field.kind.width();
+            align = align.max(size);
+        }
+        width.next_multiple_of(align)
+    }
+
+    /// Check that the fields fill the `size` byte records given by --struct
+    pub fn check_size(&self, size: usize) -> Result<(), String> {
+        let width = self.width();
+        if width == size {
+            return Ok(());
+        }
+        let mut e = format!(
+            "the fields add up to {} bytes, not the {} of --struct",
+            width, size
+        );
+        if self.c_width() == size {
+            e.push_str(", which a C compiler would pad them to, so list the padding as u8 fields");
+        }
+        Err(e)
+    }
+
+    /// The names of the values in a record, one for each bitfield of a field that has them
+    #[cfg(feature = "tui")]
+    pub fn names(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        for field in &self.fields {
+            if field.bits.is_empty() {
+                names.push(field.name.as_str());
+            } else {
+                names.extend(field.bits.iter().map(|bits| bits.name.as_str()));
+            }
+        }
+        names
+    }
+
+    /// The named values of one `width()` byte record, in the order of `names()`
+    pub fn decode(&self, record: &[u8], endian: Endian, raw: bool) -> Vec<(&str, Number)> {
+        let mut values = Vec::new();
+        let mut at = 0;
+        for field in &self.fields {
+            let bytes = &record[at..at + field.kind.width()];
+            at += field.kind.width();
+            match field.kind.read(bytes, endian) {
+                Number::Int(value) if !field.bits.is_empty() => {
+                    for bits in &field.bits {
+                        values.push((bits.name.as_str(), Number::Int(bits.extract(value))));
+                    }
+                }
+                _ => values.push((field.name.as_str(), field.value(bytes, endian, raw))),
+            }
+        }
+        values
+    }
+}
+
+fn parse_field(line: &str) -> Result<Field, String> {
+    let (line, bits) = match line.split_once('{') {
+        Some((field, bits)) => {
+            let bits = bits
+                .trim_end()
+                .strip_suffix('}')
+                .ok_or("the bitfield list is missing its closing }")?;
+            (field, Some(bits))
+        }
+        None => (line, None),
+    };
+    let mut words = line.split_whitespace();
+    let kind = words.next().unwrap();
+    let mut kind = Kind::parse(kind).ok_or_else(|| {
+        format!(
+            "\"{}\" is not a field type (u8, i8, u16, i16, u32, i32, f32, b64)",
+            kind
+        )
+    })?;
+    let name = words.next().ok_or("the field needs a name")?.to_string();
+    if let Kind::B64(size) = &mut kind {
+        let word = words
+            .next()
+            .ok_or("a b64 field needs its size in bytes after the name, e.g. `b64 id 8`")?;
+        *size = word
+            .parse()
+            .ok()
+            .filter(|&size| size > 0)
+            .ok_or_else(|| format!("\"{}\" is not a size in bytes", word))?;
+    }
+    let mut field = Field {
+        kind,
+        name,
+        scale: None,
+        offset: None,
+        bits: Vec::new(),
+    };
+    for word in words {
+        let number = |s: &str| {
+            s.parse::<f64>()
+                .map_err(|_| format!("\"{}\" is not a number", word))
+        };
+        if let Some(scale) = word.strip_prefix('*') {
+            field.scale = Some(number(scale)?);
+        } else if let Some(offset) = word.strip_prefix('+') {
+            field.offset = Some(number(offset)?);
+        } else if word.starts_with('-') {
+            field.offset = Some(number(word)?);
+        } else {
+            return Err(format!(
+                "\"{}\" should be a scale (*0.1) or an offset (+10, -40)",
+                word
+            ));
+        }
+    }
+    if matches!(kind, Kind::B64(_)) && (field.scale.is_some() || field.offset.is_some()) {
+        return Err("a b64 field can't be scaled or offset".to_string());
+    }
+    if let Some(bits) = bits {
+        if matches!(kind, Kind::F32 | Kind::B64(_)) {
+            return Err("bitfields need an integer field".to_string());
+        }
+        if field.scale.is_some() || field.offset.is_some() {
+            return Err("a field with bitfields can't be scaled or offset".to_string());
+        }
+        field.bits = parse_bits(bits, kind.width() as u32 * 8)?;
+    }
+    Ok(field)
+}
+
+/// Parse a format, one `TYPE NAME [*SCALE] [+OFFSET]`, `TYPE NAME { BITS:LOW-HIGH, .. }`
+/// or `b64 NAME SIZE` field per line (or separated by `;`)
+/// Blank lines and lines starting with `#` are skipped
+pub fn parse(text: &str) -> Result<Format, String> {
+    let mut fields = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        for field in line.split(';').map(str::trim) {
+            if field.is_empty() || field.starts_with('#') {
+                continue;
+            }
+            fields.push(parse_field(field).map_err(|e| format!("line {}: {}", n + 1, e))?);
+        }
+    }
+    if fields.is_empty() {
+        return Err("the format has no fields".to_string());
+    }
+    Ok(Format { fields })
+}
+
+/// Read and parse a format file
+pub fn load(path: &OsStr) -> Result<Format, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read {}: {}", path.to_string_lossy(), e))?;
+    parse(&text).map_err(|e| format!("{}: {}", path.to_string_lossy(), e))
+}
+
+/// Writes every whole record as a line of `name=value` pairs, one for each bitfield of a field that has them
+pub struct FormatWriter {
+    format: Format,
+    endian: Endian,
+    raw: bool,
+    carry: Vec<u8>,
+}
+
+impl FormatWriter {
+    pub fn new(format: Format, endian: Endian, raw: bool) -> Self {
+        FormatWriter {
+            format,
+            endian,
+            raw,
+            carry: Vec::new(),
+        }
+    }
+}
+
+impl Encoder for FormatWriter {
+    fn write(&mut self, data: &[u8], out: &mut dyn Write) -> io::Result<()> {
+        self.carry.extend_from_slice(data);
+        let mut records = self.carry.chunks_exact(self.format.width());
+        for record in &mut records {
+            let values = self.format.decode(record, self.endian, self.raw);
+            for (i, (name, value)) in values.iter().enumerate() {
+                let sep = if i > 0 { " " } else { "" };
+                write!(out, "{}{}={}", sep, name, value)?;
+            }
+            out.write_all(b"\n")?;
+        }
+        let used = self.carry.len() - records.remainder().len();
+        self.carry.drain(..used);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn b64_fields_show_their_bytes_as_base64() {
+        let format = parse("u8 kind; b64 id 5; u16 len").unwrap();
+        assert_eq!(format.width(), 8);
+        assert_eq!(format.fields[1].kind, Kind::B64(5));
+        let values = format.decode(b"\x01fooba\x02\x00", Endian::Big, false);
+        assert_eq!(
+            values,
+            [
+                ("kind", Number::Int(1)),
+                ("id", Number::Text(String::from("Zm9vYmE="))),
+                ("len", Number::Int(512)),
+            ]
+        );
+        let mut writer = FormatWriter::new(format, Endian::Little, false);
+        let mut out = Vec::new();
+        writer.write(b"\x01fooba\x02\x00", &mut out).unwrap();
+        assert_eq!(out, b"kind=1 id=Zm9vYmE= len=2\n");
+    }
+
+    #[test]
+    fn b64_fields_need_a_size_and_cant_be_scaled() {
+        assert!(parse("b64 id").unwrap_err().contains("needs its size"));
+        assert!(parse("b64 id 0").unwrap_err().contains("not a size"));
+        assert!(parse("b64 id 4 *2")
+            .unwrap_err()
+            .contains("can't be scaled"));
+        assert!(parse("b64 id 4 { a:0 }")
+            .unwrap_err()
+            .contains("need an integer field"));
+    }
+
+    #[test]
+    fn b64_fields_are_not_padded_in_a_c_struct() {
+        // u8, then 3 bytes that C aligns to 1, then a u32 aligned to 4
+        let format = parse("u8 a; b64 b 3; u32 c").unwrap();
+        assert_eq!((format.width(), format.c_width()), (8, 8));
+        let format = parse("u8 a; b64 b 2; u32 c").unwrap();
+        assert_eq!((format.width(), format.c_width()), (7, 8));
+    }
+
+    #[test]
+    fn scaled_fields_become_floats_and_raw_keeps_the_integers() {
+        let format = parse("u16 temp *0.1 +0; i8 delta *2 -40; u8 count").unwrap();
+        assert_eq!(format.fields[0].scale, Some(0.1));
+        assert_eq!(format.fields[1].offset, Some(-40.0));
+        // 250 * 0.1, then -3 * 2 - 40
+        let record = [0x00, 0xfa, 0xfd, 0x07];
+        assert_eq!(
+            format.decode(&record, Endian::Big, false),
+            [
+                ("temp", Number::Float(25.0)),
+                ("delta", Number::Float(-46.0)),
+                ("count", Number::Int(7)),
+            ]
+        );
+        assert_eq!(
+            format.decode(&record, Endian::Big, true),
+            [
+                ("temp", Number::Int(250)),
+                ("delta", Number::Int(-3)),
+                ("count", Number::Int(7)),
+            ]
+        );
+    }
+
+    #[test]
+    fn an_offset_alone_shifts_the_value() {
+        let format = parse("u8 celsius -40").unwrap();
+        assert_eq!(
+            format.decode(&[10], Endian::Big, false),
+            [("celsius", Number::Float(-30.0))]
+        );
+        let format = parse("u8 kelvin +273.15").unwrap();
+        assert_eq!(
+            format.decode(&[0], Endian::Big, false),
+            [("kelvin", Number::Float(273.15))]
+        );
+    }
+
+    #[test]
+    fn scales_and_offsets_have_to_be_numbers() {
+        assert!(parse("u8 a *x").unwrap_err().contains("not a number"));
+        assert!(parse("u8 a 10").unwrap_err().contains("should be a scale"));
+    }
+
+    #[test]
+    fn bitfields_split_an_integer_into_named_ranges() {
+        let format = parse("u8 status { ready:0, error:1-2, mode:3-5 }; u8 next").unwrap();
+        // mode 0b101, error 0b10, ready 1
+        let values = format.decode(&[0b0010_1101, 9], Endian::Big, false);
+        assert_eq!(
+            values,
+            [
+                ("ready", Number::Int(1)),
+                ("error", Number::Int(2)),
+                ("mode", Number::Int(5)),
+                ("next", Number::Int(9)),
+            ]
+        );
+        let format = parse("u16 word { low:0-7, top:15 }").unwrap();
+        assert_eq!(
+            format.decode(&[0x80, 0x34], Endian::Big, false),
+            [("low", Number::Int(0x34)), ("top", Number::Int(1))]
+        );
+    }
+
+    #[test]
+    fn bit_ranges_have_to_fit_the_field() {
+        assert!(parse("u8 s { a:8 }")
+            .unwrap_err()
+            .contains("doesn't fit in a 8 bit"));
+        assert!(parse("u16 s { a:4-16 }").unwrap_err().contains("16 bit"));
+        assert!(parse("u16 s { a:3-1 }")
+            .unwrap_err()
+            .contains("wrong way round"));
+        assert!(parse("u8 s { a:0").unwrap_err().contains("closing }"));
+        assert!(parse("u8 s { }").unwrap_err().contains("empty"));
+        assert!(parse("f32 s { a:0 }")
+            .unwrap_err()
+            .contains("integer field"));
+        assert!(parse("u32 s { a:31 }").is_ok());
+    }
+
+    #[test]
+    fn the_struct_size_has_to_match_the_fields() {
+        let format = parse("u8 a; u16 b; u8 c").unwrap();
+        assert_eq!(format.check_size(4), Ok(()));
+        // C puts a byte before b to align it, and one after c to align the next record
+        let e = format.check_size(6).unwrap_err();
+        assert!(
+            e.starts_with("the fields add up to 4 bytes, not the 6 of --struct"),
+            "{}",
+            e
+        );
+        assert!(e.contains("C compiler would pad"), "{}", e);
+        let e = format.check_size(5).unwrap_err();
+        assert!(!e.contains("pad"), "{}", e);
+        assert_eq!(
+            parse("u8 a; u8 pad; u16 b; u8 c; u8 pad")
+                .unwrap()
+                .check_size(6),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn records_split_across_writes_are_put_back_together() {
+        let format = parse("u8 a; u16 b; u8 c").unwrap();
+        let mut writer = FormatWriter::new(format, Endian::Big, false);
+        let mut out = Vec::new();
+        writer.write(b"\x01\x00", &mut out).unwrap();
+        assert!(out.is_empty());
+        writer.write(b"\x02\x03\x04\x00\x05", &mut out).unwrap();
+        assert_eq!(out, b"a=1 b=2 c=3\n");
+        writer.write(b"\x06\x07", &mut out).unwrap();
+        assert_eq!(out, b"a=1 b=2 c=3\na=4 b=5 c=6\n");
+        // one byte of the next record is still waiting
+        assert_eq!(writer.carry, b"\x07");
+    }
+}
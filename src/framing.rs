@@ -0,0 +1,62 @@
+use std::str::FromStr;
+
+use crate::cobs::CobsDecoder;
+use crate::length::LengthDecoder;
+
+/// How the received stream is split into frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    Cobs,
+    Length,
+}
+
+impl FromStr for Framing {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "cobs" => Framing::Cobs,
+            "length" => Framing::Length,
+            _ => return Err("Framing should be cobs or length"),
+        })
+    }
+}
+
+/// One of the frame decoders, so the streaming loop can treat them alike
+pub enum Deframer {
+    Cobs(CobsDecoder),
+    Length(LengthDecoder),
+}
+
+impl Deframer {
+    /// Hand the payload of every frame completed by `data` to `frame`
+    pub fn feed(&mut self, data: &[u8], frame: impl FnMut(&[u8])) {
+        match self {
+            Deframer::Cobs(cobs) => cobs.feed(data, frame),
+            Deframer::Length(length) => length.feed(data, frame),
+        }
+    }
+
+    /// How many frames (COBS) or bytes (length prefixed) were thrown away
+    #[cfg(feature = "json")]
+    pub fn dropped(&self) -> u64 {
+        match self {
+            Deframer::Cobs(cobs) => cobs.invalid as u64,
+            Deframer::Length(length) => length.oversized as u64,
+        }
+    }
+
+    /// What was thrown away, for the summary
+    pub fn skipped(&self) -> Option<String> {
+        match self {
+            Deframer::Cobs(cobs) if cobs.invalid > 0 => {
+                Some(format!("Skipped {} invalid COBS frames", cobs.invalid))
+            }
+            Deframer::Length(length) if length.oversized > 0 => Some(format!(
+                "Skipped {} bytes whose length was over --max-frame",
+                length.oversized
+            )),
+            _ => None,
+        }
+    }
+}
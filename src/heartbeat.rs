@@ -0,0 +1,71 @@
+use std::time::{Duration, Instant};
+
+/// Decides when to show that we are still waiting on a silent port
+pub struct Heartbeat {
+    interval: Duration,
+    last_data: Instant,
+    last_beat: Instant,
+    showing: bool,
+}
+
+impl Heartbeat {
+    pub fn new(interval: Duration, start: Instant) -> Self {
+        Heartbeat {
+            interval,
+            last_data: start,
+            last_beat: start,
+            showing: false,
+        }
+    }
+
+    /// Data arrived at `now`, returns whether the waiting indicator needs clearing
+    pub fn data(&mut self, now: Instant) -> bool {
+        self.last_data = now;
+        self.last_beat = now;
+        std::mem::replace(&mut self.showing, false)
+    }
+
+    /// Whether the waiting indicator is on screen
+    pub fn showing(&self) -> bool {
+        self.showing
+    }
+
+    /// How long we have been waiting, if it is time to show (or update) the indicator
+    pub fn poll(&mut self, now: Instant) -> Option<Duration> {
+        if now - self.last_beat < self.interval {
+            return None;
+        }
+        self.last_beat = now;
+        self.showing = true;
+        Some(now - self.last_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn beats_once_per_interval_of_silence() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut heartbeat = Heartbeat::new(Duration::from_millis(1000), start);
+        assert_eq!(heartbeat.poll(at(999)), None);
+        assert_eq!(heartbeat.poll(at(1000)), Some(Duration::from_millis(1000)));
+        assert!(heartbeat.showing());
+        assert_eq!(heartbeat.poll(at(1500)), None);
+        assert_eq!(heartbeat.poll(at(2100)), Some(Duration::from_millis(2100)));
+    }
+
+    #[test]
+    fn data_clears_the_indicator_and_restarts_the_wait() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut heartbeat = Heartbeat::new(Duration::from_millis(1000), start);
+        assert!(!heartbeat.data(at(500)));
+        assert_eq!(heartbeat.poll(at(1200)), None);
+        assert_eq!(heartbeat.poll(at(1500)), Some(Duration::from_millis(1000)));
+        assert!(heartbeat.data(at(1600)));
+        assert!(!heartbeat.showing());
+    }
+}
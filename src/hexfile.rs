@@ -0,0 +1,271 @@
+use std::io::{Read, Write};
+#[cfg(feature = "regex")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "regex")]
+use regex::bytes::Regex;
+
+/// How long a device gets to acknowledge a record
+#[cfg(feature = "regex")]
+pub const ACK_TIMEOUT: Duration = Duration::from_millis(5000);
+
+/// The firmware file formats that can be sent record by record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// Intel HEX, `:LLAAAATT…CC` records ending with an end of file record
+    IntelHex,
+    /// Motorola S-records, `STLL…CC`
+    Srec,
+}
+
+/// The bytes spelled out by the hex digits of a record after its start code
+fn hex_bytes(digits: &str) -> Result<Vec<u8>, String> {
+    if !digits.len().is_multiple_of(2) || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(String::from("should be an even number of hex digits"));
+    }
+    Ok((0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap())
+        .collect())
+}
+
+/// Check the length and checksum of one Intel HEX record, giving its type
+fn check_ihex(line: &str) -> Result<u8, String> {
+    let digits = line
+        .strip_prefix(':')
+        .ok_or_else(|| String::from("should start with ':'"))?;
+    let bytes = hex_bytes(digits)?;
+    if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+        return Err(String::from("has the wrong length for its byte count"));
+    }
+    if bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) != 0 {
+        return Err(String::from("has a bad checksum"));
+    }
+    match bytes[3] {
+        kind @ 0..=5 => Ok(kind),
+        kind => Err(format!("has the unknown record type {:02X}", kind)),
+    }
+}
+
+/// Check the type, length and checksum of one S-record
+fn check_srec(line: &str) -> Result<(), String> {
+    let mut chars = line.chars();
+    if chars.next() != Some('S') {
+        return Err(String::from("should start with 'S'"));
+    }
+    match chars.next() {
+        Some('0'..='3' | '5'..='9') => {}
+        _ => return Err(String::from("has an unknown record type")),
+    }
+    let bytes = hex_bytes(chars.as_str())?;
+    if bytes.len() < 3 || bytes.len() != bytes[0] as usize + 1 {
+        return Err(String::from("has the wrong length for its byte count"));
+    }
+    if bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) != 0xff {
+        return Err(String::from("has a bad checksum"));
+    }
+    Ok(())
+}
+
+/// The records of a firmware file, checked and without their line endings, blank lines are skipped
+pub fn parse(text: &str, kind: Kind) -> Result<Vec<String>, String> {
+    let mut records = Vec::new();
+    let mut ended = false;
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |e: String| format!("line {}: the record {}", number + 1, e);
+        if ended {
+            return Err(error(String::from("comes after the end of file record")));
+        }
+        match kind {
+            Kind::IntelHex => ended = check_ihex(line).map_err(error)? == 1,
+            Kind::Srec => check_srec(line).map_err(error)?,
+        }
+        records.push(line.to_string());
+    }
+    if kind == Kind::IntelHex && !ended {
+        return Err(String::from("there is no end of file record"));
+    }
+    if records.is_empty() {
+        return Err(String::from("there are no records"));
+    }
+    Ok(records)
+}
+
+/// Send the records one at a time, each followed by `eol`
+/// After each one `ack` is called to wait for the device, and `progress` is told how many have gone
+pub fn send<P: Read + Write>(
+    port: &mut P,
+    records: &[String],
+    eol: &[u8],
+    mut ack: impl FnMut(&mut P) -> Result<(), String>,
+    mut progress: impl FnMut(usize),
+) -> Result<(), String> {
+    for (i, record) in records.iter().enumerate() {
+        let sent = port
+            .write_all(record.as_bytes())
+            .and_then(|()| port.write_all(eol))
+            .and_then(|()| port.flush());
+        sent.map_err(|e| format!("record {}: {}", i + 1, e))?;
+        ack(port).map_err(|e| format!("record {}: {}", i + 1, e))?;
+        progress(i + 1);
+    }
+    Ok(())
+}
+
+/// Read from the port until `pattern` matches what arrived since the call, or `timeout` runs out
+#[cfg(feature = "regex")]
+pub fn wait_for_ack(
+    port: &mut impl Read,
+    pattern: &Regex,
+    timeout: Duration,
+) -> Result<(), String> {
+    let deadline = Instant::now() + timeout;
+    let mut received = Vec::new();
+    let mut buf = [0; 256];
+    while !pattern.is_match(&received) {
+        if Instant::now() >= deadline {
+            return Err(format!(
+                "no /{}/ acknowledgement within {}ms, got {:?}",
+                pattern,
+                timeout.as_millis(),
+                String::from_utf8_lossy(&received)
+            ));
+        }
+        match port.read(&mut buf) {
+            Ok(0) => std::thread::sleep(crate::IDLE_SLEEP),
+            Ok(n) => received.extend_from_slice(&buf[..n]),
+            Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockPort;
+
+    const IHEX: &str = ":10010000214601360121470136007EFE09D2190140\r\n\r\n:00000001FF\r\n";
+    const SREC: &str = "S00F000068656C6C6F202020202000003C\nS11F00007C0802A6900100049421FFF07C6C1B787C8C23783C6000003863000026\nS5030001FB\nS9030000FC\n";
+
+    #[test]
+    fn intel_hex_records_are_checked_and_kept_in_order() {
+        assert_eq!(
+            parse(IHEX, Kind::IntelHex),
+            Ok(vec![
+                String::from(":10010000214601360121470136007EFE09D2190140"),
+                String::from(":00000001FF"),
+            ])
+        );
+    }
+
+    #[test]
+    fn broken_intel_hex_says_which_line_and_why() {
+        let bad_sum = ":10010000214601360121470136007EFE09D2190141\n:00000001FF\n";
+        assert_eq!(
+            parse(bad_sum, Kind::IntelHex),
+            Err(String::from("line 1: the record has a bad checksum"))
+        );
+        assert!(parse(":0000000AF6\n", Kind::IntelHex)
+            .unwrap_err()
+            .contains("unknown record type 0A"));
+        assert_eq!(
+            parse(
+                ":10010000214601360121470136007EFE09D2190140\n",
+                Kind::IntelHex
+            ),
+            Err(String::from("there is no end of file record"))
+        );
+        assert!(parse(":00000001FF\n:00000001FF\n", Kind::IntelHex)
+            .unwrap_err()
+            .contains("line 2: the record comes after the end of file record"));
+        assert!(parse("10010000\n", Kind::IntelHex)
+            .unwrap_err()
+            .contains("should start with ':'"));
+    }
+
+    #[test]
+    fn s_records_are_checked() {
+        assert_eq!(parse(SREC, Kind::Srec).unwrap().len(), 4);
+        assert!(parse("S4030000FC\n", Kind::Srec)
+            .unwrap_err()
+            .contains("unknown record type"));
+        assert!(parse("S9030000FD\n", Kind::Srec)
+            .unwrap_err()
+            .contains("bad checksum"));
+        assert_eq!(
+            parse("\n\n", Kind::Srec),
+            Err(String::from("there are no records"))
+        );
+    }
+
+    #[test]
+    fn records_are_sent_as_lines_with_progress() {
+        let records = parse(IHEX, Kind::IntelHex).unwrap();
+        let mut port = MockPort::default();
+        let mut acks = 0;
+        let mut progress = Vec::new();
+        send(
+            &mut port,
+            &records,
+            b"\r\n",
+            |_| {
+                acks += 1;
+                Ok(())
+            },
+            |sent| progress.push(sent),
+        )
+        .unwrap();
+        assert_eq!(
+            port.state().written,
+            b":10010000214601360121470136007EFE09D2190140\r\n:00000001FF\r\n"
+        );
+        assert_eq!((acks, progress), (2, vec![1, 2]));
+    }
+
+    #[test]
+    fn a_failed_ack_stops_at_that_record() {
+        let records = parse(IHEX, Kind::IntelHex).unwrap();
+        let mut port = MockPort::default();
+        let result = send(
+            &mut port,
+            &records,
+            b"\n",
+            |_| Err(String::from("NAK")),
+            |_| {},
+        );
+        assert_eq!(result, Err(String::from("record 1: NAK")));
+        assert_eq!(
+            port.state().written,
+            b":10010000214601360121470136007EFE09D2190140\n"
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn an_ack_is_waited_for_until_it_matches() {
+        let pattern = Regex::new("OK").unwrap();
+        let mut port = MockPort::with_input(b"busy\r\nO");
+        let waiting = port.clone();
+        let feeder = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            waiting.state().input.extend(b"K\r\n");
+        });
+        assert_eq!(
+            wait_for_ack(&mut port, &pattern, Duration::from_secs(5)),
+            Ok(())
+        );
+        feeder.join().unwrap();
+        let error = wait_for_ack(&mut port, &pattern, Duration::from_millis(20)).unwrap_err();
+        assert!(
+            error.starts_with("no /OK/ acknowledgement within 20ms"),
+            "{}",
+            error
+        );
+    }
+}
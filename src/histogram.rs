@@ -0,0 +1,118 @@
+use std::fmt;
+
+/// How many of the most frequent bytes are listed
+const TOP: usize = 8;
+
+/// How often every byte value has been seen
+pub struct Histogram {
+    counts: [u64; 256],
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Histogram { counts: [0; 256] }
+    }
+
+    pub fn add(&mut self, data: &[u8]) {
+        for &b in data {
+            self.counts[b as usize] += 1;
+        }
+    }
+
+    pub fn count(&self, byte: u8) -> u64 {
+        self.counts[byte as usize]
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Share of the bytes that are printable ASCII or whitespace, between 0 and 1
+    pub fn printable_ratio(&self) -> f64 {
+        let printable: u64 = (0..=255u8)
+            .filter(|b| b.is_ascii_graphic() || b.is_ascii_whitespace())
+            .map(|b| self.count(b))
+            .sum();
+        printable as f64 / self.total().max(1) as f64
+    }
+
+    /// The `n` most frequent bytes that have been seen, most frequent first
+    pub fn top(&self, n: usize) -> Vec<(u8, u64)> {
+        let mut seen: Vec<(u8, u64)> = (0..=255u8)
+            .map(|b| (b, self.count(b)))
+            .filter(|&(_, count)| count > 0)
+            .collect();
+        seen.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        seen.truncate(n);
+        seen
+    }
+}
+
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total();
+        write!(
+            f,
+            "Byte histogram: {} bytes, {:.1}% printable",
+            total,
+            self.printable_ratio() * 100.0
+        )?;
+        for (b, count) in self.top(TOP) {
+            let shown = if b.is_ascii_graphic() {
+                format!("'{}'", b as char)
+            } else {
+                String::new()
+            };
+            write!(
+                f,
+                "\n  {:#04x} {:>3} {:>10} ({:.1}%)",
+                b,
+                shown,
+                count,
+                count as f64 * 100.0 / total as f64
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tallies_a_known_distribution() {
+        let mut histogram = Histogram::new();
+        histogram.add(b"aaab");
+        histogram.add(&[0, 0, 0xff, b'a']);
+        assert_eq!(histogram.count(b'a'), 4);
+        assert_eq!(histogram.count(b'b'), 1);
+        assert_eq!(histogram.count(0), 2);
+        assert_eq!(histogram.count(0xff), 1);
+        assert_eq!(histogram.count(b'c'), 0);
+        assert_eq!(histogram.total(), 8);
+        assert_eq!(histogram.printable_ratio(), 5.0 / 8.0);
+    }
+
+    #[test]
+    fn top_is_most_frequent_first_then_by_value() {
+        let mut histogram = Histogram::new();
+        histogram.add(b"zzyyyx");
+        assert_eq!(histogram.top(2), [(b'y', 3), (b'z', 2)]);
+        assert_eq!(histogram.top(10), [(b'y', 3), (b'z', 2), (b'x', 1)]);
+        histogram.add(b"x");
+        assert_eq!(histogram.top(2), [(b'y', 3), (b'x', 2)]);
+    }
+
+    #[test]
+    fn report_lists_the_bytes() {
+        let mut histogram = Histogram::new();
+        histogram.add(b"AA\n");
+        assert_eq!(
+            histogram.to_string(),
+            "Byte histogram: 3 bytes, 100.0% printable\n  \
+             0x41 'A'          2 (66.7%)\n  \
+             0x0a              1 (33.3%)"
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use std::time::{Duration, Instant};
+
+/// What ends a partial line cut short by --newline-on-idle, with --mark-partial it is flagged
+pub fn ending(mark: bool) -> &'static [u8] {
+    if mark {
+        b" [partial]\n"
+    } else {
+        b"\n"
+    }
+}
+
+/// Decides when a line that stopped part way has waited long enough to be ended for it
+pub struct IdleLine {
+    interval: Duration,
+    last_data: Instant,
+    partial: bool,
+}
+
+impl IdleLine {
+    pub fn new(interval: Duration, start: Instant) -> Self {
+        IdleLine {
+            interval,
+            last_data: start,
+            partial: false,
+        }
+    }
+
+    /// Keep track of the `data` passed on at `now`, returns whether the partial line
+    /// left by earlier data has now been idle for the whole interval and should be ended
+    pub fn due(&mut self, data: &[u8], now: Instant) -> bool {
+        if let Some(&last) = data.last() {
+            self.last_data = now;
+            self.partial = last != b'\n';
+            return false;
+        }
+        if self.partial && now - self.last_data >= self.interval {
+            self.partial = false;
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn a_partial_line_is_ended_once_it_has_sat_for_the_interval() {
+        let start = Instant::now();
+        let mut idle = IdleLine::new(ms(100), start);
+        assert!(!idle.due(b"temp=2", start));
+        assert!(!idle.due(b"", start + ms(99)));
+        assert!(idle.due(b"", start + ms(100)));
+        // Only once
+        assert!(!idle.due(b"", start + ms(300)));
+    }
+
+    #[test]
+    fn new_data_restarts_the_wait() {
+        let start = Instant::now();
+        let mut idle = IdleLine::new(ms(100), start);
+        idle.due(b"temp", start);
+        idle.due(b"=2", start + ms(80));
+        assert!(!idle.due(b"", start + ms(150)));
+        assert!(idle.due(b"", start + ms(180)));
+    }
+
+    #[test]
+    fn complete_lines_are_left_alone() {
+        let start = Instant::now();
+        let mut idle = IdleLine::new(ms(100), start);
+        assert!(!idle.due(b"", start + ms(500)));
+        idle.due(b"ok\n", start);
+        assert!(!idle.due(b"", start + ms(500)));
+        assert_eq!(ending(false), b"\n");
+        assert_eq!(ending(true), b" [partial]\n");
+    }
+}
@@ -0,0 +1,715 @@
+use std::fmt::Display;
+use std::io::{self, stdout, BufWriter, Write};
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, Clear, ClearType};
+use crossterm::{cursor, queue};
+use serialport::SerialPort;
+
+use crate::bell::Bell;
+use crate::clipboard::{Clipboard, Copied};
+use crate::line_editor::LineEditor;
+use crate::paste::{self, Paced};
+use crate::port_command::{self, PortCommand};
+use crate::scrollback::Scrollback;
+use crate::sparkline::History;
+use crate::stats::{Rates, Stats};
+use crate::terminal::TerminalGuard;
+use crate::Args;
+
+/// Parse a `KEY=STRING` macro spec, KEY is one of F1 to F12 and STRING may contain escapes
+pub fn parse_macro(spec: &str) -> Result<(KeyCode, Vec<u8>), String> {
+    let (key, text) = spec
+        .split_once('=')
+        .ok_or_else(|| format!("macro \"{}\" should look like KEY=STRING", spec))?;
+    let key = key.trim();
+    let code = key
+        .strip_prefix(|c| c == 'F' || c == 'f')
+        .and_then(|n| n.parse().ok())
+        .filter(|n| (1..=12).contains(n))
+        .map(KeyCode::F)
+        .ok_or_else(|| format!("macro key \"{}\" should be one of F1 to F12", key))?;
+    Ok((code, crate::escape::unescape(text)?))
+}
+
+/// The one line summary pinned to the bottom of the interactive modes
+/// `graph` is the recent throughput drawn as a sparkline
+pub fn status_line(settings: &str, rates: &Rates, graph: &str) -> String {
+    if graph.is_empty() {
+        format!("{} | {:.0} B/s", settings, rates.bytes)
+    } else {
+        format!("{} | {} {:.0} B/s", settings, graph, rates.bytes)
+    }
+}
+
+/// The line written when marking the log, `text` is optional
+pub fn marker(timestamp: impl Display, text: &str) -> String {
+    if text.is_empty() {
+        format!("--- MARK {} ---", timestamp)
+    } else {
+        format!("--- MARK {} {} ---", timestamp, text)
+    }
+}
+
+/// Where Ctrl-S saves the screen taken at `now`
+pub fn snapshot_name(now: &chrono::DateTime<chrono::Local>) -> String {
+    now.format("oxterm-%Y%m%d-%H%M%S.txt").to_string()
+}
+
+/// Whether what is sent is also shown on screen, on unless turned off for a device that echoes
+pub fn local_echo(setting: Option<bool>) -> bool {
+    setting.unwrap_or(true)
+}
+
+/// How sent bytes look when echoed, line endings become newlines and other control characters are left out
+pub fn echo_text(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .replace("\r\n", "\n")
+        .chars()
+        .filter_map(|c| match c {
+            '\r' => Some('\n'),
+            c if c == '\n' || c == '\t' || !c.is_control() => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// What received text looks like on screen and how many bells it rang
+/// BELs are kept off the screen, where every redraw would ring them again
+fn on_screen(data: &[u8]) -> (String, usize) {
+    let bells = data.iter().filter(|&&b| b == crate::bell::BEL).count();
+    let mut text = String::from_utf8_lossy(data).into_owned();
+    if bells > 0 {
+        text.retain(|c| c != '\x07');
+    }
+    (text, bells)
+}
+
+/// Run an interactive session on the port
+/// In line mode typed text is edited locally and sent on Enter, otherwise every key is sent as typed
+/// Ctrl-P opens a line for the `:` commands that change the port settings
+pub fn run(mut port: Box<dyn SerialPort>, args: &Args, line_mode: bool) -> io::Result<()> {
+    let mut guard = TerminalGuard::fullscreen()?;
+    if args.paste_safe {
+        guard.bracketed_paste()?;
+    }
+    session(port.as_mut(), &mut stdout(), args, line_mode)
+}
+
+/// Run a `:` command on the port, giving the message to show and the new status bar settings if they changed
+fn run_command(port: &mut dyn SerialPort, args: &Args, line: &str) -> (String, Option<String>) {
+    let command = match PortCommand::parse(line) {
+        Ok(command) => command,
+        Err(e) => return (e, None),
+    };
+    match command
+        .apply(port)
+        .and_then(|()| port_command::current(port))
+    {
+        Ok((baud_rate, framing)) => (
+            format!("Port is at {} baud, {}", baud_rate, framing),
+            Some(args.settings_with(baud_rate, &framing)),
+        ),
+        Err(e) => (format!(":{} failed: {}", line.trim(), e), None),
+    }
+}
+
+/// Send what was typed, or queue it behind the paste still being sent
+fn send(
+    port: &mut dyn SerialPort,
+    paced: &mut Paced,
+    screen: &mut Scrollback,
+    echo: bool,
+    bytes: Vec<u8>,
+    shown: String,
+) -> io::Result<()> {
+    if !paced.is_empty() {
+        paced.type_ahead(&bytes, &shown);
+        return Ok(());
+    }
+    port.write_all(&bytes)?;
+    if echo {
+        screen.push(&shown);
+    }
+    Ok(())
+}
+
+/// Queue the lines of a bracketed paste to be sent one at a time
+/// In lines mode they are edited into the input line, and what follows the last line ending is left there
+fn queue_paste(
+    paced: &mut Paced,
+    input: &mut LineEditor,
+    text: &str,
+    args: &Args,
+    line_mode: bool,
+    hex_input: bool,
+) -> Result<(), String> {
+    if !line_mode {
+        // Sent as if typed, Enter sends a newline
+        let (lines, rest) = paste::split(text);
+        for line in lines.into_iter().map(|line| line + "\n").chain(Some(rest)) {
+            if !line.is_empty() {
+                let shown = echo_text(line.as_bytes());
+                paced.push(line.into_bytes(), shown);
+            }
+        }
+        return Ok(());
+    }
+    let lines = input.paste(text);
+    let sent: Result<Vec<_>, _> = lines
+        .iter()
+        .map(|line| {
+            if hex_input {
+                parse_hex_line(line)
+            } else {
+                Ok([line.as_bytes(), args.output_eol.bytes()].concat())
+            }
+        })
+        .collect();
+    let sent = sent.map_err(|e| format!("Nothing pasted was sent: {}", e))?;
+    for (bytes, line) in sent.into_iter().zip(lines) {
+        paced.push(bytes, format!("{}\n", line));
+    }
+    Ok(())
+}
+
+fn session(
+    port: &mut dyn SerialPort,
+    term: &mut impl Write,
+    args: &Args,
+    line_mode: bool,
+) -> io::Result<()> {
+    let mut buf = vec![0; args.capacity];
+    let mut screen = Scrollback::new(args.scrollback);
+    let mut input = LineEditor::new();
+    if let Some(path) = &args.history_file {
+        input.load_history(path.as_ref())?;
+    }
+    let mut stats = Stats::new(Instant::now(), &args.tokens);
+    let mut rates = Rates::default();
+    let mut history = History::new(args.sparkline);
+    let mut hex_input = args.hex_input;
+    let mut settings = args.settings();
+    let mut command: Option<LineEditor> = None;
+    let mut paced = Paced::new(args.line_delay, Instant::now());
+    let mut message = None;
+    let mut clipboard = Clipboard::connect();
+    let mut dirty = true;
+    let echo = local_echo(args.local_echo);
+    let mut log = match &args.log {
+        Some(path) => Some(BufWriter::with_capacity(
+            args.capacity,
+            crate::create_output(path, args.append)?,
+        )),
+        None => None,
+    };
+
+    loop {
+        // Stopped from outside, Ctrl-C itself arrives as a key
+        if crate::STOP.load(std::sync::atomic::Ordering::Relaxed) {
+            break;
+        }
+        if event::poll(Duration::from_millis(10))? {
+            match event::read()? {
+                Event::Key(key) if key.kind != KeyEventKind::Release => {
+                    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                    if key.code == KeyCode::Char('c') && ctrl {
+                        break;
+                    }
+                    message = None;
+                    let page = page_rows(line_mode || command.is_some())?;
+                    match key.code {
+                        KeyCode::Esc if command.is_some() => command = None,
+                        _ if command.is_some() => {
+                            if let Some(line) = command.as_mut().unwrap().key(key) {
+                                command = None;
+                                let (shown, changed) = run_command(port, args, &line);
+                                message = Some(shown);
+                                settings = changed.unwrap_or(settings);
+                            }
+                        }
+                        KeyCode::Char('p') if ctrl => command = Some(LineEditor::new()),
+                        code if args.macros.contains_key(&code) => {
+                            let bytes = args.macros[&code].clone();
+                            let shown = echo_text(&bytes);
+                            send(port, &mut paced, &mut screen, echo, bytes, shown)?;
+                        }
+                        KeyCode::PageUp => screen.scroll_up(page.saturating_sub(1), page),
+                        KeyCode::PageDown => screen.scroll_down(page.saturating_sub(1)),
+                        KeyCode::Home => screen.top(page),
+                        KeyCode::End => screen.bottom(),
+                        KeyCode::Char('x') if line_mode && ctrl => hex_input = !hex_input,
+                        KeyCode::Char('y') if ctrl => {
+                            let lines: Vec<&str> = match args.copy_lines {
+                                Some(n) => screen.last(n).map(String::as_str).collect(),
+                                None => screen.visible(page).map(String::as_str).collect(),
+                            };
+                            let term_name = std::env::var("TERM").ok();
+                            let text = lines.join("\n");
+                            message =
+                                Some(match clipboard.copy(term, term_name.as_deref(), &text)? {
+                                    Some(Copied::System) => {
+                                        format!("Copied {} lines to the clipboard", lines.len())
+                                    }
+                                    Some(Copied::Terminal) => format!(
+                                        "Asked the terminal to copy {} lines, if it allows OSC 52",
+                                        lines.len()
+                                    ),
+                                    None => {
+                                        "No clipboard, and this terminal can't be asked to copy"
+                                            .to_string()
+                                    }
+                                });
+                        }
+                        KeyCode::Char('s') if ctrl => {
+                            let path = snapshot_name(&chrono::Local::now());
+                            let text = screen.render(page, args.snapshot_context);
+                            message = Some(match std::fs::write(&path, text) {
+                                Ok(()) => format!("Saved the screen to {}", path),
+                                Err(e) => format!("Could not save the screen to {}: {}", path, e),
+                            });
+                        }
+                        KeyCode::Char('k') if ctrl => {
+                            let text = if line_mode {
+                                input.take()
+                            } else {
+                                String::new()
+                            };
+                            let mut mark = marker(crate::timestamp(&args.timestamp_format), &text);
+                            if !screen.at_line_start() {
+                                mark.insert(0, '\n');
+                            }
+                            mark.push('\n');
+                            screen.push(&mark);
+                            if let Some(log) = &mut log {
+                                log.write_all(mark.as_bytes())?;
+                                // So the mark lands in the file next to the data it marks
+                                log.flush()?;
+                            }
+                        }
+                        _ if line_mode => {
+                            if let Some(line) = input.key(key) {
+                                let bytes = if hex_input {
+                                    parse_hex_line(&line)
+                                } else {
+                                    Ok([line.as_bytes(), args.output_eol.bytes()].concat())
+                                };
+                                match bytes {
+                                    // Always a new line, whatever --output-eol sends
+                                    Ok(bytes) => {
+                                        let shown = format!("{}\n", line);
+                                        send(port, &mut paced, &mut screen, echo, bytes, shown)?;
+                                    }
+                                    Err(e) => {
+                                        message = Some(format!("Not sent: {}", e));
+                                        input.set_text(&line);
+                                    }
+                                }
+                            }
+                        }
+                        _ => {
+                            if let Some(bytes) = key_bytes(key) {
+                                let shown = echo_text(&bytes);
+                                send(port, &mut paced, &mut screen, echo, bytes, shown)?;
+                            }
+                        }
+                    }
+                    dirty = true;
+                }
+                Event::Paste(text) if command.is_none() => {
+                    let queued =
+                        queue_paste(&mut paced, &mut input, &text, args, line_mode, hex_input);
+                    message = queued.err();
+                    dirty = true;
+                }
+                Event::Resize(..) => dirty = true,
+                _ => {}
+            }
+        }
+
+        while let Some((bytes, shown)) = paced.next(Instant::now()) {
+            port.write_all(&bytes)?;
+            if echo {
+                screen.push(&shown);
+            }
+            dirty = true;
+        }
+
+        match port.read(&mut buf[..args.read_size()]) {
+            Ok(n) => {
+                if args.strip_parity {
+                    crate::bytemap::strip_parity(&mut buf[..n]);
+                }
+                stats.count_bytes(&buf[..n]);
+                let (n, rung) = args.bell.apply(&mut buf[..n]);
+                if rung > 0 && args.bell == Bell::Visual {
+                    message = Some("Bell from the device".to_string());
+                    dirty = true;
+                }
+                stats.count_text(&buf[..n]);
+                let (text, bells) = on_screen(&buf[..n]);
+                // Only an audible bell is still there, it goes straight to the terminal as it arrives
+                if bells > 0 {
+                    term.write_all(&vec![crate::bell::BEL; bells])?;
+                }
+                screen.push(&text);
+                if args.complete_output {
+                    input.words.learn_output(&text);
+                }
+                if let Some(log) = &mut log {
+                    log.write_all(&buf[..n])?;
+                }
+                dirty |= n > 0;
+            }
+            Err(ref e) if crate::severity(e) == crate::Severity::Recoverable => {}
+            Err(e) => return Err(e),
+        }
+
+        if let Some(new) = stats.tick(Instant::now()) {
+            history.push(new.bytes);
+            rates = new;
+            dirty = true;
+        }
+
+        if dirty {
+            let prompt = if hex_input { "hex> " } else { "> " };
+            let text = match &command {
+                Some(command) => command.text(),
+                None => input.text(),
+            };
+            let input = match &command {
+                Some(command) => Some((":", text.as_str(), command.cursor())),
+                None if line_mode => Some((prompt, text.as_str(), input.cursor())),
+                None => None,
+            };
+            let status = match &message {
+                Some(message) => message.clone(),
+                None if !paced.is_empty() => {
+                    format!(
+                        "{} | pasting, {} lines to go",
+                        status_line(&settings, &rates, &history.render()),
+                        paced.len()
+                    )
+                }
+                None if screen.offset() > 0 => {
+                    format!(
+                        "{} | scrolled up {}",
+                        status_line(&settings, &rates, &history.render()),
+                        screen.offset()
+                    )
+                }
+                None => status_line(&settings, &rates, &history.render()),
+            };
+            draw(term, &screen, &status, input)?;
+            dirty = false;
+        }
+    }
+    if let Some(path) = &args.history_file {
+        input.save_history(path.as_ref())?;
+    }
+    match &mut log {
+        Some(log) => log.flush(),
+        None => Ok(()),
+    }
+}
+
+/// Parse a line of hex such as `02 10 ff` or `0x0210ff` into the bytes it spells out
+pub fn parse_hex_line(s: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    for word in s.split_whitespace() {
+        let digits = word
+            .strip_prefix("0x")
+            .or_else(|| word.strip_prefix("0X"))
+            .unwrap_or(word);
+        if let Some(c) = digits.chars().find(|c| !c.is_ascii_hexdigit()) {
+            return Err(format!("'{}' is not a hex digit in \"{}\"", c, word));
+        }
+        if digits.len() % 2 != 0 {
+            return Err(format!("\"{}\" has an odd number of hex digits", word));
+        }
+        for pair in digits.as_bytes().chunks(2) {
+            let pair = std::str::from_utf8(pair).unwrap();
+            bytes.push(u8::from_str_radix(pair, 16).unwrap());
+        }
+    }
+    Ok(bytes)
+}
+
+/// The bytes a terminal would send for this key
+fn key_bytes(key: KeyEvent) -> Option<Vec<u8>> {
+    Some(match key.code {
+        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            vec![(c.to_ascii_uppercase() as u8) & 0x1f]
+        }
+        KeyCode::Char(c) => c.to_string().into_bytes(),
+        KeyCode::Enter => b"\n".to_vec(),
+        KeyCode::Backspace => vec![0x7f],
+        KeyCode::Tab => b"\t".to_vec(),
+        KeyCode::Esc => vec![0x1b],
+        _ => return None,
+    })
+}
+
+/// Rows available for received data, the status bar and input line take the rest
+fn page_rows(line_mode: bool) -> io::Result<usize> {
+    let (_, height) = terminal::size()?;
+    Ok(height.saturating_sub(1 + line_mode as u16) as usize)
+}
+
+fn draw(
+    term: &mut impl Write,
+    screen: &Scrollback,
+    status: &str,
+    input: Option<(&str, &str, usize)>,
+) -> io::Result<()> {
+    let (width, height) = terminal::size()?;
+    let width = width as usize;
+    let rows = height.saturating_sub(1 + input.is_some() as u16);
+
+    let mut lines = screen.visible(rows as usize);
+    for row in 0..rows {
+        queue!(term, cursor::MoveTo(0, row), Clear(ClearType::CurrentLine))?;
+        if let Some(line) = lines.next() {
+            let line: String = line.chars().take(width).collect();
+            queue!(term, Print(line))?;
+        }
+    }
+
+    let status: String = format!("{:width$}", status, width = width)
+        .chars()
+        .take(width)
+        .collect();
+    queue!(
+        term,
+        cursor::MoveTo(0, height.saturating_sub(1)),
+        SetAttribute(Attribute::Reverse),
+        Print(status),
+        SetAttribute(Attribute::Reset),
+    )?;
+
+    if let Some((prompt, input, at)) = input {
+        let column = (prompt.chars().count() + at) as u16;
+        queue!(
+            term,
+            cursor::MoveTo(0, rows),
+            Clear(ClearType::CurrentLine),
+            Print(prompt),
+            Print(input),
+            cursor::MoveTo(column, rows),
+        )?;
+    }
+    term.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serialport::Parity;
+    use std::collections::HashMap;
+
+    #[test]
+    fn status_line_shows_the_settings_and_throughput() {
+        let args = Args {
+            port: String::from("/dev/ttyUSB0"),
+            baud_rate: 9600,
+            parity: Parity::Even,
+            mode: String::from("iterm"),
+            convertfrom: crate::ConvertFrom::HEX,
+            ..Args::default()
+        };
+        let start = Instant::now();
+        let mut stats = Stats::new(start, &args.tokens);
+        stats.count_bytes(&[0; 2500]);
+        let rates = stats.tick(start + Duration::from_secs(2)).unwrap();
+        assert_eq!(
+            status_line(&args.settings(), &rates, ""),
+            "/dev/ttyUSB0 | 9600 | 8E1 | iterm | HEX | 1250 B/s"
+        );
+        assert_eq!(
+            status_line(&args.settings(), &rates, "▁█"),
+            "/dev/ttyUSB0 | 9600 | 8E1 | iterm | HEX | ▁█ 1250 B/s"
+        );
+    }
+
+    #[test]
+    fn macros_map_function_keys_to_unescaped_bytes() {
+        let specs = ["F1=AT\\r\\n", "f12=\\x02go\\x03", "F5= a=b "];
+        let macros: HashMap<_, _> = specs.iter().map(|s| parse_macro(s).unwrap()).collect();
+        assert_eq!(macros[&KeyCode::F(1)], b"AT\r\n");
+        assert_eq!(macros[&KeyCode::F(12)], b"\x02go\x03");
+        assert_eq!(macros[&KeyCode::F(5)], b" a=b ");
+    }
+
+    #[test]
+    fn bad_macros_are_rejected() {
+        assert!(parse_macro("F1").unwrap_err().contains("KEY=STRING"));
+        assert!(parse_macro("F13=x").unwrap_err().contains("F1 to F12"));
+        assert!(parse_macro("A=x").unwrap_err().contains("F1 to F12"));
+        assert!(parse_macro("F2=\\q")
+            .unwrap_err()
+            .contains("unknown escape"));
+    }
+
+    #[test]
+    fn hex_lines_are_parsed_into_bytes() {
+        assert_eq!(parse_hex_line("02 10 ff").unwrap(), [0x02, 0x10, 0xff]);
+        assert_eq!(
+            parse_hex_line(" 0x0210FF  0Xab ").unwrap(),
+            [0x02, 0x10, 0xff, 0xab]
+        );
+        assert!(parse_hex_line("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn odd_length_hex_is_rejected() {
+        assert_eq!(
+            parse_hex_line("02 1").unwrap_err(),
+            "\"1\" has an odd number of hex digits"
+        );
+        assert!(parse_hex_line("0x123").is_err());
+    }
+
+    #[test]
+    fn non_hex_is_rejected() {
+        assert_eq!(
+            parse_hex_line("02 g0").unwrap_err(),
+            "'g' is not a hex digit in \"g0\""
+        );
+        assert!(parse_hex_line("0x").is_ok());
+        assert!(parse_hex_line("hello").is_err());
+    }
+
+    #[test]
+    fn markers_carry_the_time_and_optional_text() {
+        assert_eq!(marker("12:00:01", ""), "--- MARK 12:00:01 ---");
+        assert_eq!(
+            marker("12:00:01", "door opened"),
+            "--- MARK 12:00:01 door opened ---"
+        );
+    }
+
+    #[test]
+    fn local_echo_is_on_unless_turned_off() {
+        assert!(local_echo(None));
+        assert!(local_echo(Some(true)));
+        assert!(!local_echo(Some(false)));
+    }
+
+    #[test]
+    fn echoed_line_endings_become_newlines() {
+        assert_eq!(echo_text(b"AT\r\n"), "AT\n");
+        assert_eq!(echo_text(b"AT\r"), "AT\n");
+        assert_eq!(echo_text(b"a\tb\x1b\x07c\n"), "a\tbc\n");
+    }
+
+    #[test]
+    fn typed_bytes_are_sent_whether_or_not_they_are_echoed() {
+        for echo in [true, false] {
+            let mut port = crate::mock::MockPort::default();
+            let mut paced = Paced::new(Duration::ZERO, Instant::now());
+            let mut screen = Scrollback::new(10);
+            let bytes = b"AT\r".to_vec();
+            let shown = echo_text(&bytes);
+            send(&mut port, &mut paced, &mut screen, echo, bytes, shown).unwrap();
+            assert_eq!(port.state().written, b"AT\r");
+            let on_screen: Vec<_> = screen.visible(10).cloned().collect();
+            if echo {
+                assert_eq!(on_screen, ["AT", ""]);
+            } else {
+                assert!(on_screen.iter().all(String::is_empty), "{:?}", on_screen);
+            }
+        }
+    }
+
+    #[test]
+    fn snapshots_are_named_after_when_they_were_taken() {
+        use chrono::TimeZone;
+        let now = chrono::Local.with_ymd_and_hms(2024, 3, 9, 7, 5, 1).unwrap();
+        assert_eq!(snapshot_name(&now), "oxterm-20240309-070501.txt");
+    }
+
+    #[test]
+    fn a_command_changes_the_port_and_the_status_bar() {
+        let mut port = crate::mock::MockPort::default();
+        let args = Args::default();
+        let (shown, settings) = run_command(&mut port, &args, ":baud 57600");
+        assert_eq!(shown, "Port is at 57600 baud, 8N1");
+        assert_eq!(settings, Some(args.settings_with(57600, "8N1")));
+        let (shown, settings) = run_command(&mut port, &args, ":baud nope");
+        assert_eq!(shown, "\"nope\" is not a baud rate");
+        assert_eq!(settings, None);
+    }
+
+    #[test]
+    fn a_multi_line_paste_is_queued_a_line_at_a_time() {
+        let args = Args::default();
+        let mut input = LineEditor::new();
+        input.set_text("AT");
+        let mut paced = Paced::new(Duration::ZERO, Instant::now());
+        queue_paste(
+            &mut paced,
+            &mut input,
+            "+GMR\r\nATI\nAT+",
+            &args,
+            true,
+            false,
+        )
+        .unwrap();
+        assert_eq!(input.text(), "AT+");
+        let queued: Vec<_> = std::iter::from_fn(|| paced.next(Instant::now())).collect();
+        assert_eq!(
+            queued,
+            [
+                (b"AT+GMR\n".to_vec(), String::from("AT+GMR\n")),
+                (b"ATI\n".to_vec(), String::from("ATI\n")),
+            ]
+        );
+
+        let mut input = LineEditor::new();
+        let e = queue_paste(&mut paced, &mut input, "0a 0b\nzz\n", &args, true, true);
+        assert!(e.unwrap_err().starts_with("Nothing pasted was sent"));
+        assert!(paced.is_empty());
+
+        queue_paste(&mut paced, &mut input, "one\ntwo", &args, false, false).unwrap();
+        let queued: Vec<_> = std::iter::from_fn(|| paced.next(Instant::now())).collect();
+        assert_eq!(queued[0].0, b"one\n");
+        assert_eq!(queued[1].0, b"two");
+    }
+
+    #[test]
+    fn bells_are_kept_off_the_screen_and_counted() {
+        assert_eq!(on_screen(b"ok\n"), (String::from("ok\n"), 0));
+        assert_eq!(on_screen(b"\x07done\x07\n"), (String::from("done\n"), 2));
+    }
+
+    #[test]
+    fn what_is_typed_during_a_paste_waits_behind_it() {
+        let mut port = crate::mock::MockPort::default();
+        let mut paced = Paced::new(Duration::from_secs(60), Instant::now());
+        let mut screen = Scrollback::new(10);
+        paced.push(b"pasted\n".to_vec(), String::from("pasted\n"));
+        // A macro, then two keys
+        for bytes in [&b"AT\r\n"[..], b"a", b"b"] {
+            let shown = echo_text(bytes);
+            send(
+                &mut port,
+                &mut paced,
+                &mut screen,
+                true,
+                bytes.to_vec(),
+                shown,
+            )
+            .unwrap();
+        }
+        assert!(port.state().written.is_empty());
+        assert_eq!(paced.len(), 2);
+        paced.next(Instant::now()).unwrap();
+        let later = Instant::now() + Duration::from_secs(60);
+        assert_eq!(
+            paced.next(later),
+            Some((b"AT\r\nab".to_vec(), String::from("AT\nab")))
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use std::io::{self, Write};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{ConvertFrom, Encoder};
+
+#[derive(Serialize)]
+struct Record {
+    ts: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    raw: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    b64: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    values: Option<Vec<Value>>,
+}
+
+/// Writes one JSON object per record
+/// Without a conversion a record is a line, otherwise it is `group` decoded values
+pub struct JsonlWriter {
+    convert: ConvertFrom,
+    group: usize,
+    timestamp_format: String,
+    label: Option<String>,
+    base64: bool,
+    carry: Vec<u8>,
+}
+
+impl JsonlWriter {
+    pub fn new(
+        convert: ConvertFrom,
+        group: usize,
+        timestamp_format: String,
+        label: Option<String>,
+        base64: bool,
+    ) -> Self {
+        JsonlWriter {
+            convert,
+            group: group.max(1),
+            timestamp_format,
+            label,
+            base64,
+            carry: Vec::new(),
+        }
+    }
+
+    fn record(
+        &self,
+        raw: &[u8],
+        values: Option<Vec<Value>>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        let record = Record {
+            ts: crate::timestamp(&self.timestamp_format).to_string(),
+            label: self.label.clone(),
+            raw: escape(raw),
+            b64: self.base64.then(|| crate::b64::encode(raw)),
+            values,
+        };
+        serde_json::to_writer(&mut *out, &record)?;
+        out.write_all(b"\n")
+    }
+}
+
+impl Encoder for JsonlWriter {
+    fn write(&mut self, data: &[u8], out: &mut dyn Write) -> io::Result<()> {
+        self.carry.extend_from_slice(data);
+
+        let mut used = 0;
+        if self.convert == ConvertFrom::NON {
+            while let Some(i) = self.carry[used..].iter().position(|&b| b == b'\n') {
+                self.record(&self.carry[used..used + i], None, out)?;
+                used += i + 1;
+            }
+        } else {
+            let width = self.convert.width();
+            for raw in self.carry.chunks_exact(width * self.group) {
+                let values = raw
+                    .chunks_exact(width)
+                    .map(|bytes| self.convert.json_value(bytes))
+                    .collect();
+                self.record(raw, Some(values), out)?;
+                used += raw.len();
+            }
+        }
+
+        self.carry.drain(..used);
+        Ok(())
+    }
+}
+
+/// Make arbitrary bytes safe to show as a string, non printables become escapes like `\x02`
+pub fn escape(raw: &[u8]) -> String {
+    raw.iter()
+        .flat_map(|&b| std::ascii::escape_default(b))
+        .map(char::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(writer: &mut JsonlWriter, reads: &[&[u8]]) -> String {
+        let mut out = Vec::new();
+        for data in reads {
+            writer.write(data, &mut out).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn int_stream_gives_one_object_per_group() {
+        // A format without any fields stamps every record the same
+        let mut writer = JsonlWriter::new(ConvertFrom::INT, 2, String::from("ts"), None, false);
+        let data: Vec<u8> = [1i32, -1, 258]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        assert_eq!(
+            lines(&mut writer, &[&data[..5], &data[5..]]),
+            concat!(
+                r#"{"ts":"ts","raw":"\\x01\\x00\\x00\\x00\\xff\\xff\\xff\\xff","values":[1,-1]}"#,
+                "\n"
+            )
+        );
+        let rest = 7i32.to_le_bytes();
+        assert_eq!(
+            lines(&mut writer, &[&rest]),
+            concat!(
+                r#"{"ts":"ts","raw":"\\x02\\x01\\x00\\x00\\x07\\x00\\x00\\x00","values":[258,7]}"#,
+                "\n"
+            )
+        );
+    }
+
+    #[test]
+    fn text_lines_carry_the_label_and_base64() {
+        let mut writer = JsonlWriter::new(
+            ConvertFrom::NON,
+            1,
+            String::from("ts"),
+            Some("a".into()),
+            true,
+        );
+        assert_eq!(
+            lines(&mut writer, &[b"hi\tthere\nhal", b"f"]),
+            concat!(
+                r#"{"ts":"ts","label":"a","raw":"hi\\tthere","b64":"aGkJdGhlcmU="}"#,
+                "\n"
+            )
+        );
+    }
+
+    #[test]
+    fn escape_keeps_printables() {
+        assert_eq!(escape(b"ok \"1\"\r\n\x7f"), "ok \\\"1\\\"\\r\\n\\x7f");
+    }
+}
@@ -0,0 +1,68 @@
+use std::io::{self, Write};
+
+/// Starts every line written through it with `[label] `, lines can be split over any number of writes
+/// Without a label everything is passed straight through
+pub struct Labeled<W> {
+    inner: W,
+    prefix: Option<Vec<u8>>,
+    line_start: bool,
+}
+
+impl<W: Write> Labeled<W> {
+    pub fn new(inner: W, label: Option<&str>) -> Self {
+        Labeled {
+            inner,
+            prefix: label.map(|label| format!("[{}] ", label).into_bytes()),
+            line_start: true,
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+}
+
+impl<W: Write> Write for Labeled<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let prefix = match &self.prefix {
+            Some(prefix) => prefix,
+            None => return self.inner.write(buf),
+        };
+        for line in buf.split_inclusive(|&b| b == b'\n') {
+            if self.line_start {
+                self.inner.write_all(prefix)?;
+            }
+            self.inner.write_all(line)?;
+            self.line_start = line.ends_with(b"\n");
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_lines_split_across_writes() {
+        let mut out = Labeled::new(Vec::new(), Some("gps"));
+        for chunk in [&b"fi"[..], b"rst\nsec", b"ond\n", b"\nthi", b"rd"] {
+            out.write_all(chunk).unwrap();
+        }
+        assert_eq!(
+            out.get_mut().as_slice(),
+            b"[gps] first\n[gps] second\n[gps] \n[gps] third"
+        );
+    }
+
+    #[test]
+    fn without_a_label_data_passes_through() {
+        let mut out = Labeled::new(Vec::new(), None);
+        out.write_all(b"a\nb").unwrap();
+        assert_eq!(out.get_mut().as_slice(), b"a\nb");
+    }
+}
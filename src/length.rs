@@ -0,0 +1,112 @@
+use crate::Endian;
+
+/// Splits a stream into frames that start with a `size` byte length of the payload after it
+/// Frames can span any number of reads, a length over `max` is taken as a lost sync
+pub struct LengthDecoder {
+    size: usize,
+    endian: Endian,
+    max: usize,
+    buf: Vec<u8>,
+    pub oversized: usize,
+}
+
+impl LengthDecoder {
+    pub fn new(size: usize, endian: Endian, max: usize) -> Self {
+        LengthDecoder {
+            size,
+            endian,
+            max,
+            buf: Vec::new(),
+            oversized: 0,
+        }
+    }
+
+    fn length(&self, header: &[u8]) -> usize {
+        let mut len = 0usize;
+        let mut add = |&b: &u8| len = len << 8 | usize::from(b);
+        match self.endian {
+            Endian::Little => header.iter().rev().for_each(&mut add),
+            Endian::Big => header.iter().for_each(&mut add),
+        }
+        len
+    }
+
+    /// Hand the payload of every frame completed by `data` to `frame`
+    pub fn feed(&mut self, data: &[u8], mut frame: impl FnMut(&[u8])) {
+        self.buf.extend_from_slice(data);
+        let mut start = 0;
+        while self.buf.len() - start >= self.size {
+            let len = self.length(&self.buf[start..start + self.size]);
+            if len > self.max {
+                // Step a byte at a time until a plausible header turns up again
+                self.oversized += 1;
+                start += 1;
+                continue;
+            }
+            let end = start + self.size + len;
+            if end > self.buf.len() {
+                break;
+            }
+            frame(&self.buf[start + self.size..end]);
+            start = end;
+        }
+        self.buf.drain(..start);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed `stream` in `chunk` byte reads, collecting the frames
+    fn frames(decoder: &mut LengthDecoder, stream: &[u8], chunk: usize) -> Vec<Vec<u8>> {
+        let mut frames = Vec::new();
+        for data in stream.chunks(chunk) {
+            decoder.feed(data, |frame| frames.push(frame.to_vec()));
+        }
+        frames
+    }
+
+    #[test]
+    fn one_byte_lengths_across_reads() {
+        let mut decoder = LengthDecoder::new(1, Endian::Little, 255);
+        let stream = b"\x03abc\x00\x02de";
+        assert_eq!(
+            frames(&mut decoder, stream, 2),
+            [b"abc".to_vec(), Vec::new(), b"de".to_vec()]
+        );
+    }
+
+    #[test]
+    fn two_byte_lengths_in_both_endiannesses() {
+        let payload = vec![0x5a; 300];
+        let mut le = LengthDecoder::new(2, Endian::Little, 1024);
+        let stream = [&[0x2c, 0x01][..], &payload].concat();
+        assert_eq!(frames(&mut le, &stream, 7), std::slice::from_ref(&payload));
+        let mut be = LengthDecoder::new(2, Endian::Big, 1024);
+        let stream = [&[0x01, 0x2c][..], &payload].concat();
+        assert_eq!(frames(&mut be, &stream, 7), [payload]);
+    }
+
+    #[test]
+    fn four_byte_lengths_split_in_the_header() {
+        let mut decoder = LengthDecoder::new(4, Endian::Big, 1024);
+        let stream = b"\x00\x00\x00\x05hello\x00\x00\x00\x01!";
+        assert_eq!(
+            frames(&mut decoder, stream, 3),
+            [b"hello".to_vec(), b"!".to_vec()]
+        );
+        // Only a partial header so far
+        assert!(frames(&mut decoder, b"\x00\x00", 3).is_empty());
+    }
+
+    #[test]
+    fn an_oversized_length_is_skipped_until_the_sync_is_back() {
+        let mut decoder = LengthDecoder::new(2, Endian::Big, 16);
+        // 0xff00 is far over the maximum, so it is stepped over a byte at a time
+        let stream = b"\xff\x00\x02hi";
+        assert_eq!(frames(&mut decoder, stream, 10), [b"hi".to_vec()]);
+        assert_eq!(decoder.oversized, 1);
+        assert!(decoder.buf.is_empty());
+    }
+}
@@ -0,0 +1,148 @@
+use std::time::{Duration, Instant};
+
+use crate::Args;
+
+/// Stops the capture after a number of bytes, lines or an amount of time, 0 means no limit
+pub struct Limits {
+    max_bytes: usize,
+    max_lines: usize,
+    duration: Duration,
+    idle_timeout: Duration,
+    start: Instant,
+    last_data: Instant,
+    bytes: usize,
+    lines: usize,
+}
+
+impl Limits {
+    pub fn new(args: &Args, start: Instant) -> Self {
+        Limits {
+            max_bytes: args.max_bytes,
+            max_lines: args.max_lines,
+            duration: args.duration,
+            idle_timeout: args.idle_timeout,
+            start,
+            last_data: start,
+            bytes: 0,
+            lines: 0,
+        }
+    }
+
+    /// Whether the capture has run for its whole duration, or the source has been silent
+    /// for longer than the idle timeout, at the instant `now`
+    pub fn expired(&self, now: Instant) -> bool {
+        let over = |limit: Duration, since: Instant| limit > Duration::ZERO && now - since >= limit;
+        over(self.duration, self.start) || over(self.idle_timeout, self.last_data)
+    }
+
+    /// How much of the `data` read at `now` fits under the limits
+    /// and whether a limit has been reached (so the capture should stop)
+    pub fn take(&mut self, data: &[u8], now: Instant) -> (usize, bool) {
+        if !data.is_empty() {
+            self.last_data = now;
+        }
+
+        let mut len = data.len();
+        let mut done = false;
+
+        if self.max_bytes > 0 && self.bytes + len >= self.max_bytes {
+            len = self.max_bytes - self.bytes;
+            done = true;
+        }
+        if self.max_lines > 0 {
+            for (i, _) in data[..len].iter().enumerate().filter(|(_, &b)| b == b'\n') {
+                self.lines += 1;
+                if self.lines == self.max_lines {
+                    len = i + 1;
+                    done = true;
+                    break;
+                }
+            }
+        }
+
+        self.bytes += len;
+        (len, done)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_limit_cuts_the_read_that_reaches_it() {
+        let args = Args {
+            max_bytes: 10,
+            ..Args::default()
+        };
+        let now = Instant::now();
+        let mut limits = Limits::new(&args, now);
+        assert_eq!(limits.take(b"123456", now), (6, false));
+        assert_eq!(limits.take(b"789abcdef", now), (4, true));
+    }
+
+    #[test]
+    fn byte_limit_stops_exactly_at_a_read_boundary() {
+        let args = Args {
+            max_bytes: 4,
+            ..Args::default()
+        };
+        let now = Instant::now();
+        let mut limits = Limits::new(&args, now);
+        assert_eq!(limits.take(b"12", now), (2, false));
+        assert_eq!(limits.take(b"34", now), (2, true));
+    }
+
+    #[test]
+    fn line_limit_stops_after_the_last_newline() {
+        let args = Args {
+            max_lines: 3,
+            ..Args::default()
+        };
+        let now = Instant::now();
+        let mut limits = Limits::new(&args, now);
+        assert_eq!(limits.take(b"a\nb", now), (3, false));
+        assert_eq!(limits.take(b"\nc\nd\ne\n", now), (3, true));
+    }
+
+    #[test]
+    fn duration_expires_at_the_boundary() {
+        let args = Args {
+            duration: Duration::from_secs(2),
+            ..Args::default()
+        };
+        let start = Instant::now();
+        let mut limits = Limits::new(&args, start);
+        // Data arriving doesn't extend the duration
+        limits.take(b"data", start + Duration::from_millis(1500));
+        assert!(!limits.expired(start + Duration::from_millis(1999)));
+        assert!(limits.expired(start + Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn no_limits_never_expire() {
+        let start = Instant::now();
+        let mut limits = Limits::new(&Args::default(), start);
+        assert_eq!(limits.take(&[b'\n'; 100], start), (100, false));
+        assert!(!limits.expired(start + Duration::from_secs(1_000_000)));
+    }
+
+    #[test]
+    fn idle_timeout_fires_once_the_data_stops() {
+        let args = Args {
+            idle_timeout: Duration::from_millis(500),
+            ..Args::default()
+        };
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut limits = Limits::new(&args, start);
+        for ms in [100, 450, 900] {
+            limits.take(b"x", at(ms));
+            assert!(!limits.expired(at(ms + 499)));
+        }
+        // Empty reads are timeouts, they aren't data
+        limits.take(b"", at(1200));
+        assert!(!limits.expired(at(1399)));
+        assert!(limits.expired(at(1400)));
+    }
+}
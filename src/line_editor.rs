@@ -0,0 +1,392 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Most lines kept in the history, the oldest are forgotten first
+const HISTORY: usize = 1000;
+/// Most words remembered for completion, the least recently seen are forgotten first
+const WORDS: usize = 500;
+
+/// The words that start with `prefix`, most recently seen first, leaving out `prefix` itself
+pub fn completions(words: &VecDeque<String>, prefix: &str) -> Vec<String> {
+    words
+        .iter()
+        .rev()
+        .filter(|word| word.starts_with(prefix) && word.as_str() != prefix)
+        .cloned()
+        .collect()
+}
+
+/// Words for completion, kept in the order they were last seen
+#[derive(Default)]
+pub struct Words {
+    words: VecDeque<String>,
+    /// The end of the device output, which may be the start of a word
+    partial: String,
+}
+
+impl Words {
+    pub fn learn(&mut self, text: &str) {
+        for word in text.split_whitespace() {
+            if let Some(i) = self.words.iter().position(|w| w == word) {
+                self.words.remove(i);
+            }
+            self.words.push_back(word.to_owned());
+        }
+        let extra = self.words.len().saturating_sub(WORDS);
+        self.words.drain(..extra);
+    }
+
+    /// Learn the words of received text, where a word can be split over reads
+    pub fn learn_output(&mut self, text: &str) {
+        self.partial.push_str(text);
+        let end = self.partial.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let complete: String = self.partial.drain(..end).collect();
+        self.learn(&complete);
+    }
+}
+
+/// The input line of lines mode, with readline style editing and history
+/// Ctrl-A and Ctrl-E move to the ends, Ctrl-W or Alt-Backspace deletes a word,
+/// Ctrl-U kills back to the start of the line, Up and Down go through the history
+#[derive(Default)]
+pub struct LineEditor {
+    line: Vec<char>,
+    cursor: usize,
+    history: Vec<String>,
+    /// Where in the history Up and Down have got to, with the line that was being typed
+    browsing: Option<(usize, String)>,
+    pub words: Words,
+    /// Where the word being completed starts, the candidates and which one is shown
+    completing: Option<(usize, Vec<String>, usize)>,
+}
+
+impl LineEditor {
+    pub fn new() -> Self {
+        LineEditor::default()
+    }
+
+    pub fn text(&self) -> String {
+        self.line.iter().collect()
+    }
+
+    /// Characters before the cursor
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.line = text.chars().collect();
+        self.cursor = self.line.len();
+    }
+
+    /// Insert pasted text at the cursor, giving the lines it ends, each as if Enter was pressed
+    /// Whatever follows the last line ending stays on the line to be edited
+    pub fn paste(&mut self, text: &str) -> Vec<String> {
+        let (mut lines, rest) = crate::paste::split(text);
+        let after: String = self.line.drain(self.cursor..).collect();
+        let before: String = self.line.drain(..).collect();
+        let rest = match lines.first_mut() {
+            Some(first) => {
+                first.insert_str(0, &before);
+                rest
+            }
+            None => before + &rest,
+        };
+        self.set_text(&rest);
+        self.line.extend(after.chars());
+        self.browsing = None;
+        self.completing = None;
+        for line in &lines {
+            self.add_history(line);
+        }
+        lines
+    }
+
+    /// Take the line as it is, for things like marks that use the typed text
+    pub fn take(&mut self) -> String {
+        let text = self.text();
+        self.set_text("");
+        text
+    }
+
+    pub fn add_history(&mut self, line: &str) {
+        self.words.learn(line);
+        if !line.is_empty() && self.history.last().map(String::as_str) != Some(line) {
+            self.history.push(line.to_owned());
+        }
+        let extra = self.history.len().saturating_sub(HISTORY);
+        self.history.drain(..extra);
+    }
+
+    /// Apply a key, returning the finished line on Enter
+    pub fn key(&mut self, key: KeyEvent) -> Option<String> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        if key.code == KeyCode::Tab {
+            self.complete();
+            return None;
+        }
+        self.completing = None;
+        match key.code {
+            KeyCode::Enter => {
+                self.browsing = None;
+                let line = self.take();
+                self.add_history(&line);
+                return Some(line);
+            }
+            KeyCode::Char('a') if ctrl => self.cursor = 0,
+            KeyCode::Char('e') if ctrl => self.cursor = self.line.len(),
+            KeyCode::Char('u') if ctrl => {
+                self.line.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            KeyCode::Char('w') if ctrl => self.delete_word(),
+            KeyCode::Backspace if alt => self.delete_word(),
+            KeyCode::Char(_) if ctrl || alt => {}
+            KeyCode::Char(c) => {
+                self.line.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.line.remove(self.cursor);
+            }
+            KeyCode::Delete if self.cursor < self.line.len() => {
+                self.line.remove(self.cursor);
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.line.len()),
+            KeyCode::Up => self.browse_back(),
+            KeyCode::Down => self.browse_forward(),
+            _ => {}
+        }
+        None
+    }
+
+    /// Complete the word before the cursor, going on to the next candidate on every Tab
+    fn complete(&mut self) {
+        let (start, candidates, index) = match self.completing.take() {
+            Some((start, candidates, index)) => {
+                let next = (index + 1) % candidates.len();
+                (start, candidates, next)
+            }
+            None => {
+                let start = self.line[..self.cursor]
+                    .iter()
+                    .rposition(|c| c.is_whitespace())
+                    .map_or(0, |i| i + 1);
+                let prefix: String = self.line[start..self.cursor].iter().collect();
+                let candidates = completions(&self.words.words, &prefix);
+                if prefix.is_empty() || candidates.is_empty() {
+                    return;
+                }
+                (start, candidates, 0)
+            }
+        };
+        let word: Vec<char> = candidates[index].chars().collect();
+        self.cursor = start + word.len();
+        self.line.splice(start..self.word_end(start), word);
+        self.completing = Some((start, candidates, index));
+    }
+
+    /// Where the word starting at `start` ends
+    fn word_end(&self, start: usize) -> usize {
+        self.line[start..]
+            .iter()
+            .position(|c| c.is_whitespace())
+            .map_or(self.line.len(), |i| start + i)
+    }
+
+    /// Delete back to the start of the word before the cursor
+    fn delete_word(&mut self) {
+        let mut start = self.cursor;
+        while start > 0 && self.line[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        while start > 0 && !self.line[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        self.line.drain(start..self.cursor);
+        self.cursor = start;
+    }
+
+    fn browse_back(&mut self) {
+        let index = match &self.browsing {
+            Some((0, _)) => return,
+            Some((i, _)) => i - 1,
+            None if self.history.is_empty() => return,
+            None => {
+                self.browsing = Some((self.history.len(), self.text()));
+                self.history.len() - 1
+            }
+        };
+        if let Some((i, _)) = &mut self.browsing {
+            *i = index;
+        }
+        let line = self.history[index].clone();
+        self.set_text(&line);
+    }
+
+    fn browse_forward(&mut self) {
+        let (index, draft) = match self.browsing.take() {
+            Some(browsing) => browsing,
+            None => return,
+        };
+        if index + 1 < self.history.len() {
+            let line = self.history[index + 1].clone();
+            self.browsing = Some((index + 1, draft));
+            self.set_text(&line);
+        } else {
+            self.set_text(&draft);
+        }
+    }
+
+    /// Read the history saved by an earlier session, a missing file is an empty history
+    pub fn load_history(&mut self, path: &Path) -> io::Result<()> {
+        match fs::read_to_string(path) {
+            Ok(text) => text.lines().for_each(|line| self.add_history(line)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        Ok(())
+    }
+
+    pub fn save_history(&self, path: &Path) -> io::Result<()> {
+        let mut text = self.history.join("\n");
+        text.push('\n');
+        fs::write(path, text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(editor: &mut LineEditor, code: KeyCode) -> Option<String> {
+        editor.key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn ctrl(editor: &mut LineEditor, c: char) {
+        editor.key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL));
+    }
+
+    fn type_text(editor: &mut LineEditor, text: &str) {
+        for c in text.chars() {
+            press(editor, KeyCode::Char(c));
+        }
+    }
+
+    #[test]
+    fn the_history_survives_a_save_and_load() {
+        let path = std::env::temp_dir().join(format!("oxterm-test-{}-history", std::process::id()));
+        let mut editor = LineEditor::new();
+        for line in ["AT", "AT+GMR", "AT+GMR", "", "ATZ"] {
+            editor.add_history(line);
+        }
+        editor.save_history(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "AT\nAT+GMR\nATZ\n");
+
+        let mut loaded = LineEditor::new();
+        loaded.load_history(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.history, editor.history);
+        // A first session has no file yet
+        let mut fresh = LineEditor::new();
+        fresh.load_history(&path).unwrap();
+        assert!(fresh.history.is_empty());
+    }
+
+    #[test]
+    fn up_and_down_go_through_the_history_and_back_to_the_draft() {
+        let mut editor = LineEditor::new();
+        editor.add_history("one");
+        editor.add_history("two");
+        type_text(&mut editor, "dra");
+        press(&mut editor, KeyCode::Up);
+        assert_eq!(editor.text(), "two");
+        press(&mut editor, KeyCode::Up);
+        press(&mut editor, KeyCode::Up);
+        assert_eq!(editor.text(), "one");
+        press(&mut editor, KeyCode::Down);
+        assert_eq!(editor.text(), "two");
+        press(&mut editor, KeyCode::Down);
+        assert_eq!(editor.text(), "dra");
+    }
+
+    #[test]
+    fn readline_keys_edit_the_line() {
+        let mut editor = LineEditor::new();
+        type_text(&mut editor, "set speed 9600");
+        ctrl(&mut editor, 'w');
+        assert_eq!(editor.text(), "set speed ");
+        type_text(&mut editor, "115200");
+        ctrl(&mut editor, 'a');
+        press(&mut editor, KeyCode::Delete);
+        type_text(&mut editor, "S");
+        assert_eq!(
+            (editor.text().as_str(), editor.cursor()),
+            ("Set speed 115200", 1)
+        );
+        ctrl(&mut editor, 'e');
+        press(&mut editor, KeyCode::Left);
+        press(&mut editor, KeyCode::Backspace);
+        assert_eq!(editor.text(), "Set speed 11520");
+        ctrl(&mut editor, 'u');
+        assert_eq!(editor.text(), "0");
+        assert_eq!(press(&mut editor, KeyCode::Enter), Some(String::from("0")));
+        assert_eq!(editor.text(), "");
+    }
+
+    #[test]
+    fn candidates_start_with_the_prefix_most_recent_first() {
+        let mut words = Words::default();
+        words.learn("status start stop status");
+        assert_eq!(words.words, ["start", "stop", "status"]);
+        assert_eq!(completions(&words.words, "st"), ["status", "stop", "start"]);
+        assert_eq!(completions(&words.words, "sta"), ["status", "start"]);
+        assert_eq!(completions(&words.words, "stop"), Vec::<String>::new());
+        assert!(completions(&words.words, "x").is_empty());
+    }
+
+    #[test]
+    fn the_word_set_is_bounded() {
+        let mut words = Words::default();
+        for i in 0..WORDS + 10 {
+            words.learn(&format!("w{}", i));
+        }
+        assert_eq!(words.words.len(), WORDS);
+        assert_eq!(words.words.front().map(String::as_str), Some("w10"));
+    }
+
+    #[test]
+    fn output_words_split_over_reads_are_learned_whole() {
+        let mut words = Words::default();
+        words.learn_output("ready: temper");
+        assert_eq!(words.words, ["ready:"]);
+        words.learn_output("ature\r\n");
+        assert_eq!(words.words, ["ready:", "temperature"]);
+    }
+
+    #[test]
+    fn tab_cycles_through_the_candidates() {
+        let mut editor = LineEditor::new();
+        editor.add_history("reboot reset");
+        type_text(&mut editor, "do re");
+        press(&mut editor, KeyCode::Tab);
+        assert_eq!(editor.text(), "do reset");
+        press(&mut editor, KeyCode::Tab);
+        assert_eq!(editor.text(), "do reboot");
+        press(&mut editor, KeyCode::Tab);
+        assert_eq!(editor.text(), "do reset");
+        type_text(&mut editor, " now");
+        assert_eq!(editor.text(), "do reset now");
+        // Nothing to complete
+        press(&mut editor, KeyCode::Char(' '));
+        press(&mut editor, KeyCode::Tab);
+        assert_eq!(editor.text(), "do reset now ");
+    }
+}
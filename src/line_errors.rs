@@ -0,0 +1,148 @@
+use std::fmt;
+
+/// Receive errors counted by the UART driver, which usually mean the baud rate or framing is wrong
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LineErrors {
+    pub framing: u64,
+    pub parity: u64,
+    pub overrun: u64,
+}
+
+impl LineErrors {
+    pub fn total(&self) -> u64 {
+        self.framing + self.parity + self.overrun
+    }
+
+    /// The errors counted since `earlier` was read
+    pub fn since(&self, earlier: &LineErrors) -> LineErrors {
+        LineErrors {
+            framing: self.framing.saturating_sub(earlier.framing),
+            parity: self.parity.saturating_sub(earlier.parity),
+            overrun: self.overrun.saturating_sub(earlier.overrun),
+        }
+    }
+
+    /// Whether there are so many errors for `bytes` received that the settings are probably wrong
+    pub fn suspicious(&self, bytes: usize) -> bool {
+        self.framing + self.parity > (bytes as u64 / 100).max(10)
+    }
+}
+
+impl fmt::Display for LineErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} framing, {} parity, {} overrun errors",
+            self.framing, self.parity, self.overrun
+        )
+    }
+}
+
+/// Reads the error counters of an open port, where the OS keeps them
+pub struct Counter {
+    #[cfg(target_os = "linux")]
+    fd: std::os::unix::io::RawFd,
+    start: LineErrors,
+}
+
+impl Counter {
+    /// None if the port has no error counters (ptys and most USB adapters don't)
+    #[cfg(target_os = "linux")]
+    pub fn new(fd: std::os::unix::io::RawFd) -> Option<Self> {
+        let start = icount(fd)?;
+        Some(Counter { fd, start })
+    }
+
+    /// The errors since the counter was made
+    pub fn read(&self) -> LineErrors {
+        #[cfg(target_os = "linux")]
+        let now = icount(self.fd).unwrap_or(self.start);
+        #[cfg(not(target_os = "linux"))]
+        let now = self.start;
+        now.since(&self.start)
+    }
+}
+
+/// `struct serial_icounter_struct` from linux/serial.h
+#[cfg(target_os = "linux")]
+#[repr(C)]
+#[derive(Default)]
+struct SerialIcounter {
+    cts: libc::c_int,
+    dsr: libc::c_int,
+    rng: libc::c_int,
+    dcd: libc::c_int,
+    rx: libc::c_int,
+    tx: libc::c_int,
+    frame: libc::c_int,
+    overrun: libc::c_int,
+    parity: libc::c_int,
+    brk: libc::c_int,
+    buf_overrun: libc::c_int,
+    reserved: [libc::c_int; 9],
+}
+
+#[cfg(target_os = "linux")]
+fn icount(fd: std::os::unix::io::RawFd) -> Option<LineErrors> {
+    let mut counts = SerialIcounter::default();
+    // Safety: TIOCGICOUNT only writes a serial_icounter_struct through the pointer
+    let res = unsafe { libc::ioctl(fd, libc::TIOCGICOUNT, &mut counts) };
+    if res != 0 {
+        return None;
+    }
+    Some(LineErrors {
+        framing: counts.frame as u64,
+        parity: counts.parity as u64,
+        overrun: (counts.overrun + counts.buf_overrun) as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_kind_of_error_is_counted_from_the_start() {
+        let start = LineErrors {
+            framing: 5,
+            parity: 1,
+            overrun: 0,
+        };
+        let now = LineErrors {
+            framing: 325,
+            parity: 3,
+            overrun: 7,
+        };
+        let errors = now.since(&start);
+        assert_eq!(
+            errors,
+            LineErrors {
+                framing: 320,
+                parity: 2,
+                overrun: 7,
+            }
+        );
+        assert_eq!(errors.total(), 329);
+        assert_eq!(
+            errors.to_string(),
+            "320 framing, 2 parity, 7 overrun errors"
+        );
+        // A counter reset by the driver doesn't go negative
+        assert_eq!(start.since(&now), LineErrors::default());
+    }
+
+    #[test]
+    fn many_errors_for_the_data_are_suspicious() {
+        let errors = |framing, overrun| LineErrors {
+            framing,
+            parity: 0,
+            overrun,
+        };
+        assert!(!errors(10, 0).suspicious(100));
+        assert!(errors(11, 0).suspicious(100));
+        assert!(!errors(11, 0).suspicious(10_000));
+        assert!(errors(101, 0).suspicious(10_000));
+        // Overruns are lost data, not wrong settings
+        assert!(!errors(0, 1000).suspicious(100));
+    }
+}
@@ -0,0 +1,209 @@
+use std::fmt;
+use std::io;
+use std::time::{Duration, Instant};
+
+use serialport::{ClearBuffer, SerialPort};
+
+/// Values that catch stuck or crossed bits, sent ahead of the counting bytes
+const EDGES: [u8; 4] = [0x00, 0xff, 0x55, 0xaa];
+
+/// The test pattern, the edge values then every byte value counting up
+/// `mask` keeps the bits that fit in the data bits of the port
+pub fn pattern(mask: u8) -> Vec<u8> {
+    EDGES
+        .iter()
+        .copied()
+        .chain(0..=255)
+        .map(|b| b & mask)
+        .collect()
+}
+
+/// A byte that came back different from how it was sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mismatch {
+    pub index: usize,
+    pub sent: u8,
+    pub got: u8,
+}
+
+/// What came back compared with what was sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comparison {
+    pub mismatches: Vec<Mismatch>,
+    /// Bytes that never came back, or extra ones that did
+    pub missing: usize,
+    pub extra: usize,
+}
+
+impl Comparison {
+    pub fn passed(&self) -> bool {
+        self.mismatches.is_empty() && self.missing == 0 && self.extra == 0
+    }
+}
+
+pub fn compare(sent: &[u8], got: &[u8]) -> Comparison {
+    Comparison {
+        mismatches: sent
+            .iter()
+            .zip(got)
+            .enumerate()
+            .filter(|(_, (s, g))| s != g)
+            .map(|(index, (&sent, &got))| Mismatch { index, sent, got })
+            .collect(),
+        missing: sent.len().saturating_sub(got.len()),
+        extra: got.len().saturating_sub(sent.len()),
+    }
+}
+
+/// How a loopback test went
+pub struct Report {
+    pub sent: usize,
+    pub comparison: Comparison,
+    /// From starting to send to the first byte coming back
+    pub latency: Option<Duration>,
+}
+
+/// Send the pattern and read it back, giving up `timeout` after the last byte arrived
+pub fn run(port: &mut dyn SerialPort, mask: u8, timeout: Duration) -> io::Result<Report> {
+    let sent = pattern(mask);
+    port.clear(ClearBuffer::All)?;
+    let start = Instant::now();
+    port.write_all(&sent)?;
+    port.flush()?;
+
+    let mut got = Vec::with_capacity(sent.len());
+    let mut latency = None;
+    let mut last = Instant::now();
+    let mut buf = [0; 256];
+    while got.len() < sent.len() && last.elapsed() < timeout {
+        match port.read(&mut buf) {
+            Ok(0) => std::thread::sleep(crate::IDLE_SLEEP),
+            Ok(n) => {
+                latency.get_or_insert_with(|| start.elapsed());
+                last = Instant::now();
+                got.extend_from_slice(&buf[..n]);
+            }
+            Err(ref e) if crate::severity(e) == crate::Severity::Recoverable => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(Report {
+        sent: sent.len(),
+        comparison: compare(&sent, &got),
+        latency,
+    })
+}
+
+/// How many mismatches are listed before the rest are just counted
+const SHOWN: usize = 16;
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let c = &self.comparison;
+        let verdict = if c.passed() { "PASS" } else { "FAIL" };
+        write!(f, "Loopback test {}: sent {} bytes", verdict, self.sent)?;
+        match self.latency {
+            Some(latency) => write!(
+                f,
+                ", first byte back after {:.1}ms",
+                latency.as_secs_f64() * 1000.0
+            )?,
+            None => write!(f, ", nothing came back (is TX connected to RX?)")?,
+        }
+        for m in c.mismatches.iter().take(SHOWN) {
+            write!(
+                f,
+                "\n  byte {:>3}: sent {:#04x} got {:#04x} (bits {:08b} differ)",
+                m.index,
+                m.sent,
+                m.got,
+                m.sent ^ m.got
+            )?;
+        }
+        if c.mismatches.len() > SHOWN {
+            write!(f, "\n  and {} more mismatches", c.mismatches.len() - SHOWN)?;
+        }
+        if c.missing > 0 && self.latency.is_some() {
+            write!(f, "\n  {} bytes never came back", c.missing)?;
+        }
+        if c.extra > 0 {
+            write!(f, "\n  {} more bytes came back than were sent", c.extra)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockPort;
+
+    #[test]
+    fn the_pattern_starts_with_the_edges_and_fits_the_data_bits() {
+        let full = pattern(0xff);
+        assert_eq!(full.len(), 260);
+        assert_eq!(full[..6], [0x00, 0xff, 0x55, 0xaa, 0x00, 0x01]);
+        assert_eq!(full[259], 0xff);
+        assert!(pattern(0x7f).iter().all(|&b| b <= 0x7f));
+    }
+
+    #[test]
+    fn a_matching_echo_passes() {
+        let sent = pattern(0xff);
+        let comparison = compare(&sent, &sent);
+        assert!(comparison.passed());
+        assert!(comparison.mismatches.is_empty());
+    }
+
+    #[test]
+    fn mismatched_missing_and_extra_bytes_fail() {
+        let comparison = compare(&[1, 2, 3, 4], &[1, 0x82, 3]);
+        assert!(!comparison.passed());
+        assert_eq!(
+            comparison.mismatches,
+            [Mismatch {
+                index: 1,
+                sent: 2,
+                got: 0x82
+            }]
+        );
+        assert_eq!((comparison.missing, comparison.extra), (1, 0));
+        let comparison = compare(&[1, 2], &[1, 2, 9]);
+        assert!(!comparison.passed());
+        assert_eq!((comparison.missing, comparison.extra), (0, 1));
+    }
+
+    #[test]
+    fn a_wired_port_passes_and_a_silent_one_fails() {
+        let mut port = MockPort::default();
+        port.state().input.extend(b"stale");
+        port.state().echo = true;
+        let report = run(&mut port, 0xff, Duration::from_millis(100)).unwrap();
+        assert!(report.comparison.passed(), "{}", report);
+        assert!(report.latency.is_some());
+        assert!(report
+            .to_string()
+            .starts_with("Loopback test PASS: sent 260 bytes"));
+
+        let mut port = MockPort::default();
+        let report = run(&mut port, 0xff, Duration::from_millis(20)).unwrap();
+        assert_eq!(report.comparison.missing, 260);
+        assert_eq!(
+            report.to_string(),
+            "Loopback test FAIL: sent 260 bytes, nothing came back (is TX connected to RX?)"
+        );
+    }
+
+    #[test]
+    fn the_report_shows_the_differing_bits() {
+        let report = Report {
+            sent: 4,
+            comparison: compare(&[0x55, 2, 3, 4], &[0x54, 2, 3]),
+            latency: Some(Duration::from_micros(1500)),
+        };
+        assert_eq!(
+            report.to_string(),
+            "Loopback test FAIL: sent 4 bytes, first byte back after 1.5ms\n  byte   0: sent 0x55 got 0x54 (bits 00000001 differ)\n  1 bytes never came back"
+        );
+    }
+}
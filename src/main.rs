@@ -1,18 +1,109 @@
-#![feature(array_chunks)]
-#![feature(with_options)]
+mod alert;
+mod b64;
+mod baud;
+mod bell;
+mod buffer_stats;
+mod buffering;
+mod bytemap;
+#[cfg(feature = "tui")]
+mod clipboard;
+mod cobs;
+mod columns;
+mod completions;
+mod crc;
+mod csv;
+#[cfg(feature = "tui")]
+mod dashboard;
+mod decimate;
+mod dedup;
+mod describe;
+mod env;
+mod escape;
+mod exit_code;
+#[cfg(feature = "regex")]
+mod expect;
+mod flow;
+mod format;
+mod framing;
+mod heartbeat;
+mod hexfile;
+mod histogram;
+mod idle_line;
+#[cfg(feature = "tui")]
+mod interactive;
+#[cfg(feature = "json")]
+mod jsonl;
+mod label;
+mod length;
+mod limits;
+#[cfg(feature = "tui")]
+mod line_editor;
+mod line_errors;
+mod loopback;
+#[cfg(test)]
+mod mock;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+mod multi;
+mod on_change;
+mod options;
+mod overrun;
+#[cfg(feature = "tui")]
+mod paste;
+mod picker;
+mod plot;
+#[cfg(feature = "tui")]
+mod port_command;
+mod profile;
+mod read_error;
+mod records;
+mod replay;
+mod rotate;
+mod script;
+#[cfg(feature = "tui")]
+mod scrollback;
+mod signals;
+#[cfg(feature = "tui")]
+mod sparkline;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod stats;
+#[cfg(feature = "json")]
+mod summary_json;
+#[cfg(feature = "tui")]
+mod term;
+#[cfg(feature = "tui")]
+mod terminal;
+mod timing;
+#[cfg(feature = "regex")]
+mod trigger;
+mod validate;
+mod watchdog;
+#[cfg(feature = "ws")]
+mod ws;
 
+use std::cell::Cell;
+#[cfg(feature = "tui")]
+use std::collections::HashMap;
+use std::convert::TryInto;
 use std::ffi::OsString;
-use std::fs::File;
+use std::fs::{File, OpenOptions};
 use std::io;
-use std::io::{stdout, BufWriter, Write};
+use std::io::{stdout, BufWriter, IsTerminal, Read, Seek, SeekFrom, Write};
 use std::process::exit;
 use std::str;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 use std::time::Instant;
 
+#[cfg(feature = "tui")]
+use crossterm::event::KeyCode;
 use serialport::{DataBits, FlowControl, Parity, SerialPort, StopBits};
 
+use exit_code::ExitCode;
+use read_error::{classify_read_error, ReadErrorAction};
+
 const HELP: &str = "\
 Simple Serial Terminal TUI in the shell 
 USAGE:
@@ -21,34 +112,216 @@ USAGE:
     String values for options are always case insensitive
 FLAGS:
 	-h, --help            Prints help information
+	-V, --version         Prints the version and build information
+	-q, --quiet           Don't print the throughput every second, the summary is still printed on exit
+	                      This is the default when stdout isn't a terminal
+	    --stats           Print the throughput every second even when stdout isn't a terminal
+	    --word-delim CHAR What ends a word in the counts, escapes as for --macro [default: space]
+	    --line-delim CHAR What ends a line in the counts, escapes as for --macro [default: \\n]
+	    --count-byte BYTE Also count this byte (decimal or 0xNN) in the throughput and summary, can be repeated
+	    --force-color     Use terminal escape codes on stderr even when stdout isn't a terminal
+	    --dry-run         Print the settings that would be used and exit without opening the port
+	    --histogram       Print how often each byte value was received on exit
+	    --detect-overrun  Warn when the OS input buffer gets close to full, which means data is about to be lost
+	    --summary-json PATH  Also write the exit summary as one JSON object to PATH, or to stderr for `-`
+	    --buffer-stats    Print how full the reads left the buffer on exit, for tuning --capacity and --max-read-size
+	    --list-profiles   List the saved profiles and exit
+	    --describe-port   Open the port, print its settings, modem lines and USB details, and exit
+	    --loopback-test   With TX wired to RX, send a test pattern, check it comes back unchanged and exit
+	                      Exits with an error if any byte is lost or changed
+	    --list-ports      Print the name of every serial port, one per line, and exit
+	    --completions SHELL  Print a completion script for bash, zsh or fish and exit
+	                      e.g. `source <(oxterm --completions bash)`
 OPTIONS:
 	-p --port         STRING     Serial port (/dev/tty* or COMx)
         Not needed if there is only one port
-        If not given and there are multiple ports we will just enumerate the ports
+        If not given and there are multiple ports, pick one from a menu (they are just listed when stdout isn't a terminal)
+	    --ports        LIST       Read several ports at once, comma separated, each as PORT or LABEL=PORT
+        Every line is written to stdout started with `[LABEL] `, the port's name by default
+	    --usb-product  STRING     Use the one USB port whose product name contains this (ignoring case)
+	    --usb-manufacturer STRING Use the one USB port whose manufacturer contains this (ignoring case)
+	    --profile      NAME       Also use the arguments saved as this profile, the ones given here win
+	    --save-profile NAME       Save the arguments (with those of --profile) as this profile, then carry on
+        Every option can also be set with an OXTERM_ variable named after it, e.g. OXTERM_PORT or
+        OXTERM_BAUD_RATE (OXTERM_BAUD for short), switches are set to 1 or 0
+        The command line wins over the environment, which wins over a --profile
 	-b --baud-rate    NUMBER     Baud rate to open with [ default: 115200 ]
+	    --detect-baud            Listen at the common baud rates and report which looks most like text
+	    --detect-and-run         Like --detect-baud, then carry on at the best rate
 	-d --data-bits    NUMBER     Data bits (5,6,7,8) [ default: 8 ]
 	-F --flow-control STRING     Flow control for the port (None, SW, HW) [default: None]
-	-P --parity       STRING     Which parity to use (None, odd, even) [default: None]
+        --xon         BYTE       XON character for software flow control, decimal or 0xNN [default: 0x11]
+        --xoff        BYTE       XOFF character for software flow control [default: 0x13]
+        Giving either of these makes oxterm do the software flow control itself
+	-P --parity       STRING     Which parity to use (None, odd, even, mark, space) [default: None]
+        Mark and space are not supported by the serial port library yet
 	-s --stop-bits    NUMBER     Number of stop bits (1, 2) [default: 1]
+	    --shared                 Don't claim the port for ourselves so other programs can open it too (alias --no-exclusive)
+	    --wait-for-port          Keep trying to open the port until it appears, e.g. while the device is plugged in
+	    --wait-timeout SECONDS   Give up waiting for the port after this long [default: 0 (forever)]
+        With --wait-for-port the streaming modes also wait for the port to come back when it disappears
+	    --read-retries NUMBER    Give up after this many passing read errors in a row, 0 never does [default: 10]
+	    --retry-backoff MS       Wait this long before retrying a read, doubling each time up to a second [default: 10]
+	    --open-delay  MS         Wait this long after opening the port, and after a --watchdog reset, before using it
+        For adapters and bootloaders that lose what is sent straight away [default: 0]
 	-T --timeout      NUMBER     Timeout (milliseconds) on receiving data [default: 0]
-    -m --mode         STRING     stdout, iterm, lines, file  [default: stdout]
+        With 0 reads return straight away, so we sleep for a few milliseconds whenever nothing has arrived
+    -m --mode         STRING     stdout, iterm, lines, term, file, csv, jsonl, sqlite, plot, mqtt, ws, dashboard  [default: stdout]
+        plot writes one converted value per line, for `feedgnuplot --stream` and the like
+        --plot-index             In plot mode, start each line with the index of the value
+        --plot-time              In plot mode, start each line with the seconds since the capture started
+        sqlite inserts every line or group of values into the `records` table of the --output-file database
+        mqtt publishes every line (or JSON record with --jsonl) as one message, needs the mqtt feature
+        --mqtt-broker HOST[:PORT] Broker to publish to [default: localhost:1883]
+        --mqtt-topic  STRING     Topic to publish on [default: oxterm]
+        --mqtt-qos    NUMBER     QoS of the published messages (0, 1, 2) [default: 0]
+        ws sends every line (or JSON record with --jsonl) to the browsers connected over WebSocket, needs the ws feature
+        Opening the address in a browser shows a page that follows the output
+        --ws-listen   ADDR:PORT  Where to serve the page and WebSocket, giving it selects ws mode [default: 127.0.0.1:8080]
+        --ws-duplex              Also write what the browsers send to the port, followed by --output-eol
+        dashboard shows the latest value of every --format field in place, with how long ago it arrived
+        iterm sends every key as it is typed, lines sends the edited line on Enter
+        Both show a status bar with the port settings and throughput, Ctrl-C quits
+        PageUp, PageDown, Home and End scroll back through the received lines
+        --sparkline   SECONDS    Graph the throughput of this many seconds in the status bar, 0 hides it [default: 20]
+        --no-local-echo          In the interactive modes, don't show what is sent, for devices that echo it back
+        --local-echo             Show what is sent as well as what is received [default]
+        In lines mode a sent line is shown ending in a newline whatever --output-eol sends
+        --scrollback  NUMBER     Lines of received data kept for scrolling back [default: 1000]
+        The input line is edited readline style: Left, Right, Ctrl-A, Ctrl-E, Ctrl-W, Ctrl-U, and Up/Down for history
+        --history-file PATH      Load the lines mode history from PATH and save it there on exit
+        Tab completes the word being typed from the words typed before, pressing it again cycles through them
+        --complete-output        Also complete from the words the device has sent
+        --hex-input              In lines mode, parse typed lines as hex bytes (e.g. `02 10 ff`), Ctrl-X toggles this
+        --log         PATH       Also write the received data to PATH (appending with -a)
+        Ctrl-K writes a `--- MARK <timestamp> ---` line to the screen and log, in lines mode with the typed text
+        Ctrl-Y copies the lines on screen to the system clipboard, or without one asks the terminal to (OSC 52)
+        --copy-lines  NUMBER     Make Ctrl-Y copy the last NUMBER received lines instead
+        Ctrl-S saves the lines on screen to `oxterm-<date>-<time>.txt` in the current directory
+        --snapshot-context NUMBER    Also save this many lines from above the screen with Ctrl-S [default: 0]
+        Ctrl-P opens a command line: `:baud 57600`, `:parity even`, `:databits 7` and `:stopbits 2` change the port
+        live, Esc cancels, and a command without a value shows the current settings
+        --paste-safe             Take a paste as a whole instead of as typed keys, then send its lines one every --line-delay
+        In lines mode the pasted text is edited into the input line, and what follows its last newline stays there
+        In term mode each line is sent as pasted, with its own line ending
+        --macro       KEY=STRING In the interactive modes, send STRING when KEY (F1 to F12) is pressed
+        Can be given multiple times. STRING may use the escapes \\n \\r \\t \\0 \\e \\\\ and \\xNN
+        term passes the terminal straight through like picocom: every byte typed is sent as is
+        and every byte received is written to the terminal, Ctrl-A q quits and Ctrl-A Ctrl-A sends Ctrl-A
     -o --output-file  PATH       File to write to (only relevant with `-m file`) [default: output.txt]
-    -a --append       PATH       In file mode we will append the new data [default: false]
+    -a --append                  In file mode append to the output file instead of overwriting it
+        --append-timestamp-to-filename  Expand strftime placeholders in --output-file (e.g. `capture-%Y%m%d-%H%M%S.txt`)
+        when starting, so every run gets its own file. Missing parent directories are created
+        --rotate-on-signal       In file mode, close and reopen the output file on SIGHUP (for logrotate)
     -C --capacity     NUMBER     Buffer capacity for stdout/file writing [default: 64] 
+        --max-read-size NUMBER   Read at most this many bytes at a time, so a big --capacity stays responsive [default: capacity]
         This is set quite low to be able to see live updates (and because UART is slow usually)
+        --buffering   STRING     When output is written out: line (after every newline), block (once --capacity
+        bytes have built up) or none (straight away) [default: line for a terminal, block otherwise]
         You could increase this a lot if you are writing to a file and/or using faster data
+        --start-trigger REGEX    Discard everything before the first line matching REGEX
+        --stop-trigger  REGEX    Stop after the first line matching REGEX
+        --repeat-trigger         After the stop trigger, wait for the start trigger again instead of stopping
+        --pretrigger  NUMBER     Also write up to this many of the bytes received just before the start trigger
+        --max-bytes   NUMBER     Stop after this many bytes have been read [default: 0 (no limit)]
+        --max-lines   NUMBER     Stop after this many lines have been read [default: 0 (no limit)]
+        --duration    SECONDS    Stop after capturing for this long [default: 0 (forever)]
+        --idle-timeout MS        Stop once no data has arrived for this long [default: 0 (never)]
+        --timeout-exit-code NUMBER   Exit status when --idle-timeout or --duration ran out before any data arrived [default: 5]
+        --newline-on-idle MS     End a partial line once nothing more has arrived for this long, so it is shown
+        --mark-partial           With --newline-on-idle, flag the lines it ended with ` [partial]`
+        --heartbeat   MS         Show `[waiting... Ns]` on stderr whenever no data has arrived for this long
+        --watchdog    MS         Reset the device when nothing has arrived for this long, then keep waiting
+        --monitor-signals MS     Read CTS, DSR, RI and CD this often and print a timestamped line when any changes
+        --reset-line  STRING     Which line is pulsed low to reset the device (DTR, RTS) [default: DTR]
+        --send-init   STRING     Send this to the port as soon as it is open, escapes as for --macro
+        --send-script PATH       Send this file to the port a line at a time while reading as usual
+        Lines starting with # are skipped
+        --expect-script PATH     Run a script of `send TEXT` and `expect REGEX [TIMEOUT_MS]` lines, then exit
+        Fails if an expect times out [default timeout: 5000ms]
+        --line-delay  MS         Wait this long after each line of --send-script or of a --paste-safe paste [default: 100]
+        --no-read                Only send --send-init and --send-script, then exit without reading anything
+        --send-ihex   PATH       Send an Intel HEX firmware file a record at a time, each followed by --output-eol, then exit
+        --send-srec   PATH       Send a Motorola S-record firmware file the same way
+        The records are checked before the port is opened, and progress is shown as a count of records sent
+        --ack-pattern REGEX      Wait for the device to send something matching this after each record [timeout: 5000ms]
+        --output-eol  STRING     Line ending sent after each line (lf, cr, crlf, none) [default: lf]
+        --once                   Stop after the first line, exiting with --timeout-exit-code if nothing arrived
+        Without --idle-timeout this waits 1000ms for data
+        --framing     STRING     Split the data into frames and pass on only their payloads (cobs, length)
+        cobs frames are split on zero bytes, length frames start with the length of the payload
+        --cobs                   Short for --framing cobs
+        --len-size    NUMBER     Bytes in the length header of --framing length (1, 2, 4) [default: 2]
+        --len-endian  STRING     Byte order of the length header (le, be) [default: le]
+        --max-frame   NUMBER     Longest payload believed, longer lengths are skipped as noise [default: 4096]
+        --crc         STRING     Check the CRC at the end of each frame (crc16-ccitt, crc32), stored in --endian byte order
+        Each frame is written as a line of hex ending in OK or BAD
+        --base64                 Write each line, or each frame with --framing, as one line of base64
+        With --jsonl this adds a `b64` field to every record
+        --record-delim STRING    End records at this instead of a newline, for the features that work a line at a time
+        Given as a string with escapes as for --macro (e.g. `;`, `\r`) or as hex bytes (e.g. 0x00, 0x0d0a)
+        Each record is written out ending in a newline
+        --strip-parity           Clear the high bit of every received byte, for 7 bit text with parity read as 8 data bits
+        Only makes sense for text, binary data uses all 8 bits
+        --bell        STRING     What to do with the BEL bytes received: audible (pass them on), visual (flash the screen
+        or the status bar instead) or ignore (drop them) [default: ignore]
+        Binary data (--convert, --framing, --format) is left alone
+        --map         FROM=TO,.. Replace each received FROM byte (decimal or 0xNN) with the string TO, escapes as for --macro
+        Can be given multiple times
     -c --convert      STRING     Perform some simple data conversion (all outputs human readable ascii)
         (NO OPT) just view/save the data, this essentially means ASCII
         HEX  convert every byte to hex representation
+        --hex-words   NUMBER     With HEX, show 2 or 4 byte words as one hex number each
+        --endian      STRING     Byte order of the words for --hex-words, of --crc and of --format fields (little, big) [default: little]
         BIN  convert every byte to binary representation
         INT  convert every 4 bytes from 32 bit integers 
         SHR  convert every 2 bytes from 16 bit integers 
         U*   unsigned variants of the above 2
         FLT  convert every 4 bytes from 32 bit floating points
-    -f --format       PATH       Path to file with parser format (unimplemented)
+        --decimate    NUMBER     Only show every Nth converted value, the stats still count everything
+        --decimate-lines NUMBER  Only show every Nth line
+        --emit-on-change         Only write a line, or a --format record, when it differs from the one before
+        --min-interval MS        With --emit-on-change, still write an unchanged one when this long has passed since the last
+        --alert-below NUMBER     Print an alert to stderr for every converted value below this
+        --alert-above NUMBER     Print an alert to stderr for every converted value above this
+        --alert-bell             Also ring the terminal bell on every alert
+        --csv                    Write the converted values as CSV (csv mode is stdout with this set)
+        --csv-header  STRING     Comma separated column names written as the first row
+        --group       NUMBER     Values per CSV row [default: number of header columns or 1]
+        --timestamp              Start every CSV row with a timestamp column
+        --jsonl                  Write one JSON object per line or group of values (jsonl mode is stdout with this set)
+        --columns                Line up the fields of delimited text lines into columns
+        --delimiter   CHAR       What separates the fields for --columns, escapes as for --macro [default: ,]
+        --label       STRING     Start every line with `[STRING] `, in CSV and JSON lines it is a label column/field after the timestamp
+        --timestamp-format STRING    strftime style format for timestamps [default: %Y-%m-%dT%H:%M:%S%.3f]
+    -f --format       PATH       Path to file with parser format
         Parse binary data into human-readable format for more efficient bandwidth usage
+        One field per line (or separated by `;`): TYPE NAME [*SCALE] [+OFFSET], e.g. `u16 temp *0.1 -40`
+        TYPE is u8, i8, u16, i16, u32, i32 or f32, in the byte order of --endian
+        A field of raw bytes is shown as base64, with its size after the name: `b64 id 8`
+        An integer field can be split into named bits instead: `u8 status { ready:0, error:1-2, mode:3-5 }`
+        Every record is written as one line of `name=value` pairs
+        --raw-values             With --format, show the values before scaling and offsetting
+        --struct      NUMBER     The size of the C struct the device sends, the --format fields must add up to it
+    -r --replay       PATH       Read data from a previously captured file instead of a serial port
+        --replay-rate     NUMBER     Throttle the replay to this many bytes per second
+        --replay-realtime            Throttle the replay to the rate implied by the baud rate and framing
+        Without either of these the replay runs as fast as possible
+        --replay-seek-bytes NUMBER   Start the replay this many bytes into the file
+        --replay-seek-time  MS       Start the replay with the data received this long into the capture
+        --replay-timing     PATH     The --timing log recorded with the capture, needed by --replay-seek-time
+    -t --timing       PATH       Also log the time since the previous byte as `delta_us\tbytes` lines
+        --timing-lines               Log the time between newline terminated lines rather than bytes
+EXIT STATUS:
+	0  Quit, a limit was reached or the input ran out
+	1  Invalid arguments or any other error
+	2  The port could not be opened
+	3  Reading stopped working, e.g. the port was unplugged
+	4  Writing the output stopped working, e.g. the disk is full
+	5  --idle-timeout or --duration ran out before any data arrived, see --timeout-exit-code
 ";
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum ConvertFrom {
     NON,
@@ -87,6 +360,116 @@ impl FromStr for ConvertFrom {
     }
 }
 
+impl ConvertFrom {
+    /// Number of bytes that make up one value
+    fn width(self) -> usize {
+        use ConvertFrom::*;
+        match self {
+            NON | HEX | BIN => 1,
+            SHR | USHR => 2,
+            INT | UINT | FLT => 4,
+        }
+    }
+
+    /// Write the single value held in `bytes`, which must be exactly `width()` long
+    fn write_value(self, bytes: &[u8], out: &mut dyn Write) -> io::Result<()> {
+        use ConvertFrom::*;
+        match self {
+            NON => out.write_all(bytes),
+            HEX => write!(out, "{:x}", bytes[0]),
+            BIN => write!(out, "{:b}", bytes[0]),
+            INT => write!(out, "{}", i32::from_le_bytes(bytes.try_into().unwrap())),
+            SHR => write!(out, "{}", i16::from_le_bytes(bytes.try_into().unwrap())),
+            UINT => write!(out, "{}", u32::from_le_bytes(bytes.try_into().unwrap())),
+            USHR => write!(out, "{}", u16::from_le_bytes(bytes.try_into().unwrap())),
+            FLT => write!(out, "{}", f32::from_le_bytes(bytes.try_into().unwrap())),
+        }
+    }
+
+    /// Convert every whole value in `data`, appending the text to `out`
+    /// Returns how many bytes were used, the rest are the start of a value split across reads
+    fn convert(self, data: &[u8], out: &mut Vec<u8>) -> io::Result<usize> {
+        let values = data.chunks_exact(self.width());
+        let used = data.len() - values.remainder().len();
+        for value in values {
+            self.write_value(value, out)?;
+        }
+        Ok(used)
+    }
+
+    /// The single value held in `bytes` as a number to compare against
+    fn number(self, bytes: &[u8]) -> f64 {
+        use ConvertFrom::*;
+        match self {
+            NON | HEX | BIN => bytes[0].into(),
+            INT => i32::from_le_bytes(bytes.try_into().unwrap()).into(),
+            SHR => i16::from_le_bytes(bytes.try_into().unwrap()).into(),
+            UINT => u32::from_le_bytes(bytes.try_into().unwrap()).into(),
+            USHR => u16::from_le_bytes(bytes.try_into().unwrap()).into(),
+            FLT => f32::from_le_bytes(bytes.try_into().unwrap()).into(),
+        }
+    }
+
+    /// The single value held in `bytes` as a JSON number
+    #[cfg(feature = "json")]
+    fn json_value(self, bytes: &[u8]) -> serde_json::Value {
+        use ConvertFrom::*;
+        match self {
+            NON | HEX | BIN => bytes[0].into(),
+            INT => i32::from_le_bytes(bytes.try_into().unwrap()).into(),
+            SHR => i16::from_le_bytes(bytes.try_into().unwrap()).into(),
+            UINT => u32::from_le_bytes(bytes.try_into().unwrap()).into(),
+            USHR => u16::from_le_bytes(bytes.try_into().unwrap()).into(),
+            FLT => f32::from_le_bytes(bytes.try_into().unwrap()).into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Endian {
+    Little,
+    Big,
+}
+
+impl FromStr for Endian {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "little" | "le" => Endian::Little,
+            "big" | "be" => Endian::Big,
+            _ => return Err("Endian option passed an invalid value"),
+        })
+    }
+}
+
+/// Write every whole `size` byte word in `data` as one hex number in `endian` byte order
+/// Returns how many bytes were used, like `ConvertFrom::convert`
+fn hex_words(data: &[u8], size: usize, endian: Endian, out: &mut Vec<u8>) -> usize {
+    let words = data.chunks_exact(size);
+    let used = data.len() - words.remainder().len();
+    for word in words {
+        let mut value = 0u32;
+        let mut add = |&b: &u8| value = value << 8 | u32::from(b);
+        match endian {
+            Endian::Little => word.iter().rev().for_each(&mut add),
+            Endian::Big => word.iter().for_each(&mut add),
+        }
+        write!(out, "{:01$x} ", value, size * 2).unwrap();
+    }
+    used
+}
+
+/// Turns the raw bytes into a structured output format
+trait Encoder {
+    fn write(&mut self, data: &[u8], out: &mut dyn Write) -> io::Result<()>;
+}
+
+/// Wall clock time used to stamp records, `format` is strftime style
+fn timestamp(format: &str) -> impl std::fmt::Display + '_ {
+    chrono::Local::now().format(format)
+}
+
 #[derive(Debug)]
 struct Args {
     port: String,
@@ -98,9 +481,133 @@ struct Args {
     timeout: Duration,
     mode: String,
     capacity: usize,
+    max_read_size: Option<usize>,
     output_file: OsString,
     append: bool,
     convertfrom: ConvertFrom,
+    replay: Option<OsString>,
+    replay_rate: Option<u32>,
+    replay_realtime: bool,
+    replay_seek_bytes: Option<u64>,
+    replay_seek_time: Option<Duration>,
+    replay_timing: Option<OsString>,
+    timing: Option<OsString>,
+    timing_lines: bool,
+    csv: bool,
+    csv_header: Vec<String>,
+    group: Option<usize>,
+    timestamp: bool,
+    jsonl: bool,
+    timestamp_format: String,
+    #[cfg(feature = "tui")]
+    macros: HashMap<KeyCode, Vec<u8>>,
+    #[cfg(feature = "tui")]
+    hex_input: bool,
+    scrollback: usize,
+    quiet: bool,
+    stats: bool,
+    /// Whether stderr output may use terminal escape codes
+    color: bool,
+    max_bytes: usize,
+    max_lines: usize,
+    duration: Duration,
+    idle_timeout: Duration,
+    send_init: Vec<u8>,
+    once: bool,
+    xon: Option<u8>,
+    xoff: Option<u8>,
+    heartbeat: Option<Duration>,
+    dry_run: bool,
+    shared: bool,
+    histogram: bool,
+    columns: bool,
+    delimiter: u8,
+    label: Option<String>,
+    wait_for_port: bool,
+    wait_timeout: Duration,
+    hex_words: Option<usize>,
+    endian: Endian,
+    map: Option<bytemap::ByteMap>,
+    strip_parity: bool,
+    tokens: stats::Tokens,
+    bell: bell::Bell,
+    #[cfg(feature = "regex")]
+    start_trigger: Option<regex::bytes::Regex>,
+    #[cfg(feature = "regex")]
+    stop_trigger: Option<regex::bytes::Regex>,
+    #[cfg(feature = "regex")]
+    repeat_trigger: bool,
+    #[cfg(feature = "regex")]
+    pretrigger: usize,
+    decimate: usize,
+    decimate_lines: usize,
+    #[cfg(feature = "tui")]
+    log: Option<OsString>,
+    #[cfg(feature = "tui")]
+    history_file: Option<OsString>,
+    #[cfg(feature = "tui")]
+    complete_output: bool,
+    #[cfg(feature = "tui")]
+    local_echo: Option<bool>,
+    #[cfg(feature = "tui")]
+    copy_lines: Option<usize>,
+    #[cfg(feature = "tui")]
+    snapshot_context: usize,
+    #[cfg(feature = "tui")]
+    sparkline: usize,
+    detect_baud: bool,
+    plot_index: bool,
+    plot_time: bool,
+    detect_and_run: bool,
+    watchdog: Option<Duration>,
+    reset_line: watchdog::ResetLine,
+    send_script: Option<OsString>,
+    expect_script: Option<OsString>,
+    line_delay: Duration,
+    output_eol: script::Eol,
+    mqtt_broker: String,
+    mqtt_topic: String,
+    mqtt_qos: u8,
+    rotate_on_signal: bool,
+    frames: Option<framing::Framing>,
+    len_size: usize,
+    len_endian: Endian,
+    max_frame: usize,
+    crc: Option<crc::Algorithm>,
+    record_delim: Option<Vec<u8>>,
+    base64: bool,
+    alert_below: Option<f64>,
+    alert_above: Option<f64>,
+    alert_bell: bool,
+    buffer_stats: bool,
+    detect_overrun: bool,
+    summary_json: Option<OsString>,
+    dated_output: bool,
+    format: Option<format::Format>,
+    raw_values: bool,
+    no_read: bool,
+    describe_port: bool,
+    monitor_signals: Option<Duration>,
+    loopback_test: bool,
+    buffering: Option<buffering::Buffering>,
+    timeout_exit_code: u8,
+    newline_on_idle: Option<Duration>,
+    mark_partial: bool,
+    ports: Vec<(String, String)>,
+    ws_listen: Option<String>,
+    ws_duplex: bool,
+    open_delay: Duration,
+    send_ihex: Option<OsString>,
+    send_srec: Option<OsString>,
+    #[cfg(feature = "regex")]
+    ack_pattern: Option<regex::bytes::Regex>,
+    read_retries: usize,
+    retry_backoff: Duration,
+    emit_on_change: bool,
+    min_interval: Option<Duration>,
+    record_size: Option<usize>,
+    #[cfg(feature = "tui")]
+    paste_safe: bool,
 }
 impl Default for Args {
     fn default() -> Self {
@@ -114,58 +621,474 @@ impl Default for Args {
             timeout: Duration::from_millis(0),
             mode: String::from("stdout"),
             capacity: 64,
+            max_read_size: None,
             output_file: "output.txt".into(),
             append: false,
             convertfrom: ConvertFrom::NON,
+            replay: None,
+            replay_rate: None,
+            replay_realtime: false,
+            replay_seek_bytes: None,
+            replay_seek_time: None,
+            replay_timing: None,
+            timing: None,
+            timing_lines: false,
+            csv: false,
+            csv_header: Vec::new(),
+            group: None,
+            timestamp: false,
+            jsonl: false,
+            timestamp_format: String::from("%Y-%m-%dT%H:%M:%S%.3f"),
+            #[cfg(feature = "tui")]
+            macros: HashMap::new(),
+            #[cfg(feature = "tui")]
+            hex_input: false,
+            scrollback: 1000,
+            quiet: false,
+            stats: false,
+            color: false,
+            max_bytes: 0,
+            max_lines: 0,
+            duration: Duration::ZERO,
+            idle_timeout: Duration::ZERO,
+            send_init: Vec::new(),
+            once: false,
+            xon: None,
+            xoff: None,
+            heartbeat: None,
+            dry_run: false,
+            shared: false,
+            histogram: false,
+            columns: false,
+            delimiter: b',',
+            label: None,
+            wait_for_port: false,
+            wait_timeout: Duration::ZERO,
+            hex_words: None,
+            endian: Endian::Little,
+            map: None,
+            strip_parity: false,
+            tokens: stats::Tokens::default(),
+            bell: bell::Bell::Ignore,
+            #[cfg(feature = "regex")]
+            start_trigger: None,
+            #[cfg(feature = "regex")]
+            stop_trigger: None,
+            #[cfg(feature = "regex")]
+            repeat_trigger: false,
+            #[cfg(feature = "regex")]
+            pretrigger: 0,
+            decimate: 0,
+            decimate_lines: 0,
+            #[cfg(feature = "tui")]
+            log: None,
+            #[cfg(feature = "tui")]
+            history_file: None,
+            #[cfg(feature = "tui")]
+            complete_output: false,
+            #[cfg(feature = "tui")]
+            local_echo: None,
+            #[cfg(feature = "tui")]
+            copy_lines: None,
+            #[cfg(feature = "tui")]
+            snapshot_context: 0,
+            #[cfg(feature = "tui")]
+            sparkline: 20,
+            detect_baud: false,
+            plot_index: false,
+            plot_time: false,
+            detect_and_run: false,
+            watchdog: None,
+            reset_line: watchdog::ResetLine::Dtr,
+            send_script: None,
+            expect_script: None,
+            line_delay: Duration::from_millis(100),
+            output_eol: script::Eol::Lf,
+            mqtt_broker: String::from("localhost:1883"),
+            mqtt_topic: String::from("oxterm"),
+            mqtt_qos: 0,
+            rotate_on_signal: false,
+            frames: None,
+            len_size: 2,
+            len_endian: Endian::Little,
+            max_frame: 4096,
+            crc: None,
+            record_delim: None,
+            base64: false,
+            alert_below: None,
+            alert_above: None,
+            alert_bell: false,
+            buffer_stats: false,
+            detect_overrun: false,
+            summary_json: None,
+            dated_output: false,
+            format: None,
+            raw_values: false,
+            no_read: false,
+            describe_port: false,
+            monitor_signals: None,
+            loopback_test: false,
+            buffering: None,
+            timeout_exit_code: exit_code::TIMEOUT,
+            newline_on_idle: None,
+            mark_partial: false,
+            ports: Vec::new(),
+            ws_listen: None,
+            ws_duplex: false,
+            open_delay: Duration::ZERO,
+            send_ihex: None,
+            send_srec: None,
+            #[cfg(feature = "regex")]
+            ack_pattern: None,
+            read_retries: 10,
+            retry_backoff: Duration::from_millis(10),
+            emit_on_change: false,
+            min_interval: None,
+            record_size: None,
+            #[cfg(feature = "tui")]
+            paste_safe: false,
+        }
+    }
+}
+impl Args {
+    /// Most bytes taken from the port in one read, at most the buffer --capacity
+    fn read_size(&self) -> usize {
+        self.max_read_size
+            .map_or(self.capacity, |n| n.clamp(1, self.capacity))
+    }
+
+    /// The framing the port is opened with
+    fn framing(&self) -> String {
+        framing(self.data_bits, self.parity, self.stop_bits)
+    }
+
+    /// The port settings as shown in the interactive status bar
+    fn settings(&self) -> String {
+        self.settings_with(self.baud_rate, &self.framing())
+    }
+
+    /// The status bar settings for a port whose baud rate or framing has changed since it was opened
+    fn settings_with(&self, baud_rate: u32, framing: &str) -> String {
+        format!(
+            "{} | {} | {} | {} | {:?}",
+            self.port, baud_rate, framing, self.mode, self.convertfrom
+        )
+    }
+
+    /// What --dry-run shows, one `Name: value` line for each setting with the defaults filled in
+    /// The port and the limits are always there, the other settings only when they are used
+    fn resolved(&self) -> Vec<String> {
+        let ms = |d: Duration| format!("{}ms", d.as_millis());
+        let limit = |n: usize| match n {
+            0 => String::from("none"),
+            n => n.to_string(),
+        };
+        let time_limit = |d: Duration| match d {
+            Duration::ZERO => String::from("none"),
+            d => ms(d),
+        };
+        let mut lines = vec![
+            format!("Port: {}", self.port),
+            format!("Baud rate: {}", self.baud_rate),
+            format!("Framing: {}", self.framing()),
+            format!("Flow control: {:?}", self.flow_control),
+            format!("Timeout: {}", ms(self.timeout)),
+            format!("Mode: {}", self.mode),
+            format!("Convert: {:?}", self.convertfrom),
+            format!("Capacity: {}", self.capacity),
+            format!("Read size: {}", self.read_size()),
+            format!("Max bytes: {}", limit(self.max_bytes)),
+            format!("Max lines: {}", limit(self.max_lines)),
+            format!("Duration: {}", time_limit(self.duration)),
+            format!("Idle timeout: {}", time_limit(self.idle_timeout)),
+        ];
+        if self.mode.eq_ignore_ascii_case("file") {
+            let how = if self.append {
+                "appended to"
+            } else {
+                "replaced"
+            };
+            lines.push(format!(
+                "Output file: {} ({})",
+                self.output_file.to_string_lossy(),
+                how
+            ));
+        }
+        if let Some(replay) = &self.replay {
+            lines.push(format!("Replay: {}", replay.to_string_lossy()));
+        }
+        if self.wait_for_port {
+            lines.push(format!("Wait for port: {}", time_limit(self.wait_timeout)));
+        }
+        if let Some(format) = &self.format {
+            lines.push(format!("Format: {} bytes a record", format.width()));
+        }
+        if let Some(label) = &self.label {
+            lines.push(format!("Label: {}", label));
+        }
+        lines
+    }
+}
+
+/// Data bits, parity and stop bits in the usual short form, e.g. 8N1
+fn framing(data_bits: DataBits, parity: Parity, stop_bits: StopBits) -> String {
+    let data = match data_bits {
+        DataBits::Five => 5,
+        DataBits::Six => 6,
+        DataBits::Seven => 7,
+        DataBits::Eight => 8,
+    };
+    let parity = match parity {
+        Parity::None => 'N',
+        Parity::Odd => 'O',
+        Parity::Even => 'E',
+    };
+    let stop = match stop_bits {
+        StopBits::One => 1,
+        StopBits::Two => 2,
+    };
+    format!("{}{}{}", data, parity, stop)
+}
+
+/// How long to wait before reading again when a port with no timeout had nothing for us
+const IDLE_SLEEP: Duration = Duration::from_millis(5);
+
+/// Set by Ctrl-C so the streaming loop can stop and print its summary
+static STOP: AtomicBool = AtomicBool::new(false);
+
+/// What both Ctrl-C and SIGTERM (a service being stopped) do: ask the running mode to stop,
+/// so it flushes and closes its output and exits normally instead of losing buffered data
+fn shutdown() {
+    STOP.store(true, Ordering::Relaxed);
+}
+
+/// Parse a delimiter given as one ASCII character, which may be an escape
+fn single_char(s: &str) -> Result<u8, String> {
+    match escape::unescape(s)?.as_slice() {
+        &[b] if b.is_ascii() => Ok(b),
+        _ => Err(format!(
+            "delimiter \"{}\" should be a single ASCII character",
+            s
+        )),
+    }
+}
+
+/// Parse a `--parity` value, mark and space are recognised but serialport has no way to set them
+fn parse_parity(s: &str) -> Result<Parity, &'static str> {
+    Ok(match s.to_ascii_lowercase().as_str() {
+        "none" => Parity::None,
+        "odd" => Parity::Odd,
+        "even" => Parity::Even,
+        "mark" | "space" => return Err("parity mode not supported on this platform"),
+        _ => return Err("Parity option passed an invalid value"),
+    })
+}
+
+/// Parse a `--data-bits` value
+fn parse_data_bits(s: &str) -> Result<DataBits, &'static str> {
+    Ok(match s {
+        "5" => DataBits::Five,
+        "6" => DataBits::Six,
+        "7" => DataBits::Seven,
+        "8" => DataBits::Eight,
+        _ => return Err("Data bits option passed an invalid value"),
+    })
+}
+
+/// Parse a `--stop-bits` value
+fn parse_stop_bits(s: &str) -> Result<StopBits, &'static str> {
+    Ok(match s.to_ascii_lowercase().as_str() {
+        "1" | "one" => StopBits::One,
+        "2" | "two" => StopBits::Two,
+        _ => return Err("Stop Bits option passed an invalid value"),
+    })
+}
+
+/// Parse a number of seconds such as `2.5`, which can't be negative
+fn seconds(s: &str) -> Result<Duration, String> {
+    let secs: f64 = s
+        .parse()
+        .map_err(|_| format!("\"{}\" is not a number of seconds", s))?;
+    Duration::try_from_secs_f64(secs)
+        .map_err(|_| format!("{} seconds is not a time that can be waited for", s))
+}
+
+/// Parse a buffer or read size, which has to be at least one byte
+fn buffer_size(s: &str) -> Result<usize, String> {
+    match s.parse() {
+        Ok(0) => Err(String::from("it has to be at least 1 byte")),
+        Ok(size) => Ok(size),
+        Err(_) => Err(format!("\"{}\" is not a number of bytes", s)),
+    }
+}
+
+/// Report an argument whose value couldn't be parsed and exit
+fn invalid_argument(name: &str, e: pico_args::Error) -> ! {
+    match e {
+        pico_args::Error::ArgumentParsingFailed { cause } => {
+            eprintln!("Invalid {}: {}", name, cause)
         }
+        e => eprintln!("Invalid {}: {}", name, e),
+    }
+    exit(ExitCode::Error.status());
+}
+
+/// Whether to leave out the throughput lines, by default they are only shown when stdout is a terminal
+fn quiet_output(quiet: bool, stats: bool, stdout_tty: bool) -> bool {
+    quiet || (!stdout_tty && !stats)
+}
+
+/// The optional cargo features compiled into this binary
+fn features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if cfg!(feature = "tui") {
+        features.push("tui");
+    }
+    if cfg!(feature = "json") {
+        features.push("json");
+    }
+    if cfg!(feature = "regex") {
+        features.push("regex");
+    }
+    if cfg!(feature = "sqlite") {
+        features.push("sqlite");
+    }
+    if cfg!(feature = "mqtt") {
+        features.push("mqtt");
+    }
+    if cfg!(feature = "ws") {
+        features.push("ws");
+    }
+    if features.is_empty() {
+        features.push("none");
+    }
+    features
+}
+
+/// The version, git hash and target this binary was built from
+fn version() -> String {
+    let mut version = format!("oxterm {}", env!("CARGO_PKG_VERSION"));
+    let hash = env!("BUILD_GIT_HASH");
+    if !hash.is_empty() {
+        version.push_str(&format!(" ({})", hash));
     }
+    version.push_str(&format!("\ntarget: {}", env!("BUILD_TARGET")));
+    version.push_str(&format!("\nfeatures: {}", features().join(", ")));
+    version
 }
+
 fn main() {
-    println!("Hello, world!");
+    #[cfg(feature = "tui")]
+    terminal::restore_on_panic();
     let mut pargs = pico_args::Arguments::from_env();
 
     if pargs.contains(["-h", "--help"]) {
         print!("{}", HELP);
+        println!("\nBuilt with features: {}", features().join(", "));
+        std::process::exit(0);
+    }
+    if pargs.contains(["-V", "--version"]) {
+        println!("{}", version());
         std::process::exit(0);
     }
+    if pargs.contains("--list-profiles") {
+        list_profiles();
+        exit(0);
+    }
+    if pargs.contains("--list-ports") {
+        let ports = serialport::available_ports().unwrap_or_else(|e| {
+            eprintln!("Could not list the serial ports: {}", e);
+            exit(1);
+        });
+        for port in ports {
+            println!("{}", port.port_name);
+        }
+        exit(0);
+    }
+    if let Some(shell) = pargs
+        .opt_value_from_str::<_, completions::Shell>("--completions")
+        .unwrap_or_else(|e| invalid_argument("--completions", e))
+    {
+        print!("{}", completions::script(shell, &options::known(HELP)));
+        exit(0);
+    }
+    let load: Option<String> = pargs
+        .opt_value_from_str("--profile")
+        .unwrap_or_else(|e| invalid_argument("--profile", e));
+    let save: Option<String> = pargs
+        .opt_value_from_str("--save-profile")
+        .unwrap_or_else(|e| invalid_argument("--save-profile", e));
+    let env = env::args(std::env::vars_os(), HELP).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        exit(1);
+    });
+    if load.is_some() || save.is_some() || !env.is_empty() {
+        pargs = with_profile(pargs, load, save, &env);
+    }
 
     let dargs = Args::default();
 
-    let args = Args {
+    let replay = pargs
+        .opt_value_from_os_str::<_, _, &'static str>(["-r", "--replay"], |s| Ok(s.to_owned()))
+        .unwrap_or_else(|e| invalid_argument("--replay", e));
+
+    let usb_product: Option<String> = pargs
+        .opt_value_from_str("--usb-product")
+        .unwrap_or_else(|e| invalid_argument("--usb-product", e));
+    let usb_manufacturer: Option<String> = pargs
+        .opt_value_from_str("--usb-manufacturer")
+        .unwrap_or_else(|e| invalid_argument("--usb-manufacturer", e));
+
+    let ports = pargs
+        .opt_value_from_str::<_, String>("--ports")
+        .unwrap_or_else(|e| invalid_argument("--ports", e))
+        .map(|list| multi::parse(&list))
+        .unwrap_or_default();
+
+    let mut args = Args {
         port: pargs
             .opt_value_from_str(["-p", "--port"])
-            .unwrap()
+            .unwrap_or_else(|e| invalid_argument("--port", e))
+            .or_else(|| replay.as_ref().map(|_| String::new()))
+            .or_else(|| (!ports.is_empty()).then(String::new))
             .unwrap_or_else(|| {
-                let ports = serialport::available_ports().expect("No ports found!");
+                let ports = picker::available(serialport::available_ports()).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    exit(1);
+                });
+                if usb_product.is_some() || usb_manufacturer.is_some() {
+                    return usb_port(ports, usb_product.as_deref(), usb_manufacturer.as_deref());
+                }
                 if ports.len() == 1 {
                     let mut ports = ports;
-                    ports.remove(0).port_name
-                } else {
-                    // TODO: prettify the output of this section
-                    println!("Found ports:");
-                    for p in ports {
-                        println!("{:?}", p);
+                    return ports.remove(0).port_name;
+                }
+                #[cfg(feature = "tui")]
+                if stdout().is_terminal() {
+                    match picker::pick(&ports) {
+                        Ok(Some(port)) => return port,
+                        Ok(None) => exit(0),
+                        Err(e) => {
+                            eprintln!(
+                                "Could not show the port picker ({}), give the port with --port",
+                                e
+                            );
+                            exit(ExitCode::Error.status());
+                        }
                     }
-                    exit(0)
                 }
+                picker::list(&ports);
+                exit(0)
             }),
 
         baud_rate: pargs
             .opt_value_from_str(["-b", "--baud-rate"])
-            .unwrap()
+            .unwrap_or_else(|e| invalid_argument("--baud-rate", e))
             .unwrap_or(dargs.baud_rate),
 
         data_bits: pargs
-            .opt_value_from_fn(["-d", "--data-bits"], |x| {
-                Ok(match x {
-                    "5" => DataBits::Five,
-                    "6" => DataBits::Six,
-                    "7" => DataBits::Seven,
-                    "8" => DataBits::Eight,
-                    _ => return Err("Data bits option passed an invalid value"),
-                })
-            })
-            .unwrap()
+            .opt_value_from_fn(["-d", "--data-bits"], parse_data_bits)
+            .unwrap_or_else(|e| invalid_argument("--data-bits", e))
             .unwrap_or(dargs.data_bits),
 
         flow_control: pargs
@@ -177,71 +1100,520 @@ fn main() {
                     _ => return Err("Flow Control option passed an invalid value"),
                 })
             })
-            .unwrap()
+            .unwrap_or_else(|e| invalid_argument("--flow-control", e))
             .unwrap_or(dargs.flow_control),
 
         parity: pargs
-            .opt_value_from_fn(["-P", "--parity"], |n| {
-                Ok(match n.to_ascii_lowercase().as_str() {
-                    "none" => Parity::None,
-                    "odd" => Parity::Odd,
-                    "even" => Parity::Even,
-                    _ => return Err("Parity option passed an invalid value"),
-                })
-            })
-            .unwrap()
+            .opt_value_from_fn(["-P", "--parity"], parse_parity)
+            .unwrap_or_else(|e| invalid_argument("--parity", e))
             .unwrap_or(dargs.parity),
 
         stop_bits: pargs
-            .opt_value_from_fn(["-s", "--stop-bits"], |n| {
-                Ok(match n.to_ascii_lowercase().as_str() {
-                    "1" | "one" => StopBits::One,
-                    "2" | "two" => StopBits::Two,
-                    _ => return Err("Stop Bits option passed an invalid value"),
-                })
-            })
-            .unwrap()
+            .opt_value_from_fn(["-s", "--stop-bits"], parse_stop_bits)
+            .unwrap_or_else(|e| invalid_argument("--stop-bits", e))
             .unwrap_or(dargs.stop_bits),
 
         timeout: pargs
             .opt_value_from_str(["-T", "--timeout"])
-            .unwrap()
-            .map(|t| Duration::from_millis(t))
+            .unwrap_or_else(|e| invalid_argument("--timeout", e))
+            .map(Duration::from_millis)
             .unwrap_or(dargs.timeout),
 
         mode: pargs
-            .opt_value_from_str(["-w", "--mode"])
-            .unwrap()
+            .opt_value_from_str(["-m", "--mode"])
+            .unwrap_or_else(|e| invalid_argument("--mode", e))
             .unwrap_or(dargs.mode),
 
         capacity: pargs
-            .opt_value_from_str(["-C", "--capacity"])
-            .unwrap()
+            .opt_value_from_fn(["-C", "--capacity"], buffer_size)
+            .unwrap_or_else(|e| invalid_argument("--capacity", e))
             .unwrap_or(dargs.capacity),
 
+        max_read_size: pargs
+            .opt_value_from_fn("--max-read-size", buffer_size)
+            .unwrap_or_else(|e| invalid_argument("--max-read-size", e)),
+
         output_file: pargs
             .opt_value_from_os_str::<_, _, &'static str>(["-o", "--output-file"], |s| {
                 Ok(s.to_owned())
             })
-            .unwrap()
+            .unwrap_or_else(|e| invalid_argument("--output-file", e))
             .unwrap_or(dargs.output_file),
 
-        append: pargs
-            .opt_value_from_str(["-a", "--append"])
-            .unwrap()
-            .unwrap_or(dargs.append),
+        append: pargs.contains(["-a", "--append"]),
+
+        dated_output: pargs.contains("--append-timestamp-to-filename"),
+
+        format: pargs
+            .opt_value_from_os_str(["-f", "--format"], format::load)
+            .unwrap_or_else(|e| {
+                match e {
+                    pico_args::Error::ArgumentParsingFailed { cause } => eprintln!("{}", cause),
+                    e => eprintln!("Invalid --format: {}", e),
+                }
+                exit(1);
+            }),
+
+        raw_values: pargs.contains("--raw-values"),
+
+        no_read: pargs.contains("--no-read"),
+
+        describe_port: pargs.contains("--describe-port"),
+
+        loopback_test: pargs.contains("--loopback-test"),
+
+        buffering: pargs
+            .opt_value_from_str("--buffering")
+            .unwrap_or_else(|e| invalid_argument("--buffering", e)),
+        timeout_exit_code: pargs
+            .opt_value_from_str("--timeout-exit-code")
+            .unwrap_or_else(|e| invalid_argument("--timeout-exit-code", e))
+            .unwrap_or(dargs.timeout_exit_code),
+        newline_on_idle: pargs
+            .opt_value_from_str("--newline-on-idle")
+            .unwrap_or_else(|e| invalid_argument("--newline-on-idle", e))
+            .map(Duration::from_millis),
+        mark_partial: pargs.contains("--mark-partial"),
+        ports,
+        ws_listen: pargs
+            .opt_value_from_str("--ws-listen")
+            .unwrap_or_else(|e| invalid_argument("--ws-listen", e)),
+        ws_duplex: pargs.contains("--ws-duplex"),
+        open_delay: pargs
+            .opt_value_from_str("--open-delay")
+            .unwrap_or_else(|e| invalid_argument("--open-delay", e))
+            .map(Duration::from_millis)
+            .unwrap_or(dargs.open_delay),
+        send_ihex: pargs
+            .opt_value_from_os_str::<_, _, &'static str>("--send-ihex", |s| Ok(s.to_owned()))
+            .unwrap_or_else(|e| invalid_argument("--send-ihex", e)),
+        send_srec: pargs
+            .opt_value_from_os_str::<_, _, &'static str>("--send-srec", |s| Ok(s.to_owned()))
+            .unwrap_or_else(|e| invalid_argument("--send-srec", e)),
+        #[cfg(feature = "regex")]
+        ack_pattern: pargs
+            .opt_value_from_fn("--ack-pattern", regex::bytes::Regex::new)
+            .unwrap_or_else(|e| invalid_argument("--ack-pattern", e)),
+        read_retries: pargs
+            .opt_value_from_str("--read-retries")
+            .unwrap_or_else(|e| invalid_argument("--read-retries", e))
+            .unwrap_or(dargs.read_retries),
+        retry_backoff: pargs
+            .opt_value_from_str("--retry-backoff")
+            .unwrap_or_else(|e| invalid_argument("--retry-backoff", e))
+            .map(Duration::from_millis)
+            .unwrap_or(dargs.retry_backoff),
+        emit_on_change: pargs.contains("--emit-on-change"),
+        min_interval: pargs
+            .opt_value_from_str("--min-interval")
+            .unwrap_or_else(|e| invalid_argument("--min-interval", e))
+            .map(Duration::from_millis),
+        record_size: pargs
+            .opt_value_from_str("--struct")
+            .unwrap_or_else(|e| invalid_argument("--struct", e)),
+        #[cfg(feature = "tui")]
+        paste_safe: pargs.contains("--paste-safe"),
+
+        monitor_signals: pargs
+            .opt_value_from_str("--monitor-signals")
+            .unwrap_or_else(|e| invalid_argument("--monitor-signals", e))
+            .map(Duration::from_millis),
 
         convertfrom: pargs
             .opt_value_from_str(["-c", "--convert"])
-            .unwrap()
+            .unwrap_or_else(|e| invalid_argument("--convert", e))
             .unwrap_or(dargs.convertfrom),
+
+        replay,
+
+        replay_rate: pargs
+            .opt_value_from_str("--replay-rate")
+            .unwrap_or_else(|e| invalid_argument("--replay-rate", e)),
+
+        replay_realtime: pargs.contains("--replay-realtime"),
+        replay_seek_bytes: pargs
+            .opt_value_from_str("--replay-seek-bytes")
+            .unwrap_or_else(|e| invalid_argument("--replay-seek-bytes", e)),
+        replay_seek_time: pargs
+            .opt_value_from_str("--replay-seek-time")
+            .unwrap_or_else(|e| invalid_argument("--replay-seek-time", e))
+            .map(Duration::from_millis),
+        replay_timing: pargs
+            .opt_value_from_os_str::<_, _, &'static str>("--replay-timing", |s| Ok(s.to_owned()))
+            .unwrap_or_else(|e| invalid_argument("--replay-timing", e)),
+
+        timing: pargs
+            .opt_value_from_os_str::<_, _, &'static str>(["-t", "--timing"], |s| Ok(s.to_owned()))
+            .unwrap_or_else(|e| invalid_argument("--timing", e)),
+
+        timing_lines: pargs.contains("--timing-lines"),
+
+        csv: pargs.contains("--csv"),
+
+        csv_header: pargs
+            .opt_value_from_fn("--csv-header", |s| {
+                Ok::<_, &'static str>(s.split(',').map(str::to_owned).collect())
+            })
+            .unwrap_or_else(|e| invalid_argument("--csv-header", e))
+            .unwrap_or(dargs.csv_header),
+
+        group: pargs
+            .opt_value_from_str("--group")
+            .unwrap_or_else(|e| invalid_argument("--group", e)),
+
+        timestamp: pargs.contains("--timestamp"),
+
+        jsonl: pargs.contains("--jsonl"),
+
+        columns: pargs.contains("--columns"),
+
+        label: pargs
+            .opt_value_from_str("--label")
+            .unwrap_or_else(|e| invalid_argument("--label", e)),
+
+        wait_for_port: pargs.contains("--wait-for-port"),
+
+        detect_baud: pargs.contains("--detect-baud"),
+
+        plot_index: pargs.contains("--plot-index"),
+
+        plot_time: pargs.contains("--plot-time"),
+
+        detect_and_run: pargs.contains("--detect-and-run"),
+
+        watchdog: pargs
+            .opt_value_from_str("--watchdog")
+            .unwrap_or_else(|e| invalid_argument("--watchdog", e))
+            .map(Duration::from_millis),
+
+        reset_line: pargs
+            .opt_value_from_str("--reset-line")
+            .unwrap_or_else(|e| invalid_argument("--reset-line", e))
+            .unwrap_or(dargs.reset_line),
+
+        send_script: pargs
+            .opt_value_from_os_str::<_, _, &'static str>("--send-script", |s| Ok(s.to_owned()))
+            .unwrap_or_else(|e| invalid_argument("--send-script", e)),
+
+        expect_script: pargs
+            .opt_value_from_os_str::<_, _, &'static str>("--expect-script", |s| Ok(s.to_owned()))
+            .unwrap_or_else(|e| invalid_argument("--expect-script", e)),
+
+        line_delay: pargs
+            .opt_value_from_str("--line-delay")
+            .unwrap_or_else(|e| invalid_argument("--line-delay", e))
+            .map(Duration::from_millis)
+            .unwrap_or(dargs.line_delay),
+
+        output_eol: pargs
+            .opt_value_from_str("--output-eol")
+            .unwrap_or_else(|e| invalid_argument("--output-eol", e))
+            .unwrap_or(dargs.output_eol),
+
+        mqtt_broker: pargs
+            .opt_value_from_str("--mqtt-broker")
+            .unwrap_or_else(|e| invalid_argument("--mqtt-broker", e))
+            .unwrap_or(dargs.mqtt_broker),
+
+        mqtt_topic: pargs
+            .opt_value_from_str("--mqtt-topic")
+            .unwrap_or_else(|e| invalid_argument("--mqtt-topic", e))
+            .unwrap_or(dargs.mqtt_topic),
+
+        mqtt_qos: pargs
+            .opt_value_from_fn("--mqtt-qos", |s| match s {
+                "0" | "1" | "2" => Ok(s.parse().unwrap()),
+                _ => Err("MQTT QoS should be 0, 1 or 2"),
+            })
+            .unwrap_or_else(|e| invalid_argument("--mqtt-qos", e))
+            .unwrap_or(dargs.mqtt_qos),
+
+        rotate_on_signal: pargs.contains("--rotate-on-signal"),
+
+        frames: match pargs
+            .opt_value_from_str("--framing")
+            .unwrap_or_else(|e| invalid_argument("--framing", e))
+        {
+            Some(framing) => Some(framing),
+            None => pargs.contains("--cobs").then_some(framing::Framing::Cobs),
+        },
+
+        len_size: pargs
+            .opt_value_from_fn("--len-size", |s| match s {
+                "1" | "2" | "4" => Ok(s.parse().unwrap()),
+                _ => Err("Length size should be 1, 2 or 4"),
+            })
+            .unwrap_or_else(|e| invalid_argument("--len-size", e))
+            .unwrap_or(dargs.len_size),
+
+        len_endian: pargs
+            .opt_value_from_str("--len-endian")
+            .unwrap_or_else(|e| invalid_argument("--len-endian", e))
+            .unwrap_or(dargs.len_endian),
+
+        max_frame: pargs
+            .opt_value_from_str("--max-frame")
+            .unwrap_or_else(|e| invalid_argument("--max-frame", e))
+            .unwrap_or(dargs.max_frame),
+
+        crc: pargs
+            .opt_value_from_str("--crc")
+            .unwrap_or_else(|e| invalid_argument("--crc", e)),
+
+        base64: pargs.contains("--base64"),
+
+        alert_below: pargs
+            .opt_value_from_str("--alert-below")
+            .unwrap_or_else(|e| invalid_argument("--alert-below", e)),
+
+        alert_above: pargs
+            .opt_value_from_str("--alert-above")
+            .unwrap_or_else(|e| invalid_argument("--alert-above", e)),
+
+        alert_bell: pargs.contains("--alert-bell"),
+
+        buffer_stats: pargs.contains("--buffer-stats"),
+
+        detect_overrun: pargs.contains("--detect-overrun"),
+
+        summary_json: pargs
+            .opt_value_from_os_str::<_, _, &'static str>("--summary-json", |s| Ok(s.to_owned()))
+            .unwrap_or_else(|e| invalid_argument("--summary-json", e)),
+
+        record_delim: pargs
+            .opt_value_from_fn("--record-delim", records::parse_delim)
+            .unwrap_or_else(|e| invalid_argument("--record-delim", e))
+            .filter(|delim| delim != b"\n"),
+
+        #[cfg(feature = "regex")]
+        start_trigger: pargs
+            .opt_value_from_fn("--start-trigger", regex::bytes::Regex::new)
+            .unwrap_or_else(|e| invalid_argument("--start-trigger", e)),
+
+        #[cfg(feature = "regex")]
+        stop_trigger: pargs
+            .opt_value_from_fn("--stop-trigger", regex::bytes::Regex::new)
+            .unwrap_or_else(|e| invalid_argument("--stop-trigger", e)),
+
+        #[cfg(feature = "regex")]
+        repeat_trigger: pargs.contains("--repeat-trigger"),
+
+        #[cfg(feature = "regex")]
+        pretrigger: pargs
+            .opt_value_from_str("--pretrigger")
+            .unwrap_or_else(|e| invalid_argument("--pretrigger", e))
+            .unwrap_or(dargs.pretrigger),
+
+        decimate: pargs
+            .opt_value_from_str("--decimate")
+            .unwrap_or_else(|e| invalid_argument("--decimate", e))
+            .unwrap_or(dargs.decimate),
+
+        decimate_lines: pargs
+            .opt_value_from_str("--decimate-lines")
+            .unwrap_or_else(|e| invalid_argument("--decimate-lines", e))
+            .unwrap_or(dargs.decimate_lines),
+
+        hex_words: pargs
+            .opt_value_from_fn("--hex-words", |s| match s {
+                "2" => Ok(2),
+                "4" => Ok(4),
+                _ => Err("Hex words option should be 2 or 4"),
+            })
+            .unwrap_or_else(|e| invalid_argument("--hex-words", e)),
+
+        endian: pargs
+            .opt_value_from_str("--endian")
+            .unwrap_or_else(|e| invalid_argument("--endian", e))
+            .unwrap_or(dargs.endian),
+
+        wait_timeout: pargs
+            .opt_value_from_fn("--wait-timeout", seconds)
+            .unwrap_or_else(|e| invalid_argument("--wait-timeout", e))
+            .unwrap_or(dargs.wait_timeout),
+
+        delimiter: pargs
+            .opt_value_from_fn("--delimiter", single_char)
+            .unwrap_or_else(|e| invalid_argument("--delimiter", e))
+            .unwrap_or(dargs.delimiter),
+
+        timestamp_format: pargs
+            .opt_value_from_str("--timestamp-format")
+            .unwrap_or_else(|e| invalid_argument("--timestamp-format", e))
+            .unwrap_or(dargs.timestamp_format),
+
+        #[cfg(feature = "tui")]
+        macros: pargs
+            .values_from_fn("--macro", interactive::parse_macro)
+            .unwrap_or_else(|e| invalid_argument("--macro", e))
+            .into_iter()
+            .collect(),
+
+        #[cfg(feature = "tui")]
+        hex_input: pargs.contains("--hex-input"),
+
+        #[cfg(feature = "tui")]
+        log: pargs
+            .opt_value_from_os_str::<_, _, &'static str>("--log", |s| Ok(s.to_owned()))
+            .unwrap_or_else(|e| invalid_argument("--log", e)),
+
+        #[cfg(feature = "tui")]
+        history_file: pargs
+            .opt_value_from_os_str::<_, _, &'static str>("--history-file", |s| Ok(s.to_owned()))
+            .unwrap_or_else(|e| invalid_argument("--history-file", e)),
+
+        #[cfg(feature = "tui")]
+        complete_output: pargs.contains("--complete-output"),
+
+        #[cfg(feature = "tui")]
+        local_echo: match (
+            pargs.contains("--local-echo"),
+            pargs.contains("--no-local-echo"),
+        ) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        },
+
+        #[cfg(feature = "tui")]
+        copy_lines: pargs
+            .opt_value_from_str("--copy-lines")
+            .unwrap_or_else(|e| invalid_argument("--copy-lines", e)),
+        #[cfg(feature = "tui")]
+        snapshot_context: pargs
+            .opt_value_from_str("--snapshot-context")
+            .unwrap_or_else(|e| invalid_argument("--snapshot-context", e))
+            .unwrap_or(dargs.snapshot_context),
+        #[cfg(feature = "tui")]
+        sparkline: pargs
+            .opt_value_from_str("--sparkline")
+            .unwrap_or_else(|e| invalid_argument("--sparkline", e))
+            .unwrap_or(dargs.sparkline),
+
+        map: {
+            let entries: Vec<_> = pargs
+                .values_from_fn("--map", bytemap::parse_map)
+                .unwrap_or_else(|e| invalid_argument("--map", e))
+                .into_iter()
+                .flatten()
+                .collect();
+            (!entries.is_empty()).then(|| bytemap::ByteMap::new(&entries))
+        },
+        strip_parity: pargs.contains("--strip-parity"),
+        tokens: stats::Tokens {
+            word: pargs
+                .opt_value_from_fn("--word-delim", single_char)
+                .unwrap_or_else(|e| invalid_argument("--word-delim", e))
+                .unwrap_or(dargs.tokens.word),
+            line: pargs
+                .opt_value_from_fn("--line-delim", single_char)
+                .unwrap_or_else(|e| invalid_argument("--line-delim", e))
+                .unwrap_or(dargs.tokens.line),
+            counted: pargs
+                .values_from_fn("--count-byte", escape::parse_byte)
+                .unwrap_or_else(|e| invalid_argument("--count-byte", e)),
+        },
+        bell: pargs
+            .opt_value_from_str("--bell")
+            .unwrap_or_else(|e| invalid_argument("--bell", e))
+            .unwrap_or(dargs.bell),
+
+        scrollback: pargs
+            .opt_value_from_str("--scrollback")
+            .unwrap_or_else(|e| invalid_argument("--scrollback", e))
+            .unwrap_or(dargs.scrollback),
+
+        quiet: pargs.contains(["-q", "--quiet"]),
+
+        stats: pargs.contains("--stats"),
+
+        color: pargs.contains("--force-color"),
+
+        max_bytes: pargs
+            .opt_value_from_str("--max-bytes")
+            .unwrap_or_else(|e| invalid_argument("--max-bytes", e))
+            .unwrap_or(dargs.max_bytes),
+
+        max_lines: pargs
+            .opt_value_from_str("--max-lines")
+            .unwrap_or_else(|e| invalid_argument("--max-lines", e))
+            .unwrap_or(dargs.max_lines),
+
+        duration: pargs
+            .opt_value_from_fn("--duration", seconds)
+            .unwrap_or_else(|e| invalid_argument("--duration", e))
+            .unwrap_or(dargs.duration),
+
+        idle_timeout: pargs
+            .opt_value_from_str("--idle-timeout")
+            .unwrap_or_else(|e| invalid_argument("--idle-timeout", e))
+            .map(Duration::from_millis)
+            .unwrap_or(dargs.idle_timeout),
+
+        send_init: pargs
+            .opt_value_from_fn("--send-init", escape::unescape)
+            .unwrap_or_else(|e| invalid_argument("--send-init", e))
+            .unwrap_or(dargs.send_init),
+
+        once: pargs.contains("--once"),
+
+        xon: pargs
+            .opt_value_from_fn("--xon", escape::parse_byte)
+            .unwrap_or_else(|e| invalid_argument("--xon", e)),
+
+        xoff: pargs
+            .opt_value_from_fn("--xoff", escape::parse_byte)
+            .unwrap_or_else(|e| invalid_argument("--xoff", e)),
+
+        heartbeat: pargs
+            .opt_value_from_str("--heartbeat")
+            .unwrap_or_else(|e| invalid_argument("--heartbeat", e))
+            .map(Duration::from_millis),
+
+        dry_run: pargs.contains("--dry-run"),
+
+        shared: pargs.contains("--shared") | pargs.contains("--no-exclusive"),
+
+        histogram: pargs.contains("--histogram"),
     };
 
-    if pargs.contains(["-o", "--output-file"]) {
-        todo!("File argument not supported yet")
+    if let Some(size) = args.record_size {
+        let checked = match &args.format {
+            Some(format) => format.check_size(size),
+            None => Err(String::from(
+                "--struct gives the size of the --format records, give that too",
+            )),
+        };
+        if let Err(e) = checked {
+            eprintln!("Invalid --format: {}", e);
+            exit(1);
+        }
     }
-    if pargs.contains(["-f", "--format"]) {
-        todo!("Parse format argument not supported yet")
+    if args.ws_listen.is_some() && args.mode.eq_ignore_ascii_case("stdout") {
+        args.mode = String::from("ws");
+    }
+    let stdout_tty = stdout().is_terminal();
+    args.quiet = quiet_output(args.quiet, args.stats, stdout_tty);
+    args.color |= stdout_tty;
+    let mode = args.mode.to_lowercase();
+    let to_stdout = !matches!(mode.as_str(), "file" | "sqlite" | "mqtt" | "ws");
+    // Browsers are watching live, so they get every line as it arrives
+    args.buffering = args.buffering.or(Some(buffering::Buffering::default_for(
+        (stdout_tty && to_stdout) || mode == "ws",
+    )));
+
+    if args.once {
+        if args.max_lines == 0 {
+            args.max_lines = 1;
+        }
+        if args.idle_timeout == Duration::ZERO {
+            args.idle_timeout = Duration::from_millis(1000);
+        }
+    }
+
+    if pargs.contains(["-o", "--output-file"]) {
+        eprintln!("Give --output-file only once, there is one output file");
+        exit(ExitCode::Error.status());
     }
 
     let remaining = pargs.finish();
@@ -249,176 +1621,2247 @@ fn main() {
         eprintln!("Warning: unused arguments left: {:?}.", remaining);
     }
 
-    let port = serialport::new(args.port.clone(), args.baud_rate)
-        .data_bits(args.data_bits)
-        .flow_control(args.flow_control)
-        .parity(args.parity)
-        .stop_bits(args.stop_bits)
-        .timeout(args.timeout)
-        .open()
-        .expect("Could not open the serial port");
+    if let Err(warnings) = validate::validate_framing(&args) {
+        for warning in &warnings {
+            eprintln!("{}", warning);
+        }
+        if warnings
+            .iter()
+            .any(|w| matches!(w, validate::Warning::Invalid(_)))
+        {
+            exit(1);
+        }
+    }
 
-    match args.mode.to_lowercase().as_str() {
-        "stdout" => serial_to_writer(port, stdout().lock(), false, &args),
-        "iterm" => serial_iterm(port, &args),
-        "lines" => serial_line_interactive(port, &args),
-        "file" => serial_to_writer(
-            port,
-            File::with_options()
-                .write(true)
-                .append(true)
-                .create(true)
-                .open(args.output_file.as_os_str())
-                .unwrap(),
-            // File::open(args.output_file.as_os_str()).unwrap(),
-            true,
-            &args,
-        ),
-        _ => {
-            println!("Invalid serial mode passed");
+    if args.dated_output {
+        args.output_file = dated_output_file(&args.output_file);
+    }
+
+    if cfg!(not(feature = "json")) && args.summary_json.is_some() {
+        eprintln!("--summary-json needs oxterm built with the json feature");
+        exit(1);
+    }
+
+    if args.dry_run {
+        println!("{}", args.settings());
+        for line in args.resolved() {
+            println!("{}", line);
         }
+        return;
     }
-}
 
-fn serial_line_interactive(port: Box<dyn SerialPort>, args: &Args) {
-    todo!("Interactive mode not implemented yet")
-}
+    // With the termination feature this also catches SIGTERM
+    ctrlc::set_handler(shutdown).expect("Could not set the Ctrl-C handler");
+    if args.rotate_on_signal {
+        if !args.mode.eq_ignore_ascii_case("file") {
+            eprintln!("Warning: --rotate-on-signal only applies to file mode");
+        }
+        rotate::reopen_on_sighup();
+    }
 
-fn serial_iterm(port: Box<dyn SerialPort>, args: &Args) {
-    todo!("Terminal mode not implemented yet")
-}
+    if let Some(path) = &args.replay {
+        replay_file(path, &args);
+        return;
+    }
 
-/// Read only streaming from the serial port
-/// Writes the data to the Write object (buffered for performance)
-fn serial_to_writer(mut port: Box<dyn SerialPort>, out: impl Write, counts: bool, args: &Args) {
-    let mut out = BufWriter::with_capacity(args.capacity, out);
-    let mut buf = Vec::with_capacity(args.capacity);
-    let buf = &mut buf;
-
-    let mut stamp = Instant::now();
-
-    let mut count_words = 0;
-    let mut count_commas = 0;
-    let mut count_bytes = 0;
-    let mut count_lines = 0;
-
-    let mut copy = move || -> Result<_, _> {
-        match port.read(buf) {
-            Ok(n) => {
-                count_bytes += n;
-                match args.convertfrom {
-                    ConvertFrom::NON => {
-                        let (words, commas, lines) = buf.iter().fold(
-                            (count_words, count_commas, count_lines),
-                            |(w, c, l), &b| match b {
-                                b' ' => (w + 1, c, l),
-                                b'\n' => (w, c, l + 1),
-                                b',' => (w, c + 1, l),
-                                _ => (w, c, l),
-                            },
-                        );
-                        count_words = words;
-                        count_commas = commas;
-                        count_lines = lines;
-                    }
-                    ConvertFrom::HEX => {
-                        let mut out = Vec::with_capacity(buf.len() * 2);
-                        for byte in &*buf {
-                            write!(out, "{:x}", byte)?;
-                        }
-                        *buf = out;
-                    }
-                    ConvertFrom::BIN => {
-                        let mut out = Vec::with_capacity(buf.len() * 8);
-                        for byte in &*buf {
-                            write!(out, "{:b}", byte)?;
-                        }
-                        *buf = out;
-                    }
-                    ConvertFrom::INT => {
-                        let mut out = Vec::with_capacity(buf.len() * 4);
-                        for &bytes in buf.array_chunks() {
-                            write!(out, "{}", i32::from_le_bytes(bytes))?;
-                        }
-                        *buf = out;
-                    }
-                    ConvertFrom::SHR => {
-                        let mut out = Vec::with_capacity(buf.len() * 2);
-                        for &bytes in buf.array_chunks() {
-                            write!(out, "{}", i16::from_le_bytes(bytes))?;
-                        }
-                        *buf = out;
-                    }
-                    ConvertFrom::FLT => {
-                        let mut out = Vec::with_capacity(buf.len() * 4);
-                        for &bytes in buf.array_chunks() {
-                            // TODO: floating point decimal points??
-                            write!(out, "{}", f32::from_le_bytes(bytes))?;
-                        }
-                        *buf = out;
+    if !args.ports.is_empty() {
+        multi_port(&args);
+        return;
+    }
+
+    if args.no_read && (args.expect_script.is_some() || args.detect_baud) {
+        eprintln!(
+            "--expect-script and --detect-baud need to read, they can't be used with --no-read"
+        );
+        exit(1);
+    }
+
+    let script = args.send_script.as_ref().map(|path| {
+        let text = std::fs::read(path).unwrap_or_else(|e| {
+            eprintln!("Could not read {}: {}", path.to_string_lossy(), e);
+            exit(1);
+        });
+        script::parse_script(&text, args.output_eol)
+    });
+
+    let firmware = match (&args.send_ihex, &args.send_srec) {
+        (Some(_), Some(_)) => {
+            eprintln!("--send-ihex and --send-srec can't be used together");
+            exit(1);
+        }
+        (Some(path), None) => Some((path, hexfile::Kind::IntelHex)),
+        (None, Some(path)) => Some((path, hexfile::Kind::Srec)),
+        (None, None) => None,
+    }
+    .map(|(path, kind)| {
+        let text = std::fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("Could not read {}: {}", path.to_string_lossy(), e);
+            exit(1);
+        });
+        hexfile::parse(&text, kind).unwrap_or_else(|e| {
+            eprintln!("Invalid firmware file {}: {}", path.to_string_lossy(), e);
+            exit(1);
+        })
+    });
+    #[cfg(feature = "regex")]
+    if args.ack_pattern.is_some() && firmware.is_none() {
+        eprintln!("--ack-pattern needs --send-ihex or --send-srec");
+        exit(1);
+    }
+
+    #[cfg(feature = "regex")]
+    let expect_steps = args.expect_script.as_ref().map(|path| {
+        let text = std::fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("Could not read {}: {}", path.to_string_lossy(), e);
+            exit(1);
+        });
+        expect::parse(&text, args.output_eol).unwrap_or_else(|e| {
+            eprintln!("Invalid expect script {}: {}", path.to_string_lossy(), e);
+            exit(1);
+        })
+    });
+    #[cfg(not(feature = "regex"))]
+    if args.expect_script.is_some() {
+        eprintln!("--expect-script needs oxterm built with the regex feature");
+        exit(1);
+    }
+
+    // The OS only knows the standard flow characters, so custom ones are handled by SoftFlow
+    let custom_flow = args.xon.is_some() || args.xoff.is_some();
+    if custom_flow && args.flow_control != FlowControl::Software {
+        eprintln!("Warning: --xon/--xoff only apply with software flow control (-F sw)");
+    }
+    let soft_flow = custom_flow && args.flow_control == FlowControl::Software;
+
+    // The OS would act on the standard flow characters, so leave it out of SoftFlow's way
+    let flow_control = if soft_flow {
+        FlowControl::None
+    } else {
+        args.flow_control
+    };
+    let opened = if args.wait_for_port {
+        let mut waiting = false;
+        retry_open(
+            || open_port(&args, &args.port, flow_control),
+            Duration::from_millis(250),
+            args.wait_timeout,
+            || {
+                if !std::mem::replace(&mut waiting, true) {
+                    eprintln!("waiting for {}…", args.port);
+                }
+            },
+        )
+    } else {
+        open_port(&args, &args.port, flow_control)
+    };
+    let (mut port, errors) = opened.unwrap_or_else(|e| {
+        let holders = if is_busy(&e) {
+            port_holders(&args.port)
+        } else {
+            Vec::new()
+        };
+        eprintln!("{}", open_error(&args, &e, &holders));
+        exit(ExitCode::OpenFailed.status());
+    });
+    std::thread::sleep(args.open_delay);
+    if args.describe_port {
+        let ports = serialport::available_ports().unwrap_or_default();
+        let info = ports.iter().find(|p| p.port_name == args.port);
+        for line in describe::describe(port.as_mut(), info) {
+            println!("{}", line);
+        }
+        return;
+    }
+    if args.loopback_test {
+        let mask = match args.data_bits {
+            DataBits::Five => 0x1f,
+            DataBits::Six => 0x3f,
+            DataBits::Seven => 0x7f,
+            DataBits::Eight => 0xff,
+        };
+        // Room for a slow adapter on top of the time the bytes take on the wire
+        let timeout = Duration::from_millis(1000);
+        match loopback::run(port.as_mut(), mask, timeout) {
+            Ok(report) => {
+                println!("{}", report);
+                if !report.comparison.passed() {
+                    exit(ExitCode::Error.status());
+                }
+            }
+            Err(e) => {
+                eprintln!("Loopback test failed: {}", e);
+                exit(ExitCode::Error.status());
+            }
+        }
+        return;
+    }
+    if args.detect_baud || args.detect_and_run {
+        eprintln!("Listening at {} baud rates…", baud::COMMON_RATES.len());
+        let candidates = match baud::detect(port.as_mut(), &baud::COMMON_RATES) {
+            Ok(candidates) => candidates,
+            Err(e) => {
+                eprintln!("Could not detect the baud rate: {}", e);
+                exit(ExitCode::Error.status());
+            }
+        };
+        for c in &candidates {
+            eprintln!(
+                "{:>7} baud: {:>5} bytes, {:>3.0}% printable",
+                c.baud_rate,
+                c.bytes,
+                c.score * 100.0
+            );
+        }
+        let best = match candidates.first().filter(|c| c.bytes > 0) {
+            Some(best) => best.baud_rate,
+            None => {
+                eprintln!("Nothing was received at any baud rate");
+                exit(ExitCode::Error.status());
+            }
+        };
+        eprintln!("Most likely {} baud", best);
+        if !args.detect_and_run {
+            return;
+        }
+        args.baud_rate = best;
+        if let Err(e) = port.set_baud_rate(best) {
+            eprintln!("Could not set the detected baud rate: {}", e);
+            exit(ExitCode::Error.status());
+        }
+    }
+    if soft_flow {
+        let (xon, xoff) = (args.xon.unwrap_or(0x11), args.xoff.unwrap_or(0x13));
+        port = Box::new(flow::SoftFlow::new(port, xon, xoff));
+    }
+
+    if !args.send_init.is_empty() {
+        if let Err(e) = port.write_all(&args.send_init) {
+            eprintln!("Could not send the init string: {}", e);
+            exit(ExitCode::WriteFailed.status());
+        }
+    }
+
+    if let Some(records) = firmware {
+        send_firmware(&mut port, &records, &args);
+        return;
+    }
+
+    if args.no_read {
+        if let Err(e) = transmit_only(&mut port, script.as_deref(), args.line_delay) {
+            eprintln!("{}", e);
+            exit(ExitCode::WriteFailed.status());
+        }
+        return;
+    }
+
+    if let Some(lines) = script {
+        // Sent from its own thread so the responses are read and shown as they arrive
+        let sender = port.try_clone().expect("Could not clone the serial port");
+        let delay = args.line_delay;
+        std::thread::spawn(move || {
+            if let Err(e) = script::send(sender, &lines, delay) {
+                eprintln!("Could not send the script: {}", e);
+            }
+        });
+    }
+
+    #[cfg(feature = "regex")]
+    if let Some(steps) = expect_steps {
+        let result = expect::run(&mut port, &steps, &mut stdout().lock());
+        if let Err(e) = result {
+            eprintln!("\nExpect script failed: {}", e);
+            exit(1);
+        }
+        return;
+    }
+
+    let control = || Control {
+        port: read_error::CurrentPort::new(
+            port.try_clone().expect("Could not clone the serial port"),
+        ),
+        errors,
+    };
+    match args.mode.to_lowercase().as_str() {
+        "stdout" | "csv" | "jsonl" | "plot" => {
+            let control = control();
+            let src = reconnecting(port, &args, flow_control, soft_flow, &control);
+            stream_to_writer(src, stdout().lock(), Some(control), &args)
+        }
+        #[cfg(feature = "tui")]
+        "iterm" => serial_iterm(port, &args),
+        #[cfg(feature = "tui")]
+        "term" => serial_term(port, &args),
+        #[cfg(feature = "tui")]
+        "lines" => serial_line_interactive(port, &args),
+        #[cfg(feature = "tui")]
+        "dashboard" => serial_dashboard(port, &args),
+        #[cfg(not(feature = "tui"))]
+        "iterm" | "lines" | "term" | "dashboard" => {
+            eprintln!("The interactive modes need oxterm built with the tui feature");
+            exit(1);
+        }
+        "file" => {
+            let control = control();
+            let src = reconnecting(port, &args, flow_control, soft_flow, &control);
+            stream_to_writer(src, open_output_file(&args), Some(control), &args)
+        }
+        "sqlite" => {
+            let control = control();
+            let src = reconnecting(port, &args, flow_control, soft_flow, &control);
+            stream_to_writer(src, io::sink(), Some(control), &args)
+        }
+        "mqtt" => {
+            let control = control();
+            let src = reconnecting(port, &args, flow_control, soft_flow, &control);
+            stream_to_writer(src, mqtt_publisher(&args), Some(control), &args)
+        }
+        "ws" => {
+            let control = control();
+            let duplex = args.ws_duplex.then(|| {
+                let port: Box<dyn Write + Send> = Box::new(control.port.clone());
+                port
+            });
+            let src = reconnecting(port, &args, flow_control, soft_flow, &control);
+            stream_to_writer(src, ws_broadcaster(&args, duplex), Some(control), &args)
+        }
+        _ => {
+            eprintln!("Invalid serial mode passed");
+        }
+    }
+}
+
+/// Send the records of a --send-ihex or --send-srec file, exiting if the port or device fails
+fn send_firmware(port: &mut Box<dyn SerialPort>, records: &[String], args: &Args) {
+    #[cfg(feature = "regex")]
+    let ack = |port: &mut Box<dyn SerialPort>| match &args.ack_pattern {
+        Some(pattern) => hexfile::wait_for_ack(port, pattern, hexfile::ACK_TIMEOUT),
+        None => Ok(()),
+    };
+    #[cfg(not(feature = "regex"))]
+    let ack = |_: &mut Box<dyn SerialPort>| Ok(());
+    let stderr_tty = io::stderr().is_terminal();
+    let progress = |sent| {
+        if stderr_tty {
+            eprint!("\rSent {}/{} records", sent, records.len());
+        }
+    };
+    let result = hexfile::send(port, records, args.output_eol.bytes(), ack, progress);
+    if stderr_tty {
+        eprintln!();
+    }
+    match result {
+        Ok(()) => eprintln!("Sent all {} records", records.len()),
+        Err(e) => {
+            eprintln!("Could not send the firmware: {}", e);
+            exit(ExitCode::WriteFailed.status());
+        }
+    }
+    // Wait for the OS to get it all out before the port is closed
+    if let Err(e) = port.flush() {
+        eprintln!("Could not send the data: {}", e);
+        exit(ExitCode::WriteFailed.status());
+    }
+}
+
+/// The port settings from `args`, with the flow control decided by the caller
+fn port_builder(
+    args: &Args,
+    port: &str,
+    flow_control: FlowControl,
+) -> serialport::SerialPortBuilder {
+    serialport::new(port, args.baud_rate)
+        .data_bits(args.data_bits)
+        .flow_control(flow_control)
+        .parity(args.parity)
+        .stop_bits(args.stop_bits)
+        .timeout(args.timeout)
+}
+
+/// Open `port` with the settings of `args`, claiming it for ourselves on unix unless `--shared` was given
+/// Also gives the port's error counters if it has any
+fn open_port(
+    args: &Args,
+    port: &str,
+    flow_control: FlowControl,
+) -> serialport::Result<(Box<dyn SerialPort>, Option<line_errors::Counter>)> {
+    let builder = port_builder(args, port, flow_control);
+    #[cfg(unix)]
+    {
+        let mut port = builder.open_native()?;
+        port.set_exclusive(!args.shared)?;
+        #[cfg(target_os = "linux")]
+        let errors = line_errors::Counter::new(std::os::unix::io::AsRawFd::as_raw_fd(&port));
+        #[cfg(not(target_os = "linux"))]
+        let errors = None;
+        Ok((Box::new(port), errors))
+    }
+    #[cfg(not(unix))]
+    {
+        if args.shared {
+            eprintln!("Warning: --shared is not supported on this platform");
+        }
+        Ok((builder.open()?, None))
+    }
+}
+
+/// Read every port of --ports at once, merging their labeled lines into stdout
+fn multi_port(args: &Args) {
+    if !args.mode.eq_ignore_ascii_case("stdout") || args.convertfrom != ConvertFrom::NON {
+        eprintln!("--ports writes the text lines of every port to stdout, it can't be used with other modes or --convert");
+        exit(1);
+    }
+    let ports = args
+        .ports
+        .iter()
+        .map(
+            |(label, port)| match open_port(args, port, args.flow_control) {
+                Ok((port, _)) => (label.clone(), port),
+                Err(e) => {
+                    eprintln!("Could not open {}: {}", port, e);
+                    exit(ExitCode::OpenFailed.status());
+                }
+            },
+        )
+        .collect();
+    std::thread::sleep(args.open_delay);
+    let ended = multi::run(ports, stdout(), args.color, args.read_size());
+    for port in &ended {
+        eprintln!("{}: received {} bytes", port.name, port.bytes);
+    }
+    if ended.iter().any(|port| port.error.is_some()) {
+        exit(ExitCode::ReadFailed.status());
+    }
+}
+
+/// The port to stream from, which with --wait-for-port is opened again when it disappears
+/// The control lines move to the reopened port, the error counters keep using the port as it was first opened
+fn reconnecting<'a>(
+    port: Box<dyn SerialPort>,
+    args: &'a Args,
+    flow_control: FlowControl,
+    soft_flow: bool,
+    control: &Control,
+) -> Box<dyn Read + 'a> {
+    if !args.wait_for_port {
+        return Box::new(port);
+    }
+    let reopen = move || {
+        let (mut port, _) = retry_open(
+            || open_port(args, &args.port, flow_control),
+            Duration::from_millis(250),
+            args.wait_timeout,
+            || {},
+        )?;
+        if soft_flow {
+            let (xon, xoff) = (args.xon.unwrap_or(0x11), args.xoff.unwrap_or(0x13));
+            port = Box::new(flow::SoftFlow::new(port, xon, xoff));
+        }
+        std::thread::sleep(args.open_delay);
+        Ok(port)
+    };
+    Box::new(read_error::Reconnecting::new(port, &args.port, reopen).current(control.port.clone()))
+}
+
+/// Call `open` every `interval` until it works, `timeout` runs out (zero waits forever) or Ctrl-C is pressed
+/// `waiting` is called after every failed attempt that will be retried
+fn retry_open<T, E>(
+    mut open: impl FnMut() -> Result<T, E>,
+    interval: Duration,
+    timeout: Duration,
+    mut waiting: impl FnMut(),
+) -> Result<T, E> {
+    let start = Instant::now();
+    loop {
+        match open() {
+            Ok(port) => return Ok(port),
+            Err(e) => {
+                let timed_out = timeout > Duration::ZERO && start.elapsed() + interval > timeout;
+                if timed_out || STOP.load(Ordering::Relaxed) {
+                    return Err(e);
+                }
+            }
+        }
+        waiting();
+        std::thread::sleep(interval);
+    }
+}
+
+/// Whether an open failed because some other program has the port
+fn is_busy(e: &serialport::Error) -> bool {
+    e.kind() == serialport::ErrorKind::NoDevice || e.description.contains("busy")
+}
+
+/// Explain why the port couldn't be opened and what might fix it
+/// `holders` are the programs known to have the port open
+fn open_error(args: &Args, e: &serialport::Error, holders: &[String]) -> String {
+    let mut msg = format!(
+        "Could not open {} at {} baud: {}",
+        args.port, args.baud_rate, e
+    );
+    if e.kind() == serialport::ErrorKind::Io(io::ErrorKind::PermissionDenied) {
+        msg.push_str("\nYou may need to be in the dialout group (`sudo usermod -aG dialout $USER`, then log in again) or to run with sudo");
+    } else if is_busy(e) {
+        if !holders.is_empty() {
+            msg.push_str(&format!("\nIt is held open by {}", holders.join(", ")));
+        }
+        msg.push_str("\nIf the other program allows sharing, try again with --shared");
+    }
+    msg
+}
+
+/// The programs that have `port` open, as `name (pid)`, where the OS lets us find out
+fn port_holders(port: &str) -> Vec<String> {
+    let target = match std::fs::canonicalize(port) {
+        Ok(path) => path,
+        Err(_) => return Vec::new(),
+    };
+    let procs = match std::fs::read_dir("/proc") {
+        Ok(procs) => procs,
+        Err(_) => return Vec::new(),
+    };
+    let me = std::process::id().to_string();
+    let mut holders = Vec::new();
+    for entry in procs.flatten() {
+        let pid = entry.file_name().to_string_lossy().into_owned();
+        if pid == me || !pid.bytes().all(|b| b.is_ascii_digit()) {
+            continue;
+        }
+        let fds = match std::fs::read_dir(entry.path().join("fd")) {
+            Ok(fds) => fds,
+            Err(_) => continue,
+        };
+        let holds = fds
+            .flatten()
+            .any(|fd| std::fs::read_link(fd.path()).is_ok_and(|link| link == target));
+        if holds {
+            let name = std::fs::read_to_string(entry.path().join("comm")).unwrap_or_default();
+            holders.push(format!("{} ({})", name.trim(), pid));
+        }
+    }
+    holders
+}
+
+/// The one port whose USB descriptor matches, exiting if there isn't exactly one
+fn usb_port(
+    ports: Vec<serialport::SerialPortInfo>,
+    product: Option<&str>,
+    manufacturer: Option<&str>,
+) -> String {
+    let mut found: Vec<_> = ports
+        .into_iter()
+        .filter(|port| picker::matches(port, product, manufacturer))
+        .collect();
+    match found.len() {
+        1 => found.remove(0).port_name,
+        0 => {
+            eprintln!("No USB port matches --usb-product/--usb-manufacturer");
+            exit(1);
+        }
+        _ => {
+            eprintln!("More than one USB port matches, pick one with -p:");
+            for port in &found {
+                eprintln!("  {}", picker::describe(port));
+            }
+            exit(1);
+        }
+    }
+}
+
+fn list_profiles() {
+    let profiles = profile::list().unwrap_or_else(|e| {
+        eprintln!("Could not list the profiles: {}", e);
+        exit(1);
+    });
+    if profiles.is_empty() {
+        println!("No profiles saved yet, save one with --save-profile NAME");
+    }
+    for (name, args) in profiles {
+        println!("{}: {}", name, args.join(" "));
+    }
+}
+
+/// The arguments with those of the environment and the `load` profile added,
+/// the arguments and profile are saved as the `save` profile if given
+fn with_profile(
+    pargs: pico_args::Arguments,
+    load: Option<String>,
+    save: Option<String>,
+    env: &[String],
+) -> pico_args::Arguments {
+    let cli: Vec<String> = pargs
+        .finish()
+        .into_iter()
+        .map(|arg| {
+            arg.into_string().unwrap_or_else(|arg| {
+                eprintln!("Profiles can't hold arguments that aren't UTF-8: {:?}", arg);
+                exit(1);
+            })
+        })
+        .collect();
+    let fail = |e: String| -> ! {
+        eprintln!("Error: {}", e);
+        exit(1);
+    };
+    let profile = match load {
+        Some(name) => profile::load(&name).unwrap_or_else(|e| fail(e)),
+        None => Vec::new(),
+    };
+    if let Some(name) = save {
+        // The environment is left out, it is there for this run
+        let saved = profile::merge(&cli, &profile);
+        let path = profile::save(&name, &saved).unwrap_or_else(|e| fail(e));
+        eprintln!("Saved profile {} to {}", name, path.display());
+    }
+    let args = layered(cli, env, &profile);
+    pico_args::Arguments::from_vec(args.into_iter().map(OsString::from).collect())
+}
+
+/// The command line over the environment over the profile, each only adding the options the ones above don't set
+fn layered(cli: Vec<String>, env: &[String], profile: &[String]) -> Vec<String> {
+    let cli = options::long_forms(cli, HELP);
+    let profile = options::long_forms(profile.to_vec(), HELP);
+    profile::merge(&profile::merge(&cli, env), &profile)
+}
+
+/// Where mqtt mode writes, exits if oxterm was built without the mqtt feature
+#[cfg(feature = "mqtt")]
+fn mqtt_publisher(args: &Args) -> mqtt::Publisher {
+    mqtt::Publisher::connect(&args.mqtt_broker, &args.mqtt_topic, args.mqtt_qos).unwrap_or_else(
+        |e| {
+            eprintln!("Invalid MQTT broker: {}", e);
+            exit(1);
+        },
+    )
+}
+
+#[cfg(not(feature = "mqtt"))]
+fn mqtt_publisher(_args: &Args) -> io::Sink {
+    eprintln!("mqtt mode needs oxterm built with the mqtt feature");
+    exit(1);
+}
+
+/// Where ws mode writes, `duplex` is where what the browsers send goes
+/// Exits if oxterm was built without the ws feature
+#[cfg(feature = "ws")]
+fn ws_broadcaster(args: &Args, duplex: Option<Box<dyn Write + Send>>) -> ws::Broadcaster {
+    let addr = args.ws_listen.as_deref().unwrap_or("127.0.0.1:8080");
+    ws::Broadcaster::listen(addr, duplex, args.output_eol.bytes()).unwrap_or_else(|e| {
+        eprintln!("Could not listen on {}: {}", addr, e);
+        exit(1);
+    })
+}
+
+#[cfg(not(feature = "ws"))]
+fn ws_broadcaster(_args: &Args, _duplex: Option<Box<dyn Write + Send>>) -> io::Sink {
+    eprintln!("ws mode needs oxterm built with the ws feature");
+    exit(1);
+}
+
+fn open_output_file(args: &Args) -> rotate::Rotating {
+    rotate::Rotating::open(&args.output_file, args.append).unwrap_or_else(|e| {
+        eprintln!(
+            "Could not open {}: {}",
+            args.output_file.to_string_lossy(),
+            e
+        );
+        exit(1);
+    })
+}
+
+/// Send the script for --no-read, the port is never read
+fn transmit_only(
+    mut port: impl Write,
+    script: Option<&[Vec<u8>]>,
+    delay: Duration,
+) -> Result<(), String> {
+    if let Some(lines) = script {
+        script::send(&mut port, lines, delay)
+            .map_err(|e| format!("Could not send the script: {}", e))?;
+    }
+    // Wait for the OS to get it all out before the port is closed
+    port.flush()
+        .map_err(|e| format!("Could not send the data: {}", e))
+}
+
+/// The output file name with its placeholders filled in for now, with its directory created
+fn dated_output_file(template: &OsString) -> OsString {
+    let template = template.to_str().unwrap_or_else(|| {
+        eprintln!("--append-timestamp-to-filename needs an --output-file that is valid UTF-8");
+        exit(1);
+    });
+    let name = rotate::dated_name(template, &chrono::Local::now()).unwrap_or_else(|e| {
+        eprintln!("Invalid --output-file: {}", e);
+        exit(1);
+    });
+    if let Some(dir) = std::path::Path::new(&name)
+        .parent()
+        .filter(|d| !d.as_os_str().is_empty())
+    {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            eprintln!("Could not create {}: {}", dir.display(), e);
+            exit(1);
+        }
+    }
+    name.into()
+}
+
+/// Open a file to write received data to, `append` keeps what it already holds
+fn create_output(path: &OsString, append: bool) -> io::Result<File> {
+    OpenOptions::new()
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .create(true)
+        .open(path)
+}
+
+/// Stream a captured file through the same conversion pipeline as a live port
+fn replay_file(path: &OsString, args: &Args) {
+    let mut file = File::open(path).unwrap_or_else(|e| {
+        eprintln!("Could not open {}: {}", path.to_string_lossy(), e);
+        exit(ExitCode::OpenFailed.status());
+    });
+    if let Some(offset) = replay_offset(args) {
+        let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+        if offset > len {
+            eprintln!(
+                "Can't start {} bytes into {}, it is only {} bytes long",
+                offset,
+                path.to_string_lossy(),
+                len
+            );
+            exit(1);
+        }
+        if let Err(e) = file.seek(SeekFrom::Start(offset)) {
+            eprintln!("Could not seek in {}: {}", path.to_string_lossy(), e);
+            exit(1);
+        }
+    }
+    let rate = args.replay_rate.or_else(|| {
+        args.replay_realtime
+            .then(|| replay::char_rate(args.baud_rate, args.data_bits, args.parity, args.stop_bits))
+    });
+    let src: Box<dyn Read> = match rate {
+        Some(rate) => Box::new(replay::Throttled::new(file, rate)),
+        None => Box::new(file),
+    };
+
+    match args.mode.to_lowercase().as_str() {
+        "stdout" | "csv" | "jsonl" | "plot" => stream_to_writer(src, stdout().lock(), None, args),
+        "file" => stream_to_writer(src, open_output_file(args), None, args),
+        "sqlite" => stream_to_writer(src, io::sink(), None, args),
+        "mqtt" => stream_to_writer(src, mqtt_publisher(args), None, args),
+        "ws" => stream_to_writer(src, ws_broadcaster(args, None), None, args),
+        _ => {
+            eprintln!("Replay doesn't support the interactive modes");
+        }
+    }
+}
+
+/// How far into the replay file to start, from --replay-seek-bytes or --replay-seek-time
+fn replay_offset(args: &Args) -> Option<u64> {
+    let at = match (args.replay_seek_bytes, args.replay_seek_time) {
+        (Some(_), Some(_)) => {
+            eprintln!("Use only one of --replay-seek-bytes and --replay-seek-time");
+            exit(1);
+        }
+        (Some(offset), None) => return Some(offset),
+        (None, Some(at)) => at,
+        (None, None) => return None,
+    };
+    let path = args.replay_timing.as_ref().unwrap_or_else(|| {
+        eprintln!(
+            "--replay-seek-time needs --replay-timing, the --timing log recorded with the capture"
+        );
+        exit(1);
+    });
+    let log = std::fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("Could not read {}: {}", path.to_string_lossy(), e);
+        exit(1);
+    });
+    match replay::offset_at(&log, at) {
+        Ok(offset) => Some(offset),
+        Err(e) => {
+            eprintln!("Can't start {}ms into the capture, {}", at.as_millis(), e);
+            exit(1);
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+fn serial_line_interactive(port: Box<dyn SerialPort>, args: &Args) {
+    if let Err(e) = interactive::run(port, args, true) {
+        stream_error(e);
+    }
+}
+
+#[cfg(feature = "tui")]
+fn serial_iterm(port: Box<dyn SerialPort>, args: &Args) {
+    if let Err(e) = interactive::run(port, args, false) {
+        stream_error(e);
+    }
+}
+
+#[cfg(feature = "tui")]
+fn serial_dashboard(port: Box<dyn SerialPort>, args: &Args) {
+    let format = match &args.format {
+        Some(format) => format,
+        None => {
+            eprintln!("dashboard mode needs a --format to decode the records");
+            exit(1);
+        }
+    };
+    if let Err(e) = dashboard::run(port, args, format) {
+        stream_error(e);
+    }
+}
+
+#[cfg(feature = "tui")]
+fn serial_term(port: Box<dyn SerialPort>, args: &Args) {
+    if let Err(e) = term::run(port, args) {
+        stream_error(e);
+    }
+}
+
+/// What the streaming loop can do with a live port besides reading from it
+struct Control {
+    /// A second handle on the port for the modem control lines
+    port: read_error::CurrentPort,
+    errors: Option<line_errors::Counter>,
+}
+
+/// Read only streaming from a serial port, replay file or any other Read source
+/// Writes the data to the Write object (buffered for performance)
+fn stream_to_writer<R: Read>(
+    mut src: R,
+    out: impl Write,
+    mut control: Option<Control>,
+    args: &Args,
+) {
+    let csv = args.csv || args.mode.eq_ignore_ascii_case("csv");
+    let jsonl = !csv && (args.jsonl || args.mode.eq_ignore_ascii_case("jsonl"));
+    // CSV and JSON lines have their own place for the label
+    let line_label = args.label.as_deref().filter(|_| !csv && !jsonl);
+    let buffering = args.buffering.unwrap_or(buffering::Buffering::Block);
+    let out = buffering::Buffered::new(buffering, args.capacity, out);
+    let mut out = label::Labeled::new(out, line_label);
+    let mut buf = vec![0; args.capacity];
+    let read_size = args.read_size();
+
+    let start = Instant::now();
+
+    let mut timing = args.timing.as_ref().map(|path| {
+        let file = File::create(path).unwrap_or_else(|e| {
+            eprintln!("Could not create {}: {}", path.to_string_lossy(), e);
+            exit(ExitCode::WriteFailed.status());
+        });
+        timing::TimingLog::new(BufWriter::new(file), args.timing_lines, start)
+    });
+
+    let group = args.group.unwrap_or_else(|| args.csv_header.len().max(1));
+    let mut encoder: Option<Box<dyn Encoder>> = None;
+    if csv {
+        if args.convertfrom == ConvertFrom::NON {
+            eprintln!("CSV output needs a --convert mode to split the data into values");
+            exit(1);
+        }
+        let timestamp = args.timestamp.then(|| args.timestamp_format.clone());
+        let writer = csv::CsvWriter::new(args.convertfrom, group, timestamp, args.label.clone());
+        if !args.csv_header.is_empty() {
+            writer.header(&args.csv_header, &mut out).unwrap();
+        }
+        encoder = Some(Box::new(writer));
+    } else if jsonl {
+        #[cfg(feature = "json")]
+        {
+            let writer = jsonl::JsonlWriter::new(
+                args.convertfrom,
+                group,
+                args.timestamp_format.clone(),
+                args.label.clone(),
+                args.base64,
+            );
+            encoder = Some(Box::new(writer));
+        }
+        #[cfg(not(feature = "json"))]
+        {
+            eprintln!("JSON lines output needs oxterm built with the json feature");
+            exit(1);
+        }
+    } else if args.mode.eq_ignore_ascii_case("sqlite") {
+        #[cfg(feature = "sqlite")]
+        {
+            let writer = sqlite::SqliteWriter::open(
+                args.output_file.as_ref(),
+                args.convertfrom,
+                group,
+                &args.csv_header,
+                args.timestamp_format.clone(),
+                args.label.clone(),
+            )
+            .unwrap_or_else(|e| {
+                eprintln!("Could not open the database: {}", e);
+                exit(1);
+            });
+            encoder = Some(Box::new(writer));
+        }
+        #[cfg(not(feature = "sqlite"))]
+        {
+            eprintln!("sqlite mode needs oxterm built with the sqlite feature");
+            exit(1);
+        }
+    } else if args.mode.eq_ignore_ascii_case("plot") {
+        if args.convertfrom == ConvertFrom::NON {
+            eprintln!("plot mode needs a --convert mode to split the data into values");
+            exit(1);
+        }
+        let start = args.plot_time.then_some(start);
+        encoder = Some(Box::new(plot::PlotWriter::new(
+            args.convertfrom,
+            args.plot_index,
+            start,
+        )));
+    } else if let Some(format) = &args.format {
+        if args.convertfrom != ConvertFrom::NON {
+            eprintln!("--format decodes the data itself, it can't be used with --convert");
+            exit(1);
+        }
+        encoder = Some(Box::new(format::FormatWriter::new(
+            format.clone(),
+            args.endian,
+            args.raw_values,
+        )));
+    } else if args.columns {
+        if args.convertfrom != ConvertFrom::NON {
+            eprintln!("--columns works on text as it is received, it can't be used with --convert");
+            exit(1);
+        }
+        encoder = Some(Box::new(columns::ColumnWriter::new(args.delimiter)));
+    } else if args.base64 && args.frames.is_none() {
+        if args.convertfrom != ConvertFrom::NON {
+            eprintln!(
+                "--base64 encodes the data as it is received, it can't be used with --convert"
+            );
+            exit(1);
+        }
+        encoder = Some(Box::new(b64::Base64Writer::new()));
+    }
+
+    let mut stats = stats::Stats::new(start, &args.tokens);
+    let mut limits = limits::Limits::new(args, start);
+    if args.newline_on_idle.is_some() && args.convertfrom != ConvertFrom::NON {
+        eprintln!("--newline-on-idle works on text lines, it can't be used with --convert");
+        exit(1);
+    }
+    if args.mark_partial && args.newline_on_idle.is_none() {
+        eprintln!("--mark-partial flags the lines ended by --newline-on-idle, give that too");
+        exit(1);
+    }
+    let mut idle_line = args
+        .newline_on_idle
+        .map(|interval| idle_line::IdleLine::new(interval, start));
+    let mut heartbeat = args
+        .heartbeat
+        .map(|interval| heartbeat::Heartbeat::new(interval, start));
+    let mut histogram = args.histogram.then(histogram::Histogram::new);
+    let mut monitor = match (args.monitor_signals, &control) {
+        (Some(interval), Some(_)) => Some(signals::Monitor::new(interval)),
+        _ => None,
+    };
+    let mut overrun = match (args.detect_overrun, &control) {
+        (true, Some(_)) => Some(overrun::Overrun::new(overrun::OS_BUFFER)),
+        _ => None,
+    };
+    let mut buffer_stats = args
+        .buffer_stats
+        .then(|| buffer_stats::BufferStats::new(read_size));
+    let mut watchdog = match (args.watchdog, &control) {
+        (Some(window), Some(_)) => Some(watchdog::Watchdog::new(window, start)),
+        _ => None,
+    };
+    // Reused by every read so converting doesn't allocate once it has grown
+    let mut converted = Vec::with_capacity(args.capacity * 8);
+    let mut carry = Vec::new();
+    let mut mapped = Vec::new();
+    let mut records = args.record_delim.clone().map(records::RecordSplitter::new);
+    let mut split = Vec::new();
+    let mut deframer = args.frames.map(|framing| match framing {
+        framing::Framing::Cobs => framing::Deframer::Cobs(cobs::CobsDecoder::new()),
+        framing::Framing::Length => framing::Deframer::Length(length::LengthDecoder::new(
+            args.len_size,
+            args.len_endian,
+            args.max_frame,
+        )),
+    });
+    let mut decoded = Vec::new();
+    if args.crc.is_some() && (args.frames.is_none() || args.convertfrom != ConvertFrom::NON) {
+        eprintln!("--crc checks the frames of --framing and writes them as hex, it can't be used with --convert");
+        exit(1);
+    }
+    let mut crc = args
+        .crc
+        .map(|algorithm| crc::FrameCheck::new(algorithm, args.endian));
+    #[cfg(feature = "regex")]
+    let mut trigger = (args.start_trigger.is_some() || args.stop_trigger.is_some()).then(|| {
+        trigger::Trigger::new(
+            args.start_trigger.clone(),
+            args.stop_trigger.clone(),
+            args.repeat_trigger,
+            args.pretrigger,
+        )
+    });
+    #[cfg(feature = "regex")]
+    let mut triggered = Vec::new();
+    if args.decimate > 0 && args.decimate_lines > 0 {
+        eprintln!("Use only one of --decimate and --decimate-lines");
+        exit(1);
+    }
+    if args.decimate > 0 && args.convertfrom == ConvertFrom::NON {
+        eprintln!("--decimate needs a --convert mode to split the data into values, use --decimate-lines for text");
+        exit(1);
+    }
+    let mut decimate = if args.decimate > 0 {
+        Some(decimate::Decimate::values(
+            args.decimate,
+            args.convertfrom.width(),
+        ))
+    } else if args.decimate_lines > 0 {
+        Some(decimate::Decimate::lines(args.decimate_lines))
+    } else {
+        None
+    };
+    let mut decimated = Vec::new();
+    if args.emit_on_change && args.convertfrom != ConvertFrom::NON {
+        eprintln!("--emit-on-change compares whole lines or --format records, it can't be used with --convert");
+        exit(1);
+    }
+    if args.min_interval.is_some() && !args.emit_on_change {
+        eprintln!("--min-interval is the heartbeat of --emit-on-change, give that too");
+        exit(1);
+    }
+    let mut on_change = args.emit_on_change.then(|| match &args.format {
+        Some(format) => on_change::OnChange::records(format.width(), args.min_interval),
+        None => on_change::OnChange::lines(args.min_interval),
+    });
+    let mut changed = Vec::new();
+    let alerting = args.alert_below.is_some() || args.alert_above.is_some();
+    if alerting && args.convertfrom == ConvertFrom::NON {
+        eprintln!(
+            "--alert-below and --alert-above need a --convert mode to split the data into values"
+        );
+        exit(1);
+    }
+    let mut alerts =
+        alerting.then(|| alert::Alerts::new(args.convertfrom, args.alert_below, args.alert_above));
+
+    // BEL is only a bell in text, in binary data it is just another value
+    let bell =
+        (args.convertfrom == ConvertFrom::NON && args.frames.is_none() && args.format.is_none())
+            .then_some(args.bell);
+    let mut flash = bell::Flash::default();
+    // Set when the source failed, so a fatal error can be told apart from failing to write
+    let read_failed = Cell::new(false);
+    let mut copy = || -> Result<bool, io::Error> {
+        let n = match src.read(&mut buf[..read_size]) {
+            // A port read times out instead of returning 0, so this is the end of a file
+            Ok(0) => return Ok(false),
+            Ok(n) => n,
+            Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {
+                // A zero timeout doesn't wait at all, so don't spin on an empty port
+                if args.timeout == Duration::ZERO {
+                    std::thread::sleep(IDLE_SLEEP);
+                }
+                0
+            }
+            Err(e) => {
+                read_failed.set(true);
+                return Err(e);
+            }
+        };
+        if args.strip_parity {
+            bytemap::strip_parity(&mut buf[..n]);
+        }
+        if let Some(buffer_stats) = &mut buffer_stats {
+            buffer_stats.add(n);
+        }
+        if let (Some(monitor), Some(control)) = (&mut monitor, &mut control) {
+            let now = Instant::now();
+            if monitor.due(now) {
+                let signals = signals::Signals::read(control.port.lock().as_mut());
+                if let Some(report) = monitor.update(signals, now) {
+                    eprintln!("[{}] {}", timestamp(&args.timestamp_format), report);
+                }
+            }
+        }
+        if let (Some(overrun), Some(control)) = (&mut overrun, &control) {
+            let waiting = control.port.lock().bytes_to_read();
+            if let Ok(waiting) = waiting {
+                if overrun.sample(waiting) {
+                    eprintln!(
+                        "Warning: {} bytes are waiting in the OS buffer (about {} fit), \
+                         not reading fast enough, try flow control or a larger --max-read-size/--capacity",
+                        waiting,
+                        overrun::OS_BUFFER
+                    );
+                }
+            }
+        }
+        let now = Instant::now();
+        let (n, limited) = limits.take(&buf[..n], now);
+        if let Some(heartbeat) = &mut heartbeat {
+            if n > 0 && heartbeat.data(now) {
+                if args.color {
+                    eprint!("\r\x1b[K");
+                }
+            } else if let Some(waited) = heartbeat.poll(now) {
+                if args.color {
+                    eprint!("\r[waiting… {:.1}s]", waited.as_secs_f64());
+                } else {
+                    eprintln!("[waiting… {:.1}s]", waited.as_secs_f64());
+                }
+            }
+        }
+        if let (Some(watchdog), Some(control)) = (&mut watchdog, &mut control) {
+            if n > 0 {
+                watchdog.data(now);
+            } else if watchdog.due(now) {
+                eprintln!(
+                    "Watchdog: nothing received for {}ms, resetting the device with {}",
+                    args.watchdog.unwrap().as_millis(),
+                    args.reset_line
+                );
+                let pulsed = watchdog::pulse(control.port.lock().as_mut(), args.reset_line);
+                if let Err(e) = pulsed {
+                    eprintln!("Could not reset the device: {}", e);
+                }
+                std::thread::sleep(args.open_delay);
+                watchdog.reset_done(Instant::now());
+            }
+        }
+        stats.count_bytes(&buf[..n]);
+        if let Some(histogram) = &mut histogram {
+            histogram.add(&buf[..n]);
+        }
+        if let Some(timing) = &mut timing {
+            timing.record(Instant::now(), &buf[..n])?;
+        }
+        // Only what is passed on loses its bells, everything above counts them as they were read
+        let n = match bell {
+            Some(bell) => {
+                let (kept, rung) = bell.apply(&mut buf[..n]);
+                if rung > 0 && bell == bell::Bell::Visual && args.color {
+                    flash.ring(Instant::now());
+                }
+                flash.poll(Instant::now());
+                kept
+            }
+            None => n,
+        };
+        let data = &buf[..n];
+        let data = match &mut deframer {
+            Some(deframer) => {
+                decoded.clear();
+                deframer.feed(data, |frame| match &mut crc {
+                    Some(crc) => crc.annotate(frame, args.base64, &mut decoded),
+                    None if args.base64 => b64::line(frame, &mut decoded),
+                    None => decoded.extend_from_slice(frame),
+                });
+                &decoded
+            }
+            None => data,
+        };
+        let data = match &mut records {
+            Some(records) => {
+                split.clear();
+                records.apply(data, &mut split);
+                &split
+            }
+            None => data,
+        };
+        let data = match &args.map {
+            Some(map) => {
+                mapped.clear();
+                map.apply(data, &mut mapped);
+                &mapped
+            }
+            None => data,
+        };
+        // Nothing new arrived, so pass on what ends the line that was left hanging
+        let idle_ended = idle_line.as_mut().is_some_and(|idle| idle.due(data, now));
+        let data = if idle_ended {
+            idle_line::ending(args.mark_partial)
+        } else {
+            data
+        };
+        #[cfg(feature = "regex")]
+        let (data, stopped) = match &mut trigger {
+            Some(trigger) => {
+                triggered.clear();
+                let stopped = trigger.feed(data, &mut triggered);
+                (&triggered[..], stopped)
+            }
+            None => (data, false),
+        };
+        #[cfg(not(feature = "regex"))]
+        let stopped = false;
+        let data = match &mut decimate {
+            Some(decimate) => {
+                decimated.clear();
+                decimate.apply(data, &mut decimated);
+                &decimated
+            }
+            None => data,
+        };
+        let data = match &mut on_change {
+            Some(on_change) => {
+                changed.clear();
+                on_change.apply(data, now, &mut changed);
+                &changed
+            }
+            None => data,
+        };
+        if let Some(alerts) = &mut alerts {
+            alerts.feed(data, |index, value, violation| {
+                let bell = if args.alert_bell { "\x07" } else { "" };
+                if args.color {
+                    eprintln!(
+                        "{}\x1b[1;7;31mALERT: sample {} is {}, {}\x1b[0m",
+                        bell, index, value, violation
+                    );
+                } else {
+                    eprintln!(
+                        "{}ALERT: sample {} is {}, {}",
+                        bell, index, value, violation
+                    );
+                }
+            });
+        }
+
+        if let Some(encoder) = &mut encoder {
+            if args.convertfrom == ConvertFrom::NON {
+                stats.count_text(data);
+            }
+            encoder.write(data, &mut out)?;
+        } else {
+            let data = if args.convertfrom == ConvertFrom::NON {
+                stats.count_text(data);
+                data
+            } else {
+                converted.clear();
+                carry.extend_from_slice(data);
+                let used = match args.hex_words {
+                    Some(size) if args.convertfrom == ConvertFrom::HEX => {
+                        hex_words(&carry, size, args.endian, &mut converted)
                     }
-                    ConvertFrom::UINT => {
-                        let mut out = Vec::with_capacity(buf.len() * 4);
-                        for &bytes in buf.array_chunks() {
-                            write!(out, "{}", u32::from_le_bytes(bytes))?;
+                    _ => args.convertfrom.convert(&carry, &mut converted)?,
+                };
+                carry.drain(..used);
+                &converted
+            };
+
+            out.write_all(data)?;
+        }
+        if idle_ended {
+            out.flush()?;
+        }
+
+        if let Some(rates) = stats.tick(Instant::now()) {
+            if !args.quiet {
+                let errors = control.as_ref().and_then(|c| c.errors.as_ref());
+                match errors.map(|e| e.read()).filter(|e| e.total() > 0) {
+                    Some(errors) => eprintln!("{}, {}", rates, errors),
+                    None => eprintln!("{}", rates),
+                }
+            }
+        }
+
+        Ok(!limited && !stopped && !limits.expired(Instant::now()))
+    };
+
+    let mut failure = None;
+    let mut dedup = dedup::Dedup::new(start);
+    let mut backoff = read_error::Backoff::new(args.retry_backoff, args.read_retries);
+    while !STOP.load(Ordering::Relaxed) {
+        match copy() {
+            Ok(true) => backoff.reset(),
+            Ok(false) => break,
+            Err(e) if read_failed.replace(false) => match classify_read_error(&e) {
+                ReadErrorAction::Ignore => {}
+                ReadErrorAction::Retry => match backoff.retry() {
+                    Some(wait) => {
+                        // Signals are routine, anything else is worth a (collapsed) mention
+                        if e.kind() != io::ErrorKind::Interrupted {
+                            let message = format!("Error: {}, retrying", e);
+                            for line in dedup.message(message, Instant::now()) {
+                                eprintln!("{}", line);
+                            }
                         }
-                        *buf = out;
+                        std::thread::sleep(wait);
                     }
-                    ConvertFrom::USHR => {
-                        let mut out = Vec::with_capacity(buf.len() * 2);
-                        for &bytes in buf.array_chunks() {
-                            write!(out, "{}", u16::from_le_bytes(bytes))?;
-                        }
-                        *buf = out;
+                    None => {
+                        eprintln!("Stopped: {} ({} retries in a row)", e, backoff.retries());
+                        failure = Some(ExitCode::ReadFailed);
+                        break;
                     }
+                },
+                ReadErrorAction::Reconnect if args.wait_for_port => {
+                    eprintln!("Stopped: {}, the port didn't come back", e);
+                    failure = Some(ExitCode::ReadFailed);
+                    break;
                 }
+                ReadErrorAction::Reconnect => {
+                    eprintln!(
+                        "Stopped: {}, the port is gone (--wait-for-port waits for it to come back)",
+                        e
+                    );
+                    failure = Some(ExitCode::ReadFailed);
+                    break;
+                }
+                ReadErrorAction::Exit => {
+                    eprintln!("{}", fatal_message(&e));
+                    failure = Some(ExitCode::ReadFailed);
+                    break;
+                }
+            },
+            Err(e) => match severity(&e) {
+                Severity::Recoverable => {}
+                Severity::BrokenPipe => exit(0),
+                Severity::DiskFull | Severity::Fatal => {
+                    eprintln!("{}", fatal_message(&e));
+                    failure = Some(ExitCode::WriteFailed);
+                    break;
+                }
+            },
+        };
+    }
+
+    // A last line cut short has nothing to be compared with, so it is passed on
+    if let Some(rest) = on_change.as_mut().map(on_change::OnChange::rest) {
+        let written = match &mut encoder {
+            Some(encoder) => encoder.write(&rest, &mut out),
+            None => out.write_all(&rest),
+        };
+        if let Err(e) = written {
+            eprintln!("{}", fatal_message(&e));
+        }
+    }
+    // Whatever still fits is written, the error that stopped us has already been reported
+    if let Err(e) = out.flush() {
+        match severity(&e) {
+            _ if failure.is_some() => {}
+            Severity::Recoverable => {}
+            Severity::BrokenPipe => exit(0),
+            Severity::DiskFull | Severity::Fatal => {
+                eprintln!("{}", fatal_message(&e));
+                failure = Some(ExitCode::WriteFailed);
             }
-            Err(ref e) if e.kind() == io::ErrorKind::TimedOut => (),
-            Err(e) => return Err(e),
         }
+    }
+    if let Some(line) = dedup.repeated(Instant::now()) {
+        eprintln!("{}", line);
+    }
+    // Closing the output can take a moment (the MQTT connection), so do it before the summary
+    drop(out);
+    drop(flash);
+    if args.color && heartbeat.is_some_and(|h| h.showing()) {
+        eprint!("\r\x1b[K");
+    }
+    let summary = stats.summary(Instant::now());
+    eprintln!("{}", summary);
+    if let Some(watchdog) = &watchdog {
+        eprintln!("Watchdog reset the device {} times", watchdog.resets);
+    }
+    let errors = control.as_ref().and_then(|c| c.errors.as_ref());
+    if let Some(errors) = errors.map(|e| e.read()).filter(|e| e.total() > 0) {
+        eprintln!("{}", errors);
+        if errors.suspicious(summary.totals.bytes) {
+            eprintln!(
+                "That is a lot of errors, check the baud rate and framing or try --detect-baud"
+            );
+        }
+    }
+    if let Some(histogram) = &histogram {
+        eprintln!("{}", histogram);
+    }
+    if let Some(buffer_stats) = &buffer_stats {
+        eprintln!("{}", buffer_stats);
+    }
+    if let Some(overrun) = &overrun {
+        eprintln!(
+            "The OS buffer came close to overrunning {} times (at most {} bytes waiting)",
+            overrun.events, overrun.peak
+        );
+    }
+    if let Some(crc) = &crc {
+        eprintln!("{} of {} frames had a bad CRC", crc.bad, crc.frames);
+    }
+    if let Some(alerts) = &alerts {
+        eprintln!("{} values were out of range", alerts.count);
+    }
+    if let Some(on_change) = &on_change {
+        eprintln!("{} unchanged repeats were left out", on_change.suppressed);
+    }
+    if let Some(skipped) = deframer.as_ref().and_then(|d| d.skipped()) {
+        eprintln!("{}", skipped);
+    }
+    #[cfg(feature = "json")]
+    if let Some(path) = &args.summary_json {
+        let line_errors = errors.map(|e| e.read());
+        let summary = serde_json::json!({
+            "bytes": summary.totals.bytes,
+            "lines": summary.totals.lines,
+            "words": summary.totals.words,
+            "counted": summary
+                .totals
+                .counted
+                .iter()
+                .map(|(byte, count)| (format!("{:#04x}", byte), serde_json::json!(count)))
+                .collect::<serde_json::Map<_, _>>(),
+            "duration": summary.duration.as_secs_f64(),
+            "failed": failure.is_some(),
+            "framing_errors": line_errors.map(|e| e.framing),
+            "parity_errors": line_errors.map(|e| e.parity),
+            "overrun_errors": line_errors.map(|e| e.overrun),
+            "frames": crc.as_ref().map(|c| c.frames),
+            "bad_crc_frames": crc.as_ref().map(|c| c.bad),
+            "skipped_frames": deframer.as_ref().map(|d| d.dropped()),
+            "alerts": alerts.as_ref().map(|a| a.count),
+            "suppressed_repeats": on_change.as_ref().map(|c| c.suppressed),
+            "watchdog_resets": watchdog.as_ref().map(|w| w.resets),
+            "near_overruns": overrun.as_ref().map(|o| o.events),
+        });
+        if let Err(e) = summary_json::write(path, &summary) {
+            eprintln!(
+                "Could not write the summary to {}: {}",
+                path.to_string_lossy(),
+                e
+            );
+            failure = Some(ExitCode::WriteFailed);
+        }
+    }
+    let timed_out = !STOP.load(Ordering::Relaxed) && limits.expired(Instant::now());
+    let code = exit_code::ending(
+        failure,
+        timed_out,
+        summary.totals.bytes,
+        args.timeout_exit_code,
+    );
+    if code != ExitCode::Success {
+        exit(code.status());
+    }
+}
 
-        out.write_all(&buf)?; // TODO: I think this should exit the program
+/// How the streaming loop treats an error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Severity {
+    /// Nothing arrived in time or a signal interrupted the read, so just carry on
+    Recoverable,
+    /// Whoever reads our output has gone (`| head` has seen enough), so exit quietly
+    BrokenPipe,
+    /// Writing can't go on, so stop rather than retrying forever
+    DiskFull,
+    /// Anything else, like the port being unplugged or losing permission to write
+    Fatal,
+}
 
-        let now = Instant::now();
-        let time = now - stamp;
-        if time.as_secs() >= 1 {
-            count_words = 0;
-            count_commas = 0;
-            count_bytes = 0;
-            count_lines = 0;
-            println!(
-                "w{}, c{}, b{}, l{}",
-                count_words as f64 / time.as_secs_f64(),
-                count_commas as f64 / time.as_secs_f64(),
-                count_bytes as f64 / time.as_secs_f64(),
-                count_lines as f64 / time.as_secs_f64(),
+fn severity(e: &io::Error) -> Severity {
+    match e.kind() {
+        io::ErrorKind::TimedOut | io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock => {
+            Severity::Recoverable
+        }
+        io::ErrorKind::BrokenPipe => Severity::BrokenPipe,
+        io::ErrorKind::StorageFull | io::ErrorKind::WriteZero => Severity::DiskFull,
+        _ => Severity::Fatal,
+    }
+}
+
+/// The one line printed before stopping on an error
+fn fatal_message(e: &io::Error) -> String {
+    match severity(e) {
+        Severity::DiskFull => format!("Stopped writing, the disk is full ({})", e),
+        _ => format!("Stopped: {}", e),
+    }
+}
+
+/// Report an error that ended a mode and exit, quietly and successfully for a broken pipe
+#[cfg(feature = "tui")]
+fn stream_error(e: std::io::Error) {
+    match severity(&e) {
+        Severity::BrokenPipe => exit(0),
+        Severity::DiskFull => {
+            eprintln!("{}", fatal_message(&e));
+            exit(ExitCode::WriteFailed.status());
+        }
+        _ => {
+            eprintln!("{}", fatal_message(&e));
+            exit(ExitCode::Error.status());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// A file in the temp directory that is only used by one test
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("oxterm-test-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn replay_converts_a_capture_to_hex() {
+        let path = temp_path("replay.bin");
+        std::fs::write(&path, [0x00, 0x0f, 0xa5, 0xff, b'\n']).unwrap();
+        let args = Args {
+            convertfrom: ConvertFrom::HEX,
+            ..Args::default()
+        };
+        let mut out = Vec::new();
+        stream_to_writer(File::open(&path).unwrap(), &mut out, None, &args);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(out, b"0fa5ffa");
+    }
+
+    #[test]
+    fn builder_carries_every_port_setting() {
+        let args = Args {
+            baud_rate: 57600,
+            data_bits: DataBits::Seven,
+            flow_control: FlowControl::Hardware,
+            parity: Parity::Odd,
+            stop_bits: StopBits::Two,
+            timeout: Duration::from_millis(20),
+            ..Args::default()
+        };
+        let expected = serialport::new("/dev/ttyS3", 57600)
+            .data_bits(DataBits::Seven)
+            .flow_control(FlowControl::None)
+            .parity(Parity::Odd)
+            .stop_bits(StopBits::Two)
+            .timeout(Duration::from_millis(20));
+        // The caller decides the flow control, SoftFlow needs the OS to keep out of it
+        assert_eq!(
+            port_builder(&args, "/dev/ttyS3", FlowControl::None),
+            expected
+        );
+    }
+
+    /// A pseudo terminal to open as a port, keep the master and slave open while using it
+    #[cfg(target_os = "linux")]
+    fn pty() -> (File, File, String) {
+        use std::os::unix::io::FromRawFd;
+        let (mut master, mut slave) = (0, 0);
+        let opened = unsafe {
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                std::ptr::null(),
+            )
+        };
+        assert_eq!(opened, 0);
+        let name = std::fs::read_link(format!("/proc/self/fd/{}", slave)).unwrap();
+        let (master, slave) = unsafe { (File::from_raw_fd(master), File::from_raw_fd(slave)) };
+        (master, slave, name.to_string_lossy().into_owned())
+    }
+
+    /// Whether the tty is claimed with TIOCEXCL
+    #[cfg(target_os = "linux")]
+    fn exclusive(tty: &File) -> bool {
+        let mut flag: libc::c_int = 0;
+        let fd = std::os::unix::io::AsRawFd::as_raw_fd(tty);
+        assert_eq!(unsafe { libc::ioctl(fd, libc::TIOCGEXCL, &mut flag) }, 0);
+        flag != 0
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn the_port_is_claimed_unless_shared() {
+        let (_master, slave, name) = pty();
+        let port = open_port(&Args::default(), &name, FlowControl::None).unwrap();
+        assert!(exclusive(&slave));
+        drop(port);
+
+        let (_master, slave, name) = pty();
+        let args = Args {
+            shared: true,
+            ..Args::default()
+        };
+        let port = open_port(&args, &name, FlowControl::None).unwrap();
+        assert!(!exclusive(&slave));
+        drop(port);
+    }
+
+    #[test]
+    fn output_is_truncated_unless_appending() {
+        let path = temp_path("append.txt");
+        std::fs::write(&path, "old\n").unwrap();
+        let path = OsString::from(path);
+        create_output(&path, true)
+            .unwrap()
+            .write_all(b"new\n")
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "old\nnew\n");
+        create_output(&path, false)
+            .unwrap()
+            .write_all(b"only\n")
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "only\n");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    fn port_args() -> Args {
+        Args {
+            port: String::from("/dev/ttyUSB0"),
+            baud_rate: 9600,
+            ..Args::default()
+        }
+    }
+
+    #[test]
+    fn open_error_explains_a_permission_problem() {
+        let e = serialport::Error::new(
+            serialport::ErrorKind::Io(io::ErrorKind::PermissionDenied),
+            "Permission denied",
+        );
+        let msg = open_error(&port_args(), &e, &[]);
+        let mut lines = msg.lines();
+        assert_eq!(
+            lines.next(),
+            Some("Could not open /dev/ttyUSB0 at 9600 baud: Permission denied")
+        );
+        assert!(lines.next().unwrap().contains("dialout group"));
+        assert_eq!(lines.next(), None);
+    }
+
+    #[test]
+    fn open_error_names_who_holds_a_busy_port() {
+        let e = serialport::Error::new(serialport::ErrorKind::NoDevice, "Device or resource busy");
+        assert!(is_busy(&e));
+        let holders = [String::from("minicom (1234)")];
+        assert_eq!(
+            open_error(&port_args(), &e, &holders),
+            "Could not open /dev/ttyUSB0 at 9600 baud: Device or resource busy\n\
+             It is held open by minicom (1234)\n\
+             If the other program allows sharing, try again with --shared"
+        );
+    }
+
+    #[test]
+    fn open_error_is_one_line_for_other_failures() {
+        let e = serialport::Error::new(
+            serialport::ErrorKind::Io(io::ErrorKind::NotFound),
+            "No such file or directory",
+        );
+        assert!(!is_busy(&e));
+        assert_eq!(
+            open_error(&port_args(), &e, &[]),
+            "Could not open /dev/ttyUSB0 at 9600 baud: No such file or directory"
+        );
+    }
+
+    #[test]
+    fn retry_open_keeps_trying_until_the_port_appears() {
+        let mut attempts = 0;
+        let mut waited = 0;
+        let opened = retry_open(
+            || {
+                attempts += 1;
+                if attempts < 3 {
+                    Err("not yet")
+                } else {
+                    Ok("port")
+                }
+            },
+            Duration::from_millis(1),
+            Duration::ZERO,
+            || waited += 1,
+        );
+        assert_eq!(opened, Ok("port"));
+        assert_eq!(attempts, 3);
+        assert_eq!(waited, 2);
+    }
+
+    #[test]
+    fn retry_open_gives_up_after_the_timeout() {
+        let start = Instant::now();
+        let opened: Result<(), _> = retry_open(
+            || Err("never"),
+            Duration::from_millis(10),
+            Duration::from_millis(50),
+            || {},
+        );
+        assert_eq!(opened, Err("never"));
+        let took = start.elapsed();
+        assert!(took >= Duration::from_millis(30) && took < Duration::from_secs(1));
+    }
+
+    fn words(data: &[u8], size: usize, endian: Endian) -> (String, usize) {
+        let mut out = Vec::new();
+        let used = hex_words(data, size, endian, &mut out);
+        (String::from_utf8(out).unwrap(), used)
+    }
+
+    #[test]
+    fn hex_words_of_both_sizes_and_endiannesses() {
+        let data = [0x01, 0x02, 0x03, 0x04, 0xab];
+        assert_eq!(
+            words(&data, 2, Endian::Little),
+            (String::from("0201 0403 "), 4)
+        );
+        assert_eq!(
+            words(&data, 2, Endian::Big),
+            (String::from("0102 0304 "), 4)
+        );
+        assert_eq!(
+            words(&data, 4, Endian::Little),
+            (String::from("04030201 "), 4)
+        );
+        assert_eq!(words(&data, 4, Endian::Big), (String::from("01020304 "), 4));
+        // Leading zeros are kept so the words line up
+        assert_eq!(words(&[0, 0], 2, Endian::Big), (String::from("0000 "), 2));
+        assert_eq!(words(&[0xff], 2, Endian::Big), (String::new(), 0));
+    }
+
+    #[test]
+    fn endian_names() {
+        assert_eq!("LE".parse(), Ok(Endian::Little));
+        assert_eq!("big".parse(), Ok(Endian::Big));
+        assert!("middle".parse::<Endian>().is_err());
+    }
+
+    #[test]
+    fn parity_accepts_all_five_names() {
+        assert_eq!(parse_parity("none"), Ok(Parity::None));
+        assert_eq!(parse_parity("Odd"), Ok(Parity::Odd));
+        assert_eq!(parse_parity("EVEN"), Ok(Parity::Even));
+        for unsupported in ["mark", "Space"] {
+            assert_eq!(
+                parse_parity(unsupported),
+                Err("parity mode not supported on this platform")
             );
-            stamp = now;
         }
+        assert_eq!(
+            parse_parity("sometimes"),
+            Err("Parity option passed an invalid value")
+        );
+    }
 
-        Ok(())
-    };
+    #[test]
+    fn seconds_must_be_a_time_that_can_be_waited_for() {
+        assert_eq!(seconds("2.5"), Ok(Duration::from_millis(2500)));
+        assert_eq!(seconds("0"), Ok(Duration::ZERO));
+        assert!(seconds("-1").is_err());
+        assert!(seconds("NaN").is_err());
+        assert!(seconds("inf").is_err());
+        assert!(seconds("1e300").is_err());
+        assert!(seconds("soon").is_err());
+    }
 
-    loop {
-        match copy() {
-            Ok(_) => {}
-            Err(e) => serial_read_error(e),
-        };
-        // match io::copy(&mut port, &mut out) {
-        //     Ok(n) => {
-        //         if counts {
-        //             // TODO: count bytes, bytes/sec, lines, etc
-        //             println!("{}", n);
-        //         }
-        //     }
-        //     Err(ref e) if e.kind() == io::ErrorKind::TimedOut => (),
-        //     Err(e) => serial_read_error(&port, e),
-        // }
-    }
-}
-
-fn serial_read_error(e: std::io::Error) {
-    // TODO: better error messages
-    eprintln!("{:?}", e);
-    // TODO: Should exit/panic?
+    #[test]
+    fn buffers_hold_at_least_a_byte() {
+        assert_eq!(buffer_size("1"), Ok(1));
+        assert_eq!(buffer_size("4096"), Ok(4096));
+        assert!(buffer_size("0").is_err());
+        assert!(buffer_size("-1").is_err());
+        assert!(buffer_size("lots").is_err());
+    }
+
+    #[test]
+    fn reads_are_capped_by_the_buffer() {
+        let read_size = |capacity, max_read_size| {
+            Args {
+                capacity,
+                max_read_size,
+                ..Args::default()
+            }
+            .read_size()
+        };
+        assert_eq!(read_size(64, None), 64);
+        assert_eq!(read_size(64, Some(16)), 16);
+        assert_eq!(read_size(64, Some(1000)), 64);
+        assert_eq!(read_size(64, Some(0)), 1);
+        assert_eq!(read_size(1, Some(0)), 1);
+    }
+
+    #[test]
+    fn capped_reads_still_stream_everything() {
+        let input = "0123456789abcdef\n".repeat(1000).into_bytes();
+        let args = Args {
+            capacity: 1000,
+            max_read_size: Some(7),
+            ..Args::default()
+        };
+        assert_eq!(stream(&input, &args), input);
+    }
+
+    #[test]
+    fn the_output_does_not_depend_on_how_the_data_was_read() {
+        // Every byte value, BEL included, so passed on with --bell audible
+        let input: Vec<u8> = (0..5000u32).map(|i| (i * 7 % 256) as u8).collect();
+        // A port timeout, so the reads that time out aren't followed by a sleep
+        let timeout = Duration::from_millis(1);
+        let raw = |capacity, max_read_size| Args {
+            bell: bell::Bell::Audible,
+            capacity,
+            max_read_size,
+            timeout,
+            ..Args::default()
+        };
+        let hex = |capacity, max_read_size| Args {
+            convertfrom: ConvertFrom::HEX,
+            hex_words: Some(2),
+            capacity,
+            max_read_size,
+            timeout,
+            ..Args::default()
+        };
+        let whole_raw = stream(&input, &raw(input.len(), None));
+        let whole_hex = stream(&input, &hex(input.len(), None));
+        assert_eq!(whole_raw, input);
+        for (chunk, capacity, max_read_size) in [(1, 64, None), (7, 64, Some(5)), (300, 4096, None)]
+        {
+            for (args, expected) in [
+                (raw(capacity, max_read_size), &whole_raw),
+                (hex(capacity, max_read_size), &whole_hex),
+            ] {
+                let src = Trickle {
+                    data: input.clone(),
+                    chunk,
+                    timed_out: false,
+                };
+                let mut out = Vec::new();
+                stream_to_writer(src, &mut out, None, &args);
+                assert_eq!(&out, expected, "{} byte reads", chunk);
+            }
+        }
+    }
+
+    /// What the streaming loop writes for `input` read from memory
+    fn stream(input: &[u8], args: &Args) -> Vec<u8> {
+        let mut out = Vec::new();
+        stream_to_writer(Cursor::new(input.to_vec()), &mut out, None, args);
+        out
+    }
+
+    /// Hands out its data a few bytes at a time with a timeout in between, like a slow port
+    struct Trickle {
+        data: Vec<u8>,
+        chunk: usize,
+        timed_out: bool,
+    }
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.timed_out = !self.timed_out;
+            if self.timed_out && !self.data.is_empty() {
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+            let n = self.chunk.min(buf.len()).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data.drain(..n);
+            Ok(n)
+        }
+    }
+
+    /// Has nothing to read until `ready`, then one line, counting every read
+    struct Slow<'a> {
+        ready: Instant,
+        reads: &'a Cell<usize>,
+        sent: bool,
+    }
+
+    impl Read for Slow<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.reads.set(self.reads.get() + 1);
+            if Instant::now() < self.ready {
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+            if std::mem::replace(&mut self.sent, true) {
+                return Ok(0);
+            }
+            buf[..3].copy_from_slice(b"ok\n");
+            Ok(3)
+        }
+    }
+
+    #[test]
+    fn piped_output_is_quiet_unless_stats_are_asked_for() {
+        // quiet, stats, stdout_tty
+        assert!(!quiet_output(false, false, true));
+        assert!(quiet_output(false, false, false));
+        assert!(!quiet_output(false, true, false));
+        assert!(quiet_output(true, false, true));
+        assert!(quiet_output(true, true, false));
+    }
+
+    #[test]
+    fn version_has_the_crate_version_target_and_features() {
+        let version = version();
+        let mut lines = version.lines();
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with(concat!("oxterm ", env!("CARGO_PKG_VERSION"))));
+        assert_eq!(
+            lines.next(),
+            Some(format!("target: {}", env!("BUILD_TARGET")).as_str())
+        );
+        assert_eq!(
+            lines.next(),
+            Some(format!("features: {}", features().join(", ")).as_str())
+        );
+        assert_eq!(cfg!(feature = "tui"), features().contains(&"tui"));
+    }
+
+    #[test]
+    fn the_watchdog_resets_the_device_when_the_data_stops() {
+        let port = mock::MockPort::with_input(b"hello\n");
+        let control = Control {
+            port: read_error::CurrentPort::new(Box::new(port.clone())),
+            errors: None,
+        };
+        let args = Args {
+            watchdog: Some(Duration::from_millis(100)),
+            duration: Duration::from_millis(450),
+            ..Args::default()
+        };
+        let mut out = Vec::new();
+        stream_to_writer(port.clone(), &mut out, Some(control), &args);
+        assert_eq!(out, b"hello\n");
+        // How many resets fit in the time depends on the machine, but each is a whole pulse
+        let lines = port.state().lines.clone();
+        assert!(!lines.is_empty(), "{:?}", lines);
+        for pulse in lines.chunks(2) {
+            assert_eq!(pulse, [("DTR", false), ("DTR", true)]);
+        }
+    }
+
+    #[test]
+    fn the_watchdog_resets_the_port_it_reconnected_to() {
+        let lost = mock::MockPort::with_input(b"hello\n");
+        lost.state().fail = Some(io::ErrorKind::NotConnected);
+        let back = mock::MockPort::default();
+        let control = Control {
+            port: read_error::CurrentPort::new(Box::new(lost.clone())),
+            errors: None,
+        };
+        let mut reopened = Some(back.clone());
+        let src = read_error::Reconnecting::new(Box::new(lost.clone()), "mock", || {
+            Ok(Box::new(reopened.take().unwrap()) as Box<dyn SerialPort>)
+        })
+        .current(control.port.clone());
+        let args = Args {
+            watchdog: Some(Duration::from_millis(50)),
+            duration: Duration::from_millis(300),
+            ..Args::default()
+        };
+        let mut out = Vec::new();
+        stream_to_writer(src, &mut out, Some(control), &args);
+        assert_eq!(out, b"hello\n");
+        assert!(lost.state().lines.is_empty());
+        let lines = back.state().lines.clone();
+        assert!(
+            lines.starts_with(&[("DTR", false), ("DTR", true)]),
+            "{:?}",
+            lines
+        );
+    }
+
+    #[test]
+    fn errors_are_recoverable_quiet_or_fatal() {
+        let severity_of = |kind: io::ErrorKind| severity(&kind.into());
+        assert_eq!(severity_of(io::ErrorKind::TimedOut), Severity::Recoverable);
+        assert_eq!(
+            severity_of(io::ErrorKind::Interrupted),
+            Severity::Recoverable
+        );
+        assert_eq!(severity_of(io::ErrorKind::BrokenPipe), Severity::BrokenPipe);
+        let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+        assert_eq!(severity(&denied), Severity::Fatal);
+        assert_eq!(fatal_message(&denied), format!("Stopped: {}", denied));
+    }
+
+    #[test]
+    fn a_full_disk_stops_the_writing() {
+        for kind in [io::ErrorKind::StorageFull, io::ErrorKind::WriteZero] {
+            assert_eq!(severity(&kind.into()), Severity::DiskFull);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn enospc_is_a_full_disk() {
+        let full = io::Error::from_raw_os_error(libc::ENOSPC);
+        assert_eq!(severity(&full), Severity::DiskFull);
+        assert!(fatal_message(&full).starts_with("Stopped writing, the disk is full ("));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn the_json_summary_has_the_session_counts() {
+        let path = temp_path("session.json");
+        let values: Vec<u8> = [5i16, 50, -5, 7]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let args = Args {
+            convertfrom: ConvertFrom::SHR,
+            alert_below: Some(0.0),
+            alert_above: Some(10.0),
+            summary_json: Some(path.clone().into_os_string()),
+            ..Args::default()
+        };
+        stream(&values, &args);
+        let summary: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(summary["bytes"], 8);
+        assert_eq!(summary["alerts"], 2);
+        assert_eq!(summary["failed"], false);
+        assert!(summary["duration"].as_f64().unwrap() >= 0.0);
+        // Not counted in this session
+        assert!(summary["watchdog_resets"].is_null());
+        assert!(summary["framing_errors"].is_null());
+        assert_eq!(summary.as_object().unwrap().len(), 16);
+    }
+
+    #[test]
+    fn a_dated_output_file_gets_its_directory_created() {
+        let dir = temp_path("dated");
+        let template = dir.join("nested").join("capture-%Y.txt");
+        let name = dated_output_file(&template.into_os_string());
+        let year = chrono::Local::now().format("%Y").to_string();
+        assert_eq!(
+            name,
+            dir.join("nested")
+                .join(format!("capture-{}.txt", year))
+                .into_os_string()
+        );
+        assert!(dir.join("nested").is_dir());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn transmit_only_sends_the_script_without_reading() {
+        let port = mock::MockPort::with_input(b"a reply");
+        let script = [b"first\r\n".to_vec(), b"second\r\n".to_vec()];
+        transmit_only(port.clone(), Some(&script), Duration::ZERO).unwrap();
+        let state = port.state();
+        assert_eq!(state.written, b"first\r\nsecond\r\n");
+        assert_eq!(state.reads, 0);
+        assert_eq!(state.input.len(), 7);
+    }
+
+    #[test]
+    fn the_command_line_beats_the_environment_which_beats_the_profile() {
+        let strings = |args: &[&str]| args.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let vars = [
+            ("OXTERM_BAUD", "57600"),
+            ("OXTERM_PARITY", "even"),
+            ("OXTERM_APPEND", "1"),
+            ("OXTERM_QUIET", "0"),
+            ("PATH", "/bin"),
+        ];
+        let env = env::args(
+            vars.iter()
+                .map(|(k, v)| (OsString::from(k), OsString::from(v))),
+            HELP,
+        )
+        .unwrap();
+        assert_eq!(env, ["--append", "--baud-rate=57600", "--parity=even"]);
+
+        let profile = strings(&["--baud-rate", "9600", "-P", "odd", "--stop-bits", "2"]);
+        let args = layered(
+            strings(&["-b", "115200", "-p", "/dev/ttyUSB0"]),
+            &env,
+            &profile,
+        );
+        let mut pargs =
+            pico_args::Arguments::from_vec(args.into_iter().map(OsString::from).collect());
+        let baud: u32 = pargs
+            .value_from_str(["-b", "--baud-rate"])
+            .unwrap_or_else(|e| invalid_argument("--baud-rate", e));
+        let parity: String = pargs
+            .value_from_str(["-P", "--parity"])
+            .unwrap_or_else(|e| invalid_argument("--parity", e));
+        let stop: u8 = pargs
+            .value_from_str(["-s", "--stop-bits"])
+            .unwrap_or_else(|e| invalid_argument("--stop-bits", e));
+        assert_eq!((baud, parity.as_str(), stop), (115200, "even", 2));
+        assert!(pargs.contains(["-a", "--append"]));
+        let _: String = pargs
+            .value_from_str(["-p", "--port"])
+            .unwrap_or_else(|e| invalid_argument("--port", e));
+        // Nothing shadowed is left over to be warned about
+        assert!(pargs.finish().is_empty());
+    }
+
+    #[test]
+    fn a_variable_for_no_option_is_an_error() {
+        let vars = vec![(OsString::from("OXTERM_NOPE"), OsString::from("1"))];
+        assert_eq!(
+            env::args(vars.into_iter(), HELP),
+            Err(String::from("OXTERM_NOPE doesn't match any option"))
+        );
+        let vars = vec![(OsString::from("OXTERM_APPEND"), OsString::from("maybe"))];
+        assert!(env::args(vars.into_iter(), HELP)
+            .unwrap_err()
+            .contains("is a switch"));
+    }
+
+    #[test]
+    fn a_partial_line_is_flushed_after_going_idle() {
+        /// Sends its chunks once their time has come, timing out in between
+        struct Paused(Vec<(Instant, &'static [u8])>);
+
+        impl Read for Paused {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                match self.0.first() {
+                    None => Ok(0),
+                    Some((at, _)) if Instant::now() < *at => {
+                        std::thread::sleep(Duration::from_millis(1));
+                        Err(io::ErrorKind::TimedOut.into())
+                    }
+                    Some(_) => {
+                        let (_, data) = self.0.remove(0);
+                        buf[..data.len()].copy_from_slice(data);
+                        Ok(data.len())
+                    }
+                }
+            }
+        }
+
+        let start = Instant::now();
+        let source = Paused(vec![
+            (start, b"temp=2"),
+            (start + Duration::from_millis(150), b"1.5\n"),
+        ]);
+        let args = Args {
+            newline_on_idle: Some(Duration::from_millis(50)),
+            mark_partial: true,
+            ..Args::default()
+        };
+        let mut out = Vec::new();
+        stream_to_writer(source, &mut out, None, &args);
+        assert_eq!(out, b"temp=2 [partial]\n1.5\n");
+    }
+
+    #[test]
+    fn stripped_parity_streams_as_text() {
+        let args = Args {
+            strip_parity: true,
+            ..Args::default()
+        };
+        let received: Vec<u8> = b"OK\r\n".iter().map(|b| b | 0x80).collect();
+        assert_eq!(stream(&received, &args), b"OK\r\n");
+    }
+
+    #[test]
+    fn bells_are_left_out_of_the_stream_unless_audible() {
+        assert_eq!(stream(b"a\x07b\n", &Args::default()), b"ab\n");
+        let audible = Args {
+            bell: bell::Bell::Audible,
+            ..Args::default()
+        };
+        assert_eq!(stream(b"a\x07b\n", &audible), b"a\x07b\n");
+    }
+
+    #[test]
+    fn repeated_lines_are_left_out_of_the_stream() {
+        let args = Args {
+            emit_on_change: true,
+            ..Args::default()
+        };
+        assert_eq!(stream(b"1\n1\n1\n2\n2\n1\nend", &args), b"1\n2\n1\nend");
+    }
+
+    #[test]
+    fn a_zero_timeout_does_not_spin_on_a_slow_source() {
+        let reads = Cell::new(0);
+        let src = Slow {
+            ready: Instant::now() + Duration::from_millis(200),
+            reads: &reads,
+            sent: false,
+        };
+        let mut out = Vec::new();
+        stream_to_writer(src, &mut out, None, &Args::default());
+        assert_eq!(out, b"ok\n");
+        // Sleeping IDLE_SLEEP between empty reads allows at most about 40 in 200ms
+        assert!(reads.get() <= 50, "{} reads", reads.get());
+    }
+
+    #[test]
+    fn streams_an_in_memory_source() {
+        let input = b"hello\nworld\n";
+        assert_eq!(stream(input, &Args::default()), input);
+    }
+
+    #[test]
+    fn values_split_across_reads_are_joined() {
+        let args = Args {
+            convertfrom: ConvertFrom::SHR,
+            ..Args::default()
+        };
+        let src = Trickle {
+            data: [1i16, -2, 300]
+                .iter()
+                .flat_map(|v| v.to_le_bytes())
+                .collect(),
+            chunk: 3,
+            timed_out: false,
+        };
+        let mut out = Vec::new();
+        stream_to_writer(src, &mut out, None, &args);
+        assert_eq!(out, b"1-2300");
+    }
+
+    #[test]
+    fn capture_stops_at_the_byte_and_line_limits() {
+        let input = b"one\ntwo\nthree\nfour\n";
+        let args = Args {
+            max_bytes: 6,
+            ..Args::default()
+        };
+        assert_eq!(stream(input, &args), b"one\ntw");
+        let args = Args {
+            max_lines: 2,
+            capacity: 3,
+            ..Args::default()
+        };
+        assert_eq!(stream(input, &args), b"one\ntwo\n");
+    }
+
+    #[test]
+    fn decimation_keeps_one_converted_value_in_n() {
+        let values: Vec<u8> = (0..10u32).flat_map(u32::to_le_bytes).collect();
+        let kept: Vec<u8> = (0..10u32).step_by(3).flat_map(u32::to_le_bytes).collect();
+        let mut expected = Vec::new();
+        ConvertFrom::UINT.convert(&kept, &mut expected).unwrap();
+        let args = Args {
+            convertfrom: ConvertFrom::UINT,
+            decimate: 3,
+            capacity: 5,
+            ..Args::default()
+        };
+        assert_eq!(stream(&values, &args), expected);
+    }
+
+    #[test]
+    fn sustained_conversion_matches_converting_at_once() {
+        let values: Vec<u8> = (0..100_000i32)
+            .flat_map(|v| (v * 7919).to_le_bytes())
+            .collect();
+        let mut expected = Vec::new();
+        ConvertFrom::UINT.convert(&values, &mut expected).unwrap();
+        let args = Args {
+            convertfrom: ConvertFrom::UINT,
+            // Not a multiple of 4 so values keep being split between reads
+            capacity: 61,
+            ..Args::default()
+        };
+        assert_eq!(stream(&values, &args), expected);
+    }
 }
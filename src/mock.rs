@@ -0,0 +1,197 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use serialport::{ClearBuffer, DataBits, FlowControl, Parity, SerialPort, StopBits};
+
+/// What a `MockPort` and all its clones have been told and will hand out
+#[derive(Debug)]
+pub struct State {
+    /// Bytes for reads to return, a read times out once this is empty
+    pub input: VecDeque<u8>,
+    /// An error for reads to fail with instead once the input has run out
+    pub fail: Option<io::ErrorKind>,
+    pub written: Vec<u8>,
+    /// Calls to `read`, including the ones that timed out
+    pub reads: usize,
+    /// Every change of the control lines in order, as ("DTR" or "RTS", level)
+    pub lines: Vec<(&'static str, bool)>,
+    pub baud_rate: u32,
+    pub data_bits: DataBits,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    pub flow_control: FlowControl,
+    pub timeout: Duration,
+    pub cts: bool,
+    pub dsr: bool,
+    pub ri: bool,
+    pub cd: bool,
+    pub breaking: bool,
+    /// Writes are also queued to be read, like a port with TX wired to RX
+    pub echo: bool,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State {
+            input: VecDeque::new(),
+            fail: None,
+            written: Vec::new(),
+            reads: 0,
+            lines: Vec::new(),
+            baud_rate: 9600,
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            flow_control: FlowControl::None,
+            timeout: Duration::ZERO,
+            cts: false,
+            dsr: false,
+            ri: false,
+            cd: false,
+            breaking: false,
+            echo: false,
+        }
+    }
+}
+
+/// A serial port in memory for the tests, its clones share one `State`
+#[derive(Debug, Clone, Default)]
+pub struct MockPort {
+    pub state: Arc<Mutex<State>>,
+}
+
+impl MockPort {
+    /// A port with `input` waiting to be read
+    pub fn with_input(input: &[u8]) -> Self {
+        let port = MockPort::default();
+        port.state().input.extend(input);
+        port
+    }
+
+    pub fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+}
+
+impl Read for MockPort {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut state = self.state();
+        state.reads += 1;
+        if state.input.is_empty() {
+            return Err(state.fail.unwrap_or(io::ErrorKind::TimedOut).into());
+        }
+        let n = buf.len().min(state.input.len());
+        for (b, input) in buf.iter_mut().zip(state.input.drain(..n)) {
+            *b = input;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for MockPort {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.state();
+        state.written.extend_from_slice(buf);
+        if state.echo {
+            state.input.extend(buf);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SerialPort for MockPort {
+    fn name(&self) -> Option<String> {
+        Some(String::from("mock"))
+    }
+    fn baud_rate(&self) -> serialport::Result<u32> {
+        Ok(self.state().baud_rate)
+    }
+    fn data_bits(&self) -> serialport::Result<DataBits> {
+        Ok(self.state().data_bits)
+    }
+    fn flow_control(&self) -> serialport::Result<FlowControl> {
+        Ok(self.state().flow_control)
+    }
+    fn parity(&self) -> serialport::Result<Parity> {
+        Ok(self.state().parity)
+    }
+    fn stop_bits(&self) -> serialport::Result<StopBits> {
+        Ok(self.state().stop_bits)
+    }
+    fn timeout(&self) -> Duration {
+        self.state().timeout
+    }
+    fn set_baud_rate(&mut self, baud_rate: u32) -> serialport::Result<()> {
+        self.state().baud_rate = baud_rate;
+        Ok(())
+    }
+    fn set_data_bits(&mut self, data_bits: DataBits) -> serialport::Result<()> {
+        self.state().data_bits = data_bits;
+        Ok(())
+    }
+    fn set_flow_control(&mut self, flow_control: FlowControl) -> serialport::Result<()> {
+        self.state().flow_control = flow_control;
+        Ok(())
+    }
+    fn set_parity(&mut self, parity: Parity) -> serialport::Result<()> {
+        self.state().parity = parity;
+        Ok(())
+    }
+    fn set_stop_bits(&mut self, stop_bits: StopBits) -> serialport::Result<()> {
+        self.state().stop_bits = stop_bits;
+        Ok(())
+    }
+    fn set_timeout(&mut self, timeout: Duration) -> serialport::Result<()> {
+        self.state().timeout = timeout;
+        Ok(())
+    }
+    fn write_request_to_send(&mut self, level: bool) -> serialport::Result<()> {
+        self.state().lines.push(("RTS", level));
+        Ok(())
+    }
+    fn write_data_terminal_ready(&mut self, level: bool) -> serialport::Result<()> {
+        self.state().lines.push(("DTR", level));
+        Ok(())
+    }
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+        Ok(self.state().cts)
+    }
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+        Ok(self.state().dsr)
+    }
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+        Ok(self.state().ri)
+    }
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+        Ok(self.state().cd)
+    }
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        Ok(self.state().input.len() as u32)
+    }
+    fn bytes_to_write(&self) -> serialport::Result<u32> {
+        Ok(0)
+    }
+    fn clear(&self, buffer_to_clear: ClearBuffer) -> serialport::Result<()> {
+        if let ClearBuffer::Input | ClearBuffer::All = buffer_to_clear {
+            self.state().input.clear();
+        }
+        Ok(())
+    }
+    fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
+        Ok(Box::new(self.clone()))
+    }
+    fn set_break(&self) -> serialport::Result<()> {
+        self.state().breaking = true;
+        Ok(())
+    }
+    fn clear_break(&self) -> serialport::Result<()> {
+        self.state().breaking = false;
+        Ok(())
+    }
+}
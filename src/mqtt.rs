@@ -0,0 +1,210 @@
+use std::io::{self, Write};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use rumqttc::{Client, Event, MqttOptions, Outgoing, QoS};
+
+/// Messages held while the broker is unreachable, newer ones are dropped once this is full
+const QUEUE: usize = 1000;
+/// How long to wait before reconnecting to the broker
+const RECONNECT: Duration = Duration::from_secs(1);
+/// How long queued messages get to go out when closing
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Split `host[:port]`, the port defaults to 1883
+pub fn parse_broker(s: &str) -> Result<(String, u16), String> {
+    match s.rsplit_once(':') {
+        Some((host, port)) => port
+            .parse()
+            .map(|port| (host.to_owned(), port))
+            .map_err(|_| format!("\"{}\" is not a port number in \"{}\"", port, s)),
+        None => Ok((s.to_owned(), 1883)),
+    }
+}
+
+/// Publishes every line written to it as one message on `topic`
+/// The connection runs on its own thread and reconnects by itself when the broker goes away
+pub struct Publisher {
+    client: Client,
+    topic: String,
+    qos: QoS,
+    line: Vec<u8>,
+    dropped: usize,
+    closed: mpsc::Receiver<()>,
+}
+
+impl Publisher {
+    pub fn connect(broker: &str, topic: &str, qos: u8) -> Result<Self, String> {
+        let (host, port) = parse_broker(broker)?;
+        let id = format!("oxterm-{}", std::process::id());
+        let mut options = MqttOptions::new(id, host, port);
+        options.set_keep_alive(Duration::from_secs(30));
+        let (client, mut connection) = Client::new(options, QUEUE);
+
+        let (done, closed) = mpsc::channel();
+        thread::spawn(move || {
+            let mut connected = true;
+            for event in connection.iter() {
+                match event {
+                    Ok(Event::Outgoing(Outgoing::Disconnect)) => break,
+                    Ok(_) => {
+                        if !connected {
+                            eprintln!("Reconnected to the MQTT broker");
+                            connected = true;
+                        }
+                    }
+                    Err(e) => {
+                        if connected {
+                            eprintln!("Lost the MQTT broker ({}), retrying", e);
+                            connected = false;
+                        }
+                        thread::sleep(RECONNECT);
+                    }
+                }
+            }
+            let _ = done.send(());
+        });
+
+        Ok(Publisher {
+            client,
+            topic: topic.to_owned(),
+            qos: rumqttc::qos(qos).map_err(|e| e.to_string())?,
+            line: Vec::new(),
+            dropped: 0,
+            closed,
+        })
+    }
+
+    fn publish(&mut self) {
+        let line = self.line.strip_suffix(b"\n").unwrap_or(&self.line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line).to_vec();
+        self.line.clear();
+        if self
+            .client
+            .try_publish(self.topic.as_str(), self.qos, false, line)
+            .is_err()
+        {
+            self.dropped += 1;
+        }
+    }
+}
+
+impl Write for Publisher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &b in buf {
+            self.line.push(b);
+            if b == b'\n' {
+                self.publish();
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for Publisher {
+    fn drop(&mut self) {
+        if !self.line.is_empty() {
+            self.publish();
+        }
+        // Queued after the messages, so they have gone out once the disconnect has
+        if self.client.try_disconnect().is_ok() {
+            let _ = self.closed.recv_timeout(CLOSE_TIMEOUT);
+        }
+        if self.dropped > 0 {
+            eprintln!(
+                "Dropped {} MQTT messages while the broker was unreachable",
+                self.dropped
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::{TcpListener, TcpStream};
+
+    #[test]
+    fn brokers_parse_with_the_default_port() {
+        assert_eq!(
+            parse_broker("localhost"),
+            Ok((String::from("localhost"), 1883))
+        );
+        assert_eq!(
+            parse_broker("10.0.0.2:8883"),
+            Ok((String::from("10.0.0.2"), 8883))
+        );
+        assert!(parse_broker("host:mqtt").is_err());
+    }
+
+    /// Read one MQTT packet, returning its type and body
+    fn packet(stream: &mut TcpStream) -> io::Result<(u8, Vec<u8>)> {
+        let mut byte = [0];
+        stream.read_exact(&mut byte)?;
+        let kind = byte[0] >> 4;
+        let (mut len, mut shift) = (0, 0);
+        loop {
+            stream.read_exact(&mut byte)?;
+            len |= ((byte[0] & 0x7f) as usize) << shift;
+            shift += 7;
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+        }
+        let mut body = vec![0; len];
+        stream.read_exact(&mut body)?;
+        Ok((kind, body))
+    }
+
+    /// Accept one client and collect the (topic, payload) of everything it publishes
+    fn broker(listener: TcpListener) -> Vec<(String, Vec<u8>)> {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut published = Vec::new();
+        while let Ok((kind, body)) = packet(&mut stream) {
+            match kind {
+                // CONNECT, accepted
+                1 => stream.write_all(&[0x20, 2, 0, 0]).unwrap(),
+                // PUBLISH at QoS 0, a length prefixed topic then the payload
+                3 => {
+                    let len = u16::from_be_bytes([body[0], body[1]]) as usize;
+                    let topic = String::from_utf8(body[2..2 + len].to_vec()).unwrap();
+                    published.push((topic, body[2 + len..].to_vec()));
+                }
+                // PINGREQ
+                12 => stream.write_all(&[0xd0, 0]).unwrap(),
+                // DISCONNECT
+                14 => break,
+                _ => {}
+            }
+        }
+        published
+    }
+
+    #[test]
+    fn each_line_is_published_on_the_topic() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let broker = thread::spawn(move || broker(listener));
+
+        let mut publisher = Publisher::connect(&address, "lab/sensor", 0).unwrap();
+        publisher.write_all(b"t=21.5\r\nt=2").unwrap();
+        publisher.write_all(b"1.6\nlast").unwrap();
+        drop(publisher);
+
+        let topic = String::from("lab/sensor");
+        assert_eq!(
+            broker.join().unwrap(),
+            [
+                (topic.clone(), b"t=21.5".to_vec()),
+                (topic.clone(), b"t=21.6".to_vec()),
+                (topic, b"last".to_vec()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,249 @@
+use std::io::{self, Read, Write};
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+use crate::label::Labeled;
+
+/// Colors the port labels take in turn: green, yellow, blue, magenta, cyan, red
+const COLORS: [u8; 6] = [32, 33, 34, 35, 36, 31];
+
+/// The `(label, port)` pairs of a `--ports` list, each entry is PORT or LABEL=PORT
+/// and is labeled with the port's name unless it says otherwise
+pub fn parse(list: &str) -> Vec<(String, String)> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match entry.split_once('=') {
+            Some((label, port)) => (label.to_string(), port.to_string()),
+            None => (entry.to_string(), entry.to_string()),
+        })
+        .collect()
+}
+
+/// The label for the `index`th port, colored when `color` is set
+pub fn label(name: &str, index: usize, color: bool) -> String {
+    if color {
+        format!("\x1b[{}m{}\x1b[0m", COLORS[index % COLORS.len()], name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Turns what one port sends into whole lines started with its label,
+/// so lines from different ports can be written out without running into each other
+pub struct Tagger {
+    labeled: Labeled<Vec<u8>>,
+    carry: Vec<u8>,
+}
+
+impl Tagger {
+    pub fn new(label: &str) -> Self {
+        Tagger {
+            labeled: Labeled::new(Vec::new(), Some(label)),
+            carry: Vec::new(),
+        }
+    }
+
+    /// The labeled lines that `data` completes, a partial line is kept back for the next call
+    pub fn feed(&mut self, data: &[u8]) -> Vec<u8> {
+        self.carry.extend_from_slice(data);
+        match self.carry.iter().rposition(|&b| b == b'\n') {
+            Some(end) => {
+                let lines: Vec<u8> = self.carry.drain(..=end).collect();
+                self.labeled.write_all(&lines).unwrap();
+                std::mem::take(self.labeled.get_mut())
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// The partial line still held back, ended with a newline, for when the port is done
+    pub fn finish(&mut self) -> Vec<u8> {
+        if self.carry.is_empty() {
+            return Vec::new();
+        }
+        self.carry.push(b'\n');
+        self.feed(&[])
+    }
+}
+
+/// How one port's reading ended
+pub struct Ended {
+    pub name: String,
+    pub bytes: usize,
+    pub error: Option<io::Error>,
+}
+
+/// Read every port on its own thread and write their labeled lines to `out` as they complete
+/// A port that fails is reported and dropped while the others carry on,
+/// this returns once every port has ended or Ctrl-C is pressed
+pub fn run<P: Read + Send, W: Write + Send>(
+    ports: Vec<(String, P)>,
+    out: W,
+    color: bool,
+    read_size: usize,
+) -> Vec<Ended> {
+    let out = Mutex::new(out);
+    std::thread::scope(|scope| {
+        let threads: Vec<_> = ports
+            .into_iter()
+            .enumerate()
+            .map(|(index, (name, port))| {
+                let out = &out;
+                scope.spawn(move || {
+                    let tagger = Tagger::new(&label(&name, index, color));
+                    let (bytes, error) = read_port(port, tagger, out, read_size, &name);
+                    Ended { name, bytes, error }
+                })
+            })
+            .collect();
+        threads
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .collect()
+    })
+}
+
+fn read_port<P: Read, W: Write>(
+    mut port: P,
+    mut tagger: Tagger,
+    out: &Mutex<W>,
+    read_size: usize,
+    name: &str,
+) -> (usize, Option<io::Error>) {
+    let mut buf = vec![0; read_size];
+    let mut bytes = 0;
+    let write = |lines: &[u8]| -> io::Result<()> {
+        if lines.is_empty() {
+            return Ok(());
+        }
+        let mut out = out.lock().unwrap();
+        out.write_all(lines)?;
+        out.flush()
+    };
+    let error = loop {
+        if crate::STOP.load(Ordering::Relaxed) {
+            break None;
+        }
+        let n = match port.read(&mut buf) {
+            Ok(0) => break None,
+            Ok(n) => n,
+            Err(ref e) if crate::severity(e) == crate::Severity::Recoverable => continue,
+            Err(e) => break Some(e),
+        };
+        bytes += n;
+        if let Err(e) = write(&tagger.feed(&buf[..n])) {
+            break Some(e);
+        }
+    };
+    if let Some(e) = &error {
+        eprintln!("{} stopped: {}", name, e);
+    }
+    // A line cut short is still worth seeing
+    let _ = write(&tagger.finish());
+    (bytes, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hands out its data a few bytes at a time, then fails if it was given an error
+    struct Chunked {
+        data: Vec<u8>,
+        chunk: usize,
+        error: Option<io::ErrorKind>,
+    }
+
+    impl Read for Chunked {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.data.is_empty() {
+                return match self.error.take() {
+                    Some(kind) => Err(io::Error::new(kind, "unplugged")),
+                    None => Ok(0),
+                };
+            }
+            // Give the other thread a chance to run in between
+            std::thread::yield_now();
+            let n = self.chunk.min(buf.len()).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data.drain(..n);
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn entries_are_ports_with_optional_labels() {
+        assert_eq!(
+            parse("/dev/ttyUSB0, gps=/dev/ttyACM0,,"),
+            [
+                (String::from("/dev/ttyUSB0"), String::from("/dev/ttyUSB0")),
+                (String::from("gps"), String::from("/dev/ttyACM0")),
+            ]
+        );
+        assert_eq!(label("gps", 1, true), "\x1b[33mgps\x1b[0m");
+        assert_eq!(label("gps", 7, true), "\x1b[33mgps\x1b[0m");
+        assert_eq!(label("gps", 1, false), "gps");
+    }
+
+    #[test]
+    fn the_tagger_only_passes_on_whole_lines() {
+        let mut tagger = Tagger::new("a");
+        assert_eq!(tagger.feed(b"one\ntw"), b"[a] one\n");
+        assert_eq!(tagger.feed(b"o"), b"");
+        assert_eq!(tagger.feed(b"\nthree\nfo"), b"[a] two\n[a] three\n");
+        assert_eq!(tagger.finish(), b"[a] fo\n");
+        assert_eq!(tagger.finish(), b"");
+    }
+
+    #[test]
+    fn lines_of_two_ports_are_merged_whole_with_their_labels() {
+        let lines = |prefix: &str| -> Vec<u8> {
+            (0..50)
+                .flat_map(|i| format!("{} line {}\n", prefix, i).into_bytes())
+                .collect()
+        };
+        let ports = vec![
+            (
+                String::from("left"),
+                Chunked {
+                    data: lines("L"),
+                    chunk: 3,
+                    error: None,
+                },
+            ),
+            (
+                String::from("right"),
+                Chunked {
+                    data: lines("R"),
+                    chunk: 5,
+                    error: Some(io::ErrorKind::NotConnected),
+                },
+            ),
+        ];
+        let mut out = Vec::new();
+        let ended = run(ports, &mut out, false, 64);
+        assert_eq!(ended[0].bytes, lines("L").len());
+        assert!(ended[0].error.is_none());
+        // The port that failed is reported without stopping the other
+        assert_eq!(ended[1].bytes, lines("R").len());
+        assert_eq!(
+            ended[1].error.as_ref().unwrap().kind(),
+            io::ErrorKind::NotConnected
+        );
+
+        let out = String::from_utf8(out).unwrap();
+        let (mut left, mut right) = (0, 0);
+        for line in out.lines() {
+            if let Some(rest) = line.strip_prefix("[left] ") {
+                assert_eq!(rest, format!("L line {}", left));
+                left += 1;
+            } else {
+                let rest = line.strip_prefix("[right] ").unwrap();
+                assert_eq!(rest, format!("R line {}", right));
+                right += 1;
+            }
+        }
+        assert_eq!((left, right), (50, 50));
+    }
+}
@@ -0,0 +1,129 @@
+use std::time::{Duration, Instant};
+
+/// Drops lines or records that repeat the one before, lines and records can be split over any number of reads
+/// With a heartbeat an unchanged one is still let through once that long has passed since the last
+pub struct OnChange {
+    /// Bytes per record, or None to work on lines
+    width: Option<usize>,
+    heartbeat: Option<Duration>,
+    current: Vec<u8>,
+    last: Option<Vec<u8>>,
+    passed: Option<Instant>,
+    /// How many repeats were dropped
+    pub suppressed: usize,
+}
+
+impl OnChange {
+    /// Compare newline terminated lines
+    pub fn lines(heartbeat: Option<Duration>) -> Self {
+        OnChange::new(None, heartbeat)
+    }
+
+    /// Compare records that are `width` bytes wide
+    pub fn records(width: usize, heartbeat: Option<Duration>) -> Self {
+        OnChange::new(Some(width.max(1)), heartbeat)
+    }
+
+    fn new(width: Option<usize>, heartbeat: Option<Duration>) -> Self {
+        OnChange {
+            width,
+            heartbeat,
+            current: Vec::new(),
+            last: None,
+            passed: None,
+            suppressed: 0,
+        }
+    }
+
+    /// Append the lines or records of `data` that differ from the one before to `out`
+    /// A line or record is held back until it is complete
+    pub fn apply(&mut self, data: &[u8], now: Instant, out: &mut Vec<u8>) {
+        for &b in data {
+            self.current.push(b);
+            let complete = match self.width {
+                Some(width) => self.current.len() == width,
+                None => b == b'\n',
+            };
+            if complete {
+                self.finish(now, out);
+            }
+        }
+    }
+
+    fn finish(&mut self, now: Instant, out: &mut Vec<u8>) {
+        let due = match (self.heartbeat, self.passed) {
+            (Some(interval), Some(passed)) => now - passed >= interval,
+            _ => false,
+        };
+        if due || self.last.as_ref() != Some(&self.current) {
+            out.extend_from_slice(&self.current);
+            self.passed = Some(now);
+            self.last = Some(std::mem::take(&mut self.current));
+        } else {
+            self.suppressed += 1;
+            self.current.clear();
+        }
+    }
+
+    /// The unfinished last line, for when the stream ends, a partial record is dropped
+    pub fn rest(&mut self) -> Vec<u8> {
+        match self.width {
+            Some(_) => Vec::new(),
+            None => std::mem::take(&mut self.current),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn only_changed_lines_are_let_through() {
+        let now = Instant::now();
+        let mut filter = OnChange::lines(None);
+        let mut out = Vec::new();
+        filter.apply(b"t=20\nt=20\nt=2", now, &mut out);
+        filter.apply(b"0\nt=21\nt=21\nt=20\nt=2", now, &mut out);
+        assert_eq!(out, b"t=20\nt=21\nt=20\n");
+        assert_eq!(filter.suppressed, 3);
+        assert_eq!(filter.rest(), b"t=2");
+    }
+
+    #[test]
+    fn an_unchanged_line_still_comes_through_as_a_heartbeat() {
+        let start = Instant::now();
+        let mut filter = OnChange::lines(Some(ms(1000)));
+        let mut out = Vec::new();
+        for (at, line) in [
+            (0, "a\n"),
+            (400, "a\n"),
+            (900, "a\n"),
+            (1000, "a\n"),
+            (1500, "b\n"),
+            (1900, "b\n"),
+            (2600, "b\n"),
+        ] {
+            filter.apply(line.as_bytes(), start + ms(at), &mut out);
+        }
+        assert_eq!(out, b"a\na\nb\nb\n");
+        assert_eq!(filter.suppressed, 3);
+    }
+
+    #[test]
+    fn records_are_compared_whole() {
+        let now = Instant::now();
+        let mut filter = OnChange::records(3, None);
+        let mut out = Vec::new();
+        filter.apply(&[1, 2, 3, 1, 2], now, &mut out);
+        filter.apply(&[3, 1, 2, 4, 9], now, &mut out);
+        assert_eq!(out, [1, 2, 3, 1, 2, 4]);
+        assert_eq!(filter.suppressed, 1);
+        // A partial record is dropped at the end
+        assert_eq!(filter.rest(), b"");
+    }
+}
@@ -0,0 +1,113 @@
+/// An option listed in the help text, with the placeholder for its value if it takes one
+#[derive(Debug, PartialEq, Eq)]
+pub struct Known<'a> {
+    pub name: &'a str,
+    pub value: Option<&'a str>,
+}
+
+/// A placeholder for an option's value in the help, such as NUMBER, PATH or KEY=STRING
+fn is_placeholder(word: &str) -> bool {
+    word.chars().any(|c| c.is_ascii_uppercase()) && !word.chars().any(|c| c.is_ascii_lowercase())
+}
+
+/// The short options the help text lists with their long names, ("-b", "--baud-rate")
+fn short_names(help: &str) -> Vec<(&str, &str)> {
+    let mut names = Vec::new();
+    for line in help.lines() {
+        let mut words = line.split_whitespace();
+        if let (Some(short), Some(long)) = (words.next(), words.next()) {
+            let short = short.trim_end_matches(',');
+            if short.len() == 2 && short.starts_with('-') && long.starts_with("--") {
+                names.push((short, long));
+            }
+        }
+    }
+    names
+}
+
+/// `args` with their short options written long, `-b 9600` as `--baud-rate 9600`
+/// so they can be matched against the options of the environment and profiles
+pub fn long_forms(args: Vec<String>, help: &str) -> Vec<String> {
+    let names = short_names(help);
+    args.into_iter()
+        .map(|arg| {
+            let (key, value) = match arg.split_once('=') {
+                Some((key, value)) => (key, Some(value)),
+                None => (arg.as_str(), None),
+            };
+            match names.iter().find(|(short, _)| *short == key) {
+                Some((_, long)) => match value {
+                    Some(value) => format!("{}={}", long, value),
+                    None => long.to_string(),
+                },
+                None => arg,
+            }
+        })
+        .collect()
+}
+
+/// The long options the help text lists at the start of its lines
+pub fn known(help: &str) -> Vec<Known<'_>> {
+    let mut options = Vec::new();
+    for line in help.lines() {
+        let mut words = line
+            .split_whitespace()
+            .skip_while(|w| w.len() <= 3 && w.starts_with('-') && !w.starts_with("--"));
+        if let Some(name) = words.next().filter(|w| w.starts_with("--")) {
+            options.push(Known {
+                name,
+                value: words.next().filter(|w| is_placeholder(w)),
+            });
+        }
+    }
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELP: &str = "
+	-b --baud-rate    NUMBER     Baud rate
+	-q, --quiet                  Quiet
+	    --log         PATH       Log
+";
+
+    #[test]
+    fn options_are_read_from_the_help() {
+        assert_eq!(
+            known(HELP),
+            [
+                Known {
+                    name: "--baud-rate",
+                    value: Some("NUMBER")
+                },
+                Known {
+                    name: "--quiet",
+                    value: None
+                },
+                Known {
+                    name: "--log",
+                    value: Some("PATH")
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn short_options_are_written_long() {
+        let args = ["-b", "9600", "-q", "-b=300", "--log", "-x", "-40"];
+        assert_eq!(
+            long_forms(args.iter().map(|s| s.to_string()).collect(), HELP),
+            [
+                "--baud-rate",
+                "9600",
+                "--quiet",
+                "--baud-rate=300",
+                "--log",
+                "-x",
+                "-40"
+            ]
+        );
+    }
+}
@@ -0,0 +1,62 @@
+/// Bytes the kernel holds for a tty before it starts dropping them (N_TTY_BUF_SIZE on Linux)
+pub const OS_BUFFER: u32 = 4096;
+
+/// Share of the OS buffer that counts as close to overrunning
+const THRESHOLD: f64 = 0.75;
+
+/// Watches how many bytes are waiting in the OS input buffer for signs we aren't keeping up
+/// A stretch of samples over the threshold counts as one event
+pub struct Overrun {
+    limit: u32,
+    near: bool,
+    pub events: u64,
+    pub peak: u32,
+}
+
+impl Overrun {
+    pub fn new(limit: u32) -> Self {
+        Overrun {
+            limit,
+            near: false,
+            events: 0,
+            peak: 0,
+        }
+    }
+
+    /// The fill level of the buffer was `waiting` bytes, returns true when it has just come close to full
+    pub fn sample(&mut self, waiting: u32) -> bool {
+        self.peak = self.peak.max(waiting);
+        let near = f64::from(waiting) >= f64::from(self.limit) * THRESHOLD;
+        let started = near && !self.near;
+        self.near = near;
+        self.events += started as u64;
+        started
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_stretch_over_the_threshold_is_one_event() {
+        let mut overrun = Overrun::new(OS_BUFFER);
+        let started: Vec<bool> = [0, 1000, 3071, 3072, 4000, 3500, 100, 3900]
+            .iter()
+            .map(|&waiting| overrun.sample(waiting))
+            .collect();
+        assert_eq!(
+            started,
+            [false, false, false, true, false, false, false, true]
+        );
+        assert_eq!(overrun.events, 2);
+        assert_eq!(overrun.peak, 4000);
+    }
+
+    #[test]
+    fn an_empty_buffer_is_never_near() {
+        let mut overrun = Overrun::new(OS_BUFFER);
+        assert!(!overrun.sample(0));
+        assert_eq!((overrun.events, overrun.peak), (0, 0));
+    }
+}
@@ -0,0 +1,264 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Split pasted text into its lines, whatever line endings it uses, and whatever follows the last one
+pub fn split(text: &str) -> (Vec<String>, String) {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let mut lines: Vec<String> = text.split('\n').map(String::from).collect();
+    let rest = lines.pop().unwrap_or_default();
+    (lines, rest)
+}
+
+/// Split pasted bytes after each line ending, which is kept as it was, `\r\n` staying in one piece
+pub fn raw_lines(bytes: &[u8]) -> Vec<&[u8]> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for (i, &b) in bytes.iter().enumerate() {
+        let ends = match b {
+            b'\n' => true,
+            b'\r' => bytes.get(i + 1) != Some(&b'\n'),
+            _ => false,
+        };
+        if ends {
+            lines.push(&bytes[start..=i]);
+            start = i + 1;
+        }
+    }
+    if start < bytes.len() {
+        lines.push(&bytes[start..]);
+    }
+    lines
+}
+
+/// What the terminal marks the start and the end of a bracketed paste with
+const START: &[u8] = b"\x1b[200~";
+const END: &[u8] = b"\x1b[201~";
+
+/// Part of what was read from a terminal in bracketed paste mode
+#[derive(Debug, PartialEq, Eq)]
+pub enum Typed {
+    Keys(Vec<u8>),
+    Paste(Vec<u8>),
+}
+
+/// Takes the bracketed pastes out of raw terminal input, for when there are no paste events to read
+#[derive(Default)]
+pub struct Brackets {
+    /// The paste so far, once its start has been seen
+    pasting: Option<Vec<u8>>,
+}
+
+impl Brackets {
+    /// Split one read of the terminal into typed keys and whole pastes
+    /// A paste that hasn't ended is kept for the next read, but keys that only look like
+    /// the start of one are passed on, so a lone Esc isn't held back until the next key
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<Typed> {
+        let mut typed = Vec::new();
+        let mut keys = Vec::new();
+        let mut rest = bytes;
+        while !rest.is_empty() {
+            if let Some(paste) = &mut self.pasting {
+                let before = paste.len();
+                paste.extend_from_slice(rest);
+                // The end may have been cut off by the read before
+                let from = before.saturating_sub(END.len() - 1);
+                match find(&paste[from..], END) {
+                    Some(at) => {
+                        let end = from + at;
+                        rest = &rest[end + END.len() - before..];
+                        paste.truncate(end);
+                        typed.push(Typed::Paste(self.pasting.take().unwrap()));
+                    }
+                    None => rest = &[],
+                }
+                continue;
+            }
+            match find(rest, START) {
+                Some(at) => {
+                    keys.extend_from_slice(&rest[..at]);
+                    if !keys.is_empty() {
+                        typed.push(Typed::Keys(std::mem::take(&mut keys)));
+                    }
+                    self.pasting = Some(Vec::new());
+                    rest = &rest[at + START.len()..];
+                }
+                None => {
+                    keys.extend_from_slice(rest);
+                    rest = &[];
+                }
+            }
+        }
+        if !keys.is_empty() {
+            typed.push(Typed::Keys(keys));
+        }
+        typed
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Pasted lines waiting to be sent, one every `delay` so the device keeps up
+/// Each is the bytes to send and how it looks when echoed
+pub struct Paced {
+    queued: VecDeque<(Vec<u8>, String)>,
+    /// Whether the last queued entry is typed keys rather than a pasted line
+    typed_last: bool,
+    delay: Duration,
+    next: Instant,
+}
+
+impl Paced {
+    pub fn new(delay: Duration, now: Instant) -> Self {
+        Paced {
+            queued: VecDeque::new(),
+            typed_last: false,
+            delay,
+            next: now,
+        }
+    }
+
+    pub fn push(&mut self, bytes: Vec<u8>, shown: String) {
+        self.queued.push_back((bytes, shown));
+        self.typed_last = false;
+    }
+
+    /// Queue what was typed while a paste is being sent, together with the keys typed before it
+    /// so they all go out at once after the paste instead of a key every delay
+    pub fn type_ahead(&mut self, bytes: &[u8], shown: &str) {
+        match self.queued.back_mut() {
+            Some((queued, queued_shown)) if self.typed_last => {
+                queued.extend_from_slice(bytes);
+                queued_shown.push_str(shown);
+            }
+            _ => {
+                self.queued.push_back((bytes.to_vec(), shown.to_string()));
+                self.typed_last = true;
+            }
+        }
+    }
+
+    /// The next line to send at `now`, once the delay after the one before has passed
+    pub fn next(&mut self, now: Instant) -> Option<(Vec<u8>, String)> {
+        if now < self.next {
+            return None;
+        }
+        let line = self.queued.pop_front()?;
+        self.next = now + self.delay;
+        Some(line)
+    }
+
+    /// Lines still to send
+    pub fn len(&self) -> usize {
+        self.queued.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queued.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_paste_is_split_into_its_lines_and_what_follows() {
+        let (lines, rest) = split("AT\r\nATI\rATZ\nAT+");
+        assert_eq!(lines, ["AT", "ATI", "ATZ"]);
+        assert_eq!(rest, "AT+");
+        let (lines, rest) = split("one line\n");
+        assert_eq!(lines, ["one line"]);
+        assert_eq!(rest, "");
+        assert_eq!(split(""), (vec![], String::new()));
+    }
+
+    #[test]
+    fn raw_lines_keep_their_endings() {
+        assert_eq!(
+            raw_lines(b"AT\r\nATI\rATZ\nAT+"),
+            [&b"AT\r\n"[..], b"ATI\r", b"ATZ\n", b"AT+"]
+        );
+        assert!(raw_lines(b"").is_empty());
+    }
+
+    #[test]
+    fn pasted_lines_are_sent_one_every_delay() {
+        let start = Instant::now();
+        let delay = Duration::from_millis(100);
+        let mut paced = Paced::new(delay, start);
+        for line in ["one", "two", "three"] {
+            paced.push(line.as_bytes().to_vec(), String::from(line));
+        }
+        assert_eq!(paced.len(), 3);
+        assert_eq!(paced.next(start).unwrap().0, b"one");
+        assert_eq!(paced.next(start), None);
+        assert_eq!(paced.next(start + delay / 2), None);
+        assert_eq!(paced.next(start + delay).unwrap().0, b"two");
+        assert_eq!(paced.next(start + 2 * delay).unwrap().1, "three");
+        assert!(paced.is_empty());
+        assert_eq!(paced.next(start + 10 * delay), None);
+    }
+
+    #[test]
+    fn keys_typed_during_a_paste_go_out_together_after_it() {
+        let start = Instant::now();
+        let delay = Duration::from_millis(100);
+        let mut paced = Paced::new(delay, start);
+        paced.push(b"one\r".to_vec(), String::from("one\n"));
+        for key in ["A", "T", "Z"] {
+            paced.type_ahead(key.as_bytes(), key);
+        }
+        paced.push(b"two\r".to_vec(), String::from("two\n"));
+        paced.type_ahead(b"\r", "\n");
+        assert_eq!(paced.len(), 4);
+        let sent: Vec<_> = (0..4)
+            .map(|step| paced.next(start + delay * step).unwrap())
+            .collect();
+        assert_eq!(
+            sent,
+            [
+                (b"one\r".to_vec(), String::from("one\n")),
+                (b"ATZ".to_vec(), String::from("ATZ")),
+                (b"two\r".to_vec(), String::from("two\n")),
+                (b"\r".to_vec(), String::from("\n")),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_bracketed_paste_is_taken_out_of_the_keys() {
+        let mut brackets = Brackets::default();
+        assert_eq!(
+            brackets.feed(b"ls\x1b[200~AT\rATI\r\x1b[201~\x1b[A"),
+            [
+                Typed::Keys(b"ls".to_vec()),
+                Typed::Paste(b"AT\rATI\r".to_vec()),
+                Typed::Keys(b"\x1b[A".to_vec()),
+            ]
+        );
+        assert_eq!(brackets.feed(b"\x1b"), [Typed::Keys(b"\x1b".to_vec())]);
+    }
+
+    #[test]
+    fn a_paste_over_several_reads_is_put_back_together() {
+        let mut brackets = Brackets::default();
+        assert!(brackets.feed(b"\x1b[200~one\r").is_empty());
+        // The end marker cut in two
+        assert!(brackets.feed(b"two\r\x1b[2").is_empty());
+        assert_eq!(
+            brackets.feed(b"01~x"),
+            [
+                Typed::Paste(b"one\rtwo\r".to_vec()),
+                Typed::Keys(b"x".to_vec()),
+            ]
+        );
+        assert_eq!(
+            brackets.feed(b"\x1b[200~a\x1b[201~\x1b[200~b\x1b[201~"),
+            [Typed::Paste(b"a".to_vec()), Typed::Paste(b"b".to_vec())]
+        );
+    }
+}
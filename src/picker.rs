@@ -0,0 +1,265 @@
+use serialport::{SerialPortInfo, SerialPortType};
+
+/// One line about a port, with what the USB device says it is
+pub fn describe(port: &SerialPortInfo) -> String {
+    match &port.port_type {
+        SerialPortType::UsbPort(usb) => {
+            let name: Vec<_> = [&usb.manufacturer, &usb.product]
+                .iter()
+                .filter_map(|s| s.as_deref())
+                .collect();
+            let mut line = format!("{} (USB {:04x}:{:04x}", port.port_name, usb.vid, usb.pid);
+            if !name.is_empty() {
+                line.push_str(&format!(" {}", name.join(" ")));
+            }
+            if let Some(serial) = &usb.serial_number {
+                line.push_str(&format!(" serial {}", serial));
+            }
+            line.push(')');
+            line
+        }
+        SerialPortType::PciPort => format!("{} (PCI)", port.port_name),
+        SerialPortType::BluetoothPort => format!("{} (Bluetooth)", port.port_name),
+        SerialPortType::Unknown => port.port_name.clone(),
+    }
+}
+
+/// Whether a port's USB descriptor contains each of the given strings, ignoring case
+/// Ports that aren't USB have no descriptor and never match
+pub fn matches(port: &SerialPortInfo, product: Option<&str>, manufacturer: Option<&str>) -> bool {
+    let usb = match &port.port_type {
+        SerialPortType::UsbPort(usb) => usb,
+        _ => return false,
+    };
+    let contains = |field: &Option<String>, wanted: Option<&str>| match wanted {
+        Some(wanted) => field
+            .as_ref()
+            .is_some_and(|field| field.to_lowercase().contains(&wanted.to_lowercase())),
+        None => true,
+    };
+    contains(&usb.product, product) && contains(&usb.manufacturer, manufacturer)
+}
+
+/// The numbered menu lines, with a marker on the selected one
+#[cfg(feature = "tui")]
+pub fn render(ports: &[SerialPortInfo], selected: usize) -> Vec<String> {
+    ports
+        .iter()
+        .enumerate()
+        .map(|(i, port)| {
+            let marker = if i == selected { '>' } else { ' ' };
+            format!("{} {}) {}", marker, i + 1, describe(port))
+        })
+        .collect()
+}
+
+/// What a key press does to the menu
+#[cfg(feature = "tui")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Choice {
+    Move(usize),
+    Pick(usize),
+    Cancel,
+}
+
+#[cfg(feature = "tui")]
+pub fn choose(selected: usize, count: usize, key: crossterm::event::KeyCode) -> Option<Choice> {
+    use crossterm::event::KeyCode;
+    Some(match key {
+        KeyCode::Up => Choice::Move(selected.checked_sub(1).unwrap_or(count - 1)),
+        KeyCode::Down => Choice::Move((selected + 1) % count),
+        KeyCode::Enter => Choice::Pick(selected),
+        KeyCode::Esc | KeyCode::Char('q') => Choice::Cancel,
+        KeyCode::Char(c) => {
+            let n = c.to_digit(10)? as usize;
+            (1..=count).contains(&n).then(|| Choice::Pick(n - 1))?
+        }
+        _ => return None,
+    })
+}
+
+/// Let the user pick a port with the arrow keys or its number, None if they gave up
+#[cfg(feature = "tui")]
+pub fn pick(ports: &[SerialPortInfo]) -> std::io::Result<Option<String>> {
+    use std::io::{self, Write};
+
+    use crossterm::event::{self, Event, KeyEventKind, KeyModifiers};
+    use crossterm::terminal::{Clear, ClearType};
+    use crossterm::{cursor, queue};
+
+    use crate::terminal::TerminalGuard;
+
+    let mut term = io::stdout();
+    println!("Pick a port (arrow keys and Enter, or its number, q to quit):");
+    let guard = TerminalGuard::raw()?;
+    let mut selected = 0;
+    let result = loop {
+        for line in render(ports, selected) {
+            queue!(term, Clear(ClearType::CurrentLine))?;
+            write!(term, "{}\r\n", line)?;
+        }
+        term.flush()?;
+        let key = match event::read() {
+            Ok(Event::Key(key)) if key.kind != KeyEventKind::Release => key,
+            Ok(_) => continue,
+            Err(e) => break Err(e),
+        };
+        if key.code == event::KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            break Ok(None);
+        }
+        match choose(selected, ports.len(), key.code) {
+            Some(Choice::Move(n)) => selected = n,
+            Some(Choice::Pick(n)) => break Ok(Some(ports[n].port_name.clone())),
+            Some(Choice::Cancel) => break Ok(None),
+            None => {}
+        }
+        queue!(term, cursor::MoveUp(ports.len() as u16))?;
+    };
+    drop(guard);
+    result
+}
+
+/// List the ports for when there is no terminal to pick from
+pub fn list(ports: &[SerialPortInfo]) {
+    println!("Found ports:");
+    for port in ports {
+        println!("  {}", describe(port));
+    }
+}
+
+/// The ports to pick from out of what listing them `found`, or why there is nothing to pick
+/// Failing to list the ports is told apart from there being none
+pub fn available(
+    found: serialport::Result<Vec<SerialPortInfo>>,
+) -> Result<Vec<SerialPortInfo>, String> {
+    match found {
+        Ok(ports) if ports.is_empty() => Err(String::from(
+            "No serial ports detected, plug one in or give it with --port",
+        )),
+        Ok(ports) => Ok(ports),
+        Err(e) => Err(format!(
+            "Could not list the serial ports ({}), give the one to use with --port",
+            e
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serialport::UsbPortInfo;
+
+    fn usb(name: &str, manufacturer: Option<&str>, product: Option<&str>) -> SerialPortInfo {
+        SerialPortInfo {
+            port_name: String::from(name),
+            port_type: SerialPortType::UsbPort(UsbPortInfo {
+                vid: 0x10c4,
+                pid: 0xea60,
+                serial_number: Some(String::from("0001")),
+                manufacturer: manufacturer.map(String::from),
+                product: product.map(String::from),
+            }),
+        }
+    }
+
+    fn ports() -> Vec<SerialPortInfo> {
+        vec![
+            usb(
+                "/dev/ttyUSB0",
+                Some("Silicon Labs"),
+                Some("CP2102 USB to UART"),
+            ),
+            SerialPortInfo {
+                port_name: String::from("/dev/ttyS0"),
+                port_type: SerialPortType::PciPort,
+            },
+            SerialPortInfo {
+                port_name: String::from("/dev/ttyACM0"),
+                port_type: SerialPortType::Unknown,
+            },
+        ]
+    }
+
+    #[test]
+    fn ports_are_described_with_their_usb_details() {
+        let ports = ports();
+        assert_eq!(
+            describe(&ports[0]),
+            "/dev/ttyUSB0 (USB 10c4:ea60 Silicon Labs CP2102 USB to UART serial 0001)"
+        );
+        assert_eq!(describe(&ports[1]), "/dev/ttyS0 (PCI)");
+        assert_eq!(describe(&ports[2]), "/dev/ttyACM0");
+        assert_eq!(
+            describe(&usb("/dev/ttyUSB1", None, None)),
+            "/dev/ttyUSB1 (USB 10c4:ea60 serial 0001)"
+        );
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn the_menu_marks_the_selected_port() {
+        assert_eq!(
+            render(&ports(), 1),
+            [
+                "  1) /dev/ttyUSB0 (USB 10c4:ea60 Silicon Labs CP2102 USB to UART serial 0001)",
+                "> 2) /dev/ttyS0 (PCI)",
+                "  3) /dev/ttyACM0",
+            ]
+        );
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn keys_move_pick_or_cancel() {
+        use crossterm::event::KeyCode;
+        assert_eq!(choose(0, 3, KeyCode::Up), Some(Choice::Move(2)));
+        assert_eq!(choose(2, 3, KeyCode::Down), Some(Choice::Move(0)));
+        assert_eq!(choose(1, 3, KeyCode::Enter), Some(Choice::Pick(1)));
+        assert_eq!(choose(0, 3, KeyCode::Char('3')), Some(Choice::Pick(2)));
+        assert_eq!(choose(0, 3, KeyCode::Char('4')), None);
+        assert_eq!(choose(0, 3, KeyCode::Char('0')), None);
+        assert_eq!(choose(0, 3, KeyCode::Char('q')), Some(Choice::Cancel));
+        assert_eq!(choose(0, 3, KeyCode::Esc), Some(Choice::Cancel));
+        assert_eq!(choose(0, 3, KeyCode::Tab), None);
+    }
+
+    #[test]
+    fn usb_descriptors_match_substrings_ignoring_case() {
+        let ports = ports();
+        assert!(matches(&ports[0], Some("cp210"), None));
+        assert!(matches(&ports[0], None, Some("silicon")));
+        assert!(matches(&ports[0], Some("UART"), Some("LABS")));
+        assert!(matches(&ports[0], None, None));
+        assert!(!matches(&ports[0], Some("ch340"), None));
+        assert!(!matches(&ports[0], Some("cp2102"), Some("FTDI")));
+        // A missing field never contains anything
+        assert!(!matches(&usb("/dev/ttyUSB1", None, None), Some("cp"), None));
+    }
+
+    #[test]
+    fn ports_that_are_not_usb_never_match() {
+        let ports = ports();
+        for port in &ports[1..] {
+            assert!(!matches(port, None, None));
+            assert!(!matches(port, Some("tty"), None));
+        }
+    }
+
+    #[test]
+    fn failing_to_list_the_ports_is_told_apart_from_having_none() {
+        let failed = serialport::Error::new(serialport::ErrorKind::Unknown, "udev is missing");
+        assert_eq!(
+            available(Err(failed)),
+            Err(String::from(
+                "Could not list the serial ports (udev is missing), give the one to use with --port"
+            ))
+        );
+        assert_eq!(
+            available(Ok(Vec::new())),
+            Err(String::from(
+                "No serial ports detected, plug one in or give it with --port"
+            ))
+        );
+        assert_eq!(available(Ok(ports())), Ok(ports()));
+    }
+}
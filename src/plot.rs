@@ -0,0 +1,87 @@
+use std::io::{self, Write};
+use std::time::Instant;
+
+use crate::{ConvertFrom, Encoder};
+
+/// Writes exactly one decoded value per line for piping into a plotter such as `feedgnuplot --stream`
+/// The value can be preceded by its index and/or the seconds since `start`
+pub struct PlotWriter {
+    convert: ConvertFrom,
+    index: Option<u64>,
+    start: Option<Instant>,
+    carry: Vec<u8>,
+}
+
+impl PlotWriter {
+    pub fn new(convert: ConvertFrom, index: bool, start: Option<Instant>) -> Self {
+        PlotWriter {
+            convert,
+            index: index.then_some(0),
+            start,
+            carry: Vec::new(),
+        }
+    }
+}
+
+impl Encoder for PlotWriter {
+    fn write(&mut self, data: &[u8], out: &mut dyn Write) -> io::Result<()> {
+        self.carry.extend_from_slice(data);
+
+        let mut values = self.carry.chunks_exact(self.convert.width());
+        for value in &mut values {
+            if let Some(index) = &mut self.index {
+                write!(out, "{} ", index)?;
+                *index += 1;
+            }
+            if let Some(start) = self.start {
+                write!(out, "{:.6} ", start.elapsed().as_secs_f64())?;
+            }
+            self.convert.write_value(value, out)?;
+            out.write_all(b"\n")?;
+        }
+
+        let used = self.carry.len() - values.remainder().len();
+        self.carry.drain(..used);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn floats(values: &[f32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn one_float_per_line() {
+        let data = floats(&[1.5, -2.25, 0.0, 100.0]);
+        let mut plot = PlotWriter::new(ConvertFrom::FLT, false, None);
+        let mut out = Vec::new();
+        // Split mid-value
+        plot.write(&data[..6], &mut out).unwrap();
+        plot.write(&data[6..], &mut out).unwrap();
+        assert_eq!(out, b"1.5\n-2.25\n0\n100\n");
+    }
+
+    #[test]
+    fn the_index_comes_first() {
+        let mut plot = PlotWriter::new(ConvertFrom::FLT, true, None);
+        let mut out = Vec::new();
+        plot.write(&floats(&[0.5, 0.25]), &mut out).unwrap();
+        plot.write(&floats(&[0.125]), &mut out).unwrap();
+        assert_eq!(out, b"0 0.5\n1 0.25\n2 0.125\n");
+    }
+
+    #[test]
+    fn the_time_is_seconds_since_the_start() {
+        let mut plot = PlotWriter::new(ConvertFrom::FLT, false, Some(Instant::now()));
+        let mut out = Vec::new();
+        plot.write(&floats(&[7.0]), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let (time, value) = out.trim_end().split_once(' ').unwrap();
+        assert!(time.parse::<f64>().unwrap() < 1.0, "{}", out);
+        assert_eq!(value, "7");
+    }
+}
@@ -0,0 +1,119 @@
+use serialport::{DataBits, Parity, SerialPort, StopBits};
+
+/// A `:` command typed in the interactive modes to look at or change the port settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortCommand {
+    Baud(Option<u32>),
+    Parity(Option<Parity>),
+    DataBits(Option<DataBits>),
+    StopBits(Option<StopBits>),
+}
+
+impl PortCommand {
+    /// Parse a command such as `baud 57600`, without a value the command asks for the current one
+    /// The leading `:` is optional
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim();
+        let line = line.strip_prefix(':').unwrap_or(line);
+        let mut words = line.split_whitespace();
+        let name = words.next().unwrap_or("");
+        let value = words.next();
+        if words.next().is_some() {
+            return Err(format!("\":{}\" takes at most one value", name));
+        }
+        Ok(match name.to_ascii_lowercase().as_str() {
+            "baud" => PortCommand::Baud(
+                value
+                    .map(|v| match v.parse() {
+                        Ok(rate) if rate > 0 => Ok(rate),
+                        _ => Err(format!("\"{}\" is not a baud rate", v)),
+                    })
+                    .transpose()?,
+            ),
+            "parity" => PortCommand::Parity(value.map(crate::parse_parity).transpose()?),
+            "databits" => PortCommand::DataBits(value.map(crate::parse_data_bits).transpose()?),
+            "stopbits" => PortCommand::StopBits(value.map(crate::parse_stop_bits).transpose()?),
+            "" => return Err("Commands are :baud, :parity, :databits and :stopbits".to_string()),
+            _ => {
+                return Err(format!(
+                    "Unknown command \":{}\", try :baud, :parity, :databits or :stopbits",
+                    name
+                ))
+            }
+        })
+    }
+
+    /// Carry out the command on the port, a command without a value changes nothing
+    pub fn apply(self, port: &mut dyn SerialPort) -> serialport::Result<()> {
+        match self {
+            PortCommand::Baud(Some(rate)) => port.set_baud_rate(rate),
+            PortCommand::Parity(Some(parity)) => port.set_parity(parity),
+            PortCommand::DataBits(Some(bits)) => port.set_data_bits(bits),
+            PortCommand::StopBits(Some(bits)) => port.set_stop_bits(bits),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The baud rate and framing the port has right now, for the status bar
+pub fn current(port: &dyn SerialPort) -> serialport::Result<(u32, String)> {
+    let framing = crate::framing(port.data_bits()?, port.parity()?, port.stop_bits()?);
+    Ok((port.baud_rate()?, framing))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockPort;
+
+    #[test]
+    fn commands_parse_into_their_settings() {
+        assert_eq!(
+            PortCommand::parse(":baud 57600"),
+            Ok(PortCommand::Baud(Some(57600)))
+        );
+        assert_eq!(PortCommand::parse("  BAUD  "), Ok(PortCommand::Baud(None)));
+        assert_eq!(
+            PortCommand::parse(":parity even"),
+            Ok(PortCommand::Parity(Some(Parity::Even)))
+        );
+        assert_eq!(
+            PortCommand::parse(":databits 7"),
+            Ok(PortCommand::DataBits(Some(DataBits::Seven)))
+        );
+        assert_eq!(
+            PortCommand::parse("stopbits 2"),
+            Ok(PortCommand::StopBits(Some(StopBits::Two)))
+        );
+    }
+
+    #[test]
+    fn bad_commands_and_values_are_explained() {
+        let error = |line| PortCommand::parse(line).unwrap_err();
+        assert_eq!(error(":baud 0"), "\"0\" is not a baud rate");
+        assert_eq!(error(":baud fast"), "\"fast\" is not a baud rate");
+        assert_eq!(error(":baud 9600 8N1"), "\":baud\" takes at most one value");
+        assert!(error(":parity sideways").contains("invalid value"));
+        assert!(error(":databits 9").contains("invalid value"));
+        assert!(error(":flow hw").starts_with("Unknown command \":flow\""));
+        assert!(error(":").starts_with("Commands are"));
+    }
+
+    #[test]
+    fn commands_call_the_matching_setter() {
+        let mut port = MockPort::default();
+        for line in [":baud 57600", ":parity odd", ":databits 7", ":stopbits 2"] {
+            PortCommand::parse(line).unwrap().apply(&mut port).unwrap();
+        }
+        {
+            let state = port.state();
+            assert_eq!(state.baud_rate, 57600);
+            assert_eq!(state.parity, Parity::Odd);
+            assert_eq!(state.data_bits, DataBits::Seven);
+            assert_eq!(state.stop_bits, StopBits::Two);
+        }
+        // Asking for the current settings changes nothing
+        PortCommand::Baud(None).apply(&mut port).unwrap();
+        assert_eq!(current(&port).unwrap(), (57600, String::from("7O2")));
+    }
+}
@@ -0,0 +1,134 @@
+use std::fs;
+use std::path::PathBuf;
+
+use directories::ProjectDirs;
+
+/// Where the profiles live, `~/.config/oxterm/profiles` on Linux
+pub fn dir() -> Result<PathBuf, String> {
+    ProjectDirs::from("", "", "oxterm")
+        .map(|dirs| dirs.config_dir().join("profiles"))
+        .ok_or_else(|| String::from("could not find a config directory"))
+}
+
+fn path(name: &str) -> Result<PathBuf, String> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(format!("\"{}\" can't be used as a profile name", name));
+    }
+    Ok(dir()?.join(name))
+}
+
+/// The arguments saved in a profile, one per line
+pub fn load(name: &str) -> Result<Vec<String>, String> {
+    let path = path(name)?;
+    let text = fs::read_to_string(&path).map_err(|e| {
+        format!(
+            "could not read profile {} ({}): {}",
+            name,
+            path.display(),
+            e
+        )
+    })?;
+    Ok(text.lines().map(String::from).collect())
+}
+
+pub fn save(name: &str, args: &[String]) -> Result<PathBuf, String> {
+    let path = path(name)?;
+    let write = || {
+        fs::create_dir_all(path.parent().unwrap())?;
+        let mut text = args.join("\n");
+        text.push('\n');
+        fs::write(&path, text)
+    };
+    write().map_err(|e| {
+        format!(
+            "could not save profile {} ({}): {}",
+            name,
+            path.display(),
+            e
+        )
+    })?;
+    Ok(path)
+}
+
+/// Every profile with its arguments, sorted by name
+pub fn list() -> Result<Vec<(String, Vec<String>)>, String> {
+    let dir = dir()?;
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("could not read {}: {}", dir.display(), e)),
+    };
+    let mut profiles = Vec::new();
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        profiles.push((name.clone(), load(&name)?));
+    }
+    profiles.sort();
+    Ok(profiles)
+}
+
+/// The option an argument sets, `--baud-rate` for both `--baud-rate` and `--baud-rate=9600`
+fn key(arg: &str) -> Option<&str> {
+    arg.starts_with('-')
+        .then(|| arg.split_once('=').map_or(arg, |(key, _)| key))
+}
+
+/// The command line followed by the profile's options that it doesn't set itself,
+/// an option is taken to be a flag followed by any values that don't start with `-`
+pub fn merge(cli: &[String], profile: &[String]) -> Vec<String> {
+    let given: Vec<_> = cli.iter().filter_map(|arg| key(arg)).collect();
+    let mut merged = cli.to_vec();
+    let mut keep = true;
+    for arg in profile {
+        if let Some(key) = key(arg) {
+            keep = !given.contains(&key);
+        }
+        if keep {
+            merged.push(arg.clone());
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| String::from(*s)).collect()
+    }
+
+    #[test]
+    fn the_command_line_wins_over_the_profile() {
+        let profile = strings(&[
+            "-b",
+            "9600",
+            "--parity=even",
+            "--macro",
+            "F1",
+            "AT\\r",
+            "-q",
+        ]);
+        let cli = strings(&["-b", "115200", "--parity", "odd"]);
+        assert_eq!(
+            merge(&cli, &profile),
+            strings(&["-b", "115200", "--parity", "odd", "--macro", "F1", "AT\\r", "-q"])
+        );
+        assert_eq!(merge(&[], &profile), profile);
+        assert_eq!(merge(&cli, &[]), cli);
+    }
+
+    #[test]
+    fn option_keys_ignore_the_value() {
+        assert_eq!(key("--baud-rate=9600"), Some("--baud-rate"));
+        assert_eq!(key("-b"), Some("-b"));
+        assert_eq!(key("9600"), None);
+    }
+
+    #[test]
+    fn names_that_would_leave_the_directory_are_rejected() {
+        for name in ["", "../x", "a/b", "a\\b", ".hidden"] {
+            assert!(path(name).is_err(), "{}", name);
+        }
+    }
+}
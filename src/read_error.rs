@@ -0,0 +1,274 @@
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use serialport::SerialPort;
+
+/// The longest the backoff waits between two retries
+const MAX_BACKOFF: Duration = Duration::from_secs(1);
+
+/// What the streaming loop does about an error reading the port
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadErrorAction {
+    /// Nothing arrived in time, so just read again
+    Ignore,
+    /// A passing problem, read again after a short wait
+    Retry,
+    /// The port has gone, e.g. the adapter was unplugged, so wait for it to come back
+    Reconnect,
+    /// Nothing more can be read
+    Exit,
+}
+
+#[cfg(unix)]
+fn disconnected(code: i32) -> bool {
+    matches!(code, libc::EIO | libc::ENXIO | libc::ENODEV)
+}
+
+#[cfg(not(unix))]
+fn disconnected(_code: i32) -> bool {
+    false
+}
+
+/// Sort a read error by whether reading can go on, now, later or once the port is back
+pub fn classify_read_error(e: &io::Error) -> ReadErrorAction {
+    match e.kind() {
+        io::ErrorKind::TimedOut => ReadErrorAction::Ignore,
+        io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock => ReadErrorAction::Retry,
+        io::ErrorKind::NotConnected
+        | io::ErrorKind::ConnectionReset
+        | io::ErrorKind::ConnectionAborted
+        | io::ErrorKind::BrokenPipe
+        | io::ErrorKind::UnexpectedEof
+        | io::ErrorKind::NotFound => ReadErrorAction::Reconnect,
+        _ if e.raw_os_error().is_some_and(disconnected) => ReadErrorAction::Reconnect,
+        _ => ReadErrorAction::Exit,
+    }
+}
+
+/// The waits between retries of a read, doubling from `first` up to a second
+/// Gives up after `limit` retries in a row, 0 never does
+pub struct Backoff {
+    first: Duration,
+    next: Duration,
+    retries: usize,
+    limit: usize,
+}
+
+impl Backoff {
+    pub fn new(first: Duration, limit: usize) -> Self {
+        Backoff {
+            first,
+            next: first,
+            retries: 0,
+            limit,
+        }
+    }
+
+    /// How long to wait before the next retry, None once there have been too many
+    pub fn retry(&mut self) -> Option<Duration> {
+        if self.limit > 0 && self.retries >= self.limit {
+            return None;
+        }
+        self.retries += 1;
+        let wait = self.next;
+        self.next = (self.next * 2).min(MAX_BACKOFF);
+        Some(wait)
+    }
+
+    /// Start again after a read that worked
+    pub fn reset(&mut self) {
+        self.next = self.first;
+        self.retries = 0;
+    }
+
+    pub fn retries(&self) -> usize {
+        self.retries
+    }
+}
+
+/// A second handle on the port being read, for the control lines and writing,
+/// which `Reconnecting` swaps for one on the reopened port
+#[derive(Clone)]
+pub struct CurrentPort(Arc<Mutex<Box<dyn SerialPort>>>);
+
+impl CurrentPort {
+    pub fn new(port: Box<dyn SerialPort>) -> Self {
+        CurrentPort(Arc::new(Mutex::new(port)))
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, Box<dyn SerialPort>> {
+        self.0.lock().unwrap()
+    }
+}
+
+impl Write for CurrentPort {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lock().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.lock().flush()
+    }
+}
+
+/// Reads a port, waiting for it to come back with `reopen` when it disconnects
+/// A reconnect shows up as an `Interrupted` error so the reader just goes round again
+pub struct Reconnecting<F> {
+    port: Box<dyn SerialPort>,
+    name: String,
+    reopen: F,
+    current: Option<CurrentPort>,
+}
+
+impl<F: FnMut() -> serialport::Result<Box<dyn SerialPort>>> Reconnecting<F> {
+    pub fn new(port: Box<dyn SerialPort>, name: &str, reopen: F) -> Self {
+        Reconnecting {
+            port,
+            name: name.to_string(),
+            reopen,
+            current: None,
+        }
+    }
+
+    /// Keep `current` on the port that is being read, after every reconnect
+    pub fn current(mut self, current: CurrentPort) -> Self {
+        self.current = Some(current);
+        self
+    }
+}
+
+impl<F: FnMut() -> serialport::Result<Box<dyn SerialPort>>> Read for Reconnecting<F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.port.read(buf) {
+            Err(e) if classify_read_error(&e) == ReadErrorAction::Reconnect => {
+                eprintln!("Lost {} ({}), waiting for it to come back…", self.name, e);
+                match (self.reopen)() {
+                    Ok(port) => {
+                        eprintln!("Reconnected to {}", self.name);
+                        if let Some(current) = &self.current {
+                            match port.try_clone() {
+                                Ok(clone) => *current.lock() = clone,
+                                Err(e) => {
+                                    eprintln!("The control lines are still on the lost port: {}", e)
+                                }
+                            }
+                        }
+                        self.port = port;
+                        Err(io::ErrorKind::Interrupted.into())
+                    }
+                    Err(_) => Err(e),
+                }
+            }
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockPort;
+
+    #[test]
+    fn errors_are_sorted_by_what_can_be_done() {
+        let action = |kind: io::ErrorKind| classify_read_error(&kind.into());
+        assert_eq!(action(io::ErrorKind::TimedOut), ReadErrorAction::Ignore);
+        assert_eq!(action(io::ErrorKind::Interrupted), ReadErrorAction::Retry);
+        assert_eq!(action(io::ErrorKind::WouldBlock), ReadErrorAction::Retry);
+        assert_eq!(
+            action(io::ErrorKind::BrokenPipe),
+            ReadErrorAction::Reconnect
+        );
+        assert_eq!(
+            action(io::ErrorKind::NotConnected),
+            ReadErrorAction::Reconnect
+        );
+        assert_eq!(action(io::ErrorKind::NotFound), ReadErrorAction::Reconnect);
+        assert_eq!(
+            action(io::ErrorKind::PermissionDenied),
+            ReadErrorAction::Exit
+        );
+        assert_eq!(action(io::ErrorKind::InvalidData), ReadErrorAction::Exit);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn an_unplugged_device_is_a_reconnect() {
+        for code in [libc::EIO, libc::ENXIO, libc::ENODEV] {
+            let e = io::Error::from_raw_os_error(code);
+            assert_eq!(classify_read_error(&e), ReadErrorAction::Reconnect);
+        }
+        let e = io::Error::from_raw_os_error(libc::EINVAL);
+        assert_eq!(classify_read_error(&e), ReadErrorAction::Exit);
+    }
+
+    #[test]
+    fn the_backoff_doubles_up_to_a_second_and_gives_up() {
+        let mut backoff = Backoff::new(Duration::from_millis(300), 4);
+        let waits: Vec<_> = std::iter::from_fn(|| backoff.retry()).collect();
+        assert_eq!(waits, [300, 600, 1000, 1000].map(Duration::from_millis));
+        assert_eq!(backoff.retries(), 4);
+        backoff.reset();
+        assert_eq!(backoff.retry(), Some(Duration::from_millis(300)));
+
+        let mut forever = Backoff::new(Duration::from_millis(1), 0);
+        assert!((0..100).all(|_| forever.retry().is_some()));
+    }
+
+    #[test]
+    fn a_lost_port_is_swapped_for_the_reopened_one() {
+        let lost = MockPort::with_input(b"before");
+        lost.state().fail = Some(io::ErrorKind::NotConnected);
+        let back = MockPort::with_input(b"after");
+        let mut reopened = Some(back.clone());
+        let mut port = Reconnecting::new(Box::new(lost), "mock", || {
+            Ok(Box::new(reopened.take().unwrap()) as Box<dyn SerialPort>)
+        });
+        let mut buf = [0; 16];
+        assert_eq!(port.read(&mut buf).unwrap(), 6);
+        assert_eq!(
+            port.read(&mut buf).unwrap_err().kind(),
+            io::ErrorKind::Interrupted
+        );
+        assert_eq!(port.read(&mut buf).unwrap(), 5);
+        assert_eq!(&buf[..5], b"after");
+    }
+
+    #[test]
+    fn a_port_that_stays_away_passes_on_the_error() {
+        let lost = MockPort::default();
+        lost.state().fail = Some(io::ErrorKind::BrokenPipe);
+        let mut port = Reconnecting::new(Box::new(lost), "mock", || {
+            Err(serialport::Error::new(
+                serialport::ErrorKind::NoDevice,
+                "gone",
+            ))
+        });
+        assert_eq!(
+            port.read(&mut [0; 4]).unwrap_err().kind(),
+            io::ErrorKind::BrokenPipe
+        );
+    }
+
+    #[test]
+    fn the_current_port_follows_a_reconnect() {
+        let lost = MockPort::default();
+        lost.state().fail = Some(io::ErrorKind::NotConnected);
+        let back = MockPort::default();
+        let current = CurrentPort::new(Box::new(lost.clone()));
+        let mut reopened = Some(back.clone());
+        let mut port = Reconnecting::new(Box::new(lost.clone()), "mock", || {
+            Ok(Box::new(reopened.take().unwrap()) as Box<dyn SerialPort>)
+        })
+        .current(current.clone());
+        let mut writer = current.clone();
+        writer.write_all(b"a").unwrap();
+        assert!(port.read(&mut [0; 4]).is_err());
+        writer.write_all(b"b").unwrap();
+        current.lock().write_data_terminal_ready(false).unwrap();
+        assert_eq!(lost.state().written, b"a");
+        assert_eq!(back.state().written, b"b");
+        assert_eq!(back.state().lines, [("DTR", false)]);
+    }
+}
@@ -0,0 +1,99 @@
+use crate::escape::unescape;
+
+/// Parse a `--record-delim` value, either hex bytes like `0x0d0a` or a string with escapes
+pub fn parse_delim(s: &str) -> Result<Vec<u8>, String> {
+    let hex = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .filter(|h| !h.is_empty() && h.len() % 2 == 0 && h.chars().all(|c| c.is_ascii_hexdigit()));
+    let delim = match hex {
+        Some(hex) => (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect(),
+        None => unescape(s)?,
+    };
+    if delim.is_empty() {
+        return Err(String::from("the record delimiter can't be empty"));
+    }
+    Ok(delim)
+}
+
+/// Ends every record at a delimiter other than newline with a newline instead,
+/// so everything that works a line at a time works a record at a time
+/// A delimiter split over reads is held back until it is known to be one
+pub struct RecordSplitter {
+    delim: Vec<u8>,
+    pending: Vec<u8>,
+}
+
+impl RecordSplitter {
+    pub fn new(delim: Vec<u8>) -> Self {
+        RecordSplitter {
+            delim,
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn apply(&mut self, data: &[u8], out: &mut Vec<u8>) {
+        for &b in data {
+            self.pending.push(b);
+            if self.pending == self.delim {
+                out.push(b'\n');
+                self.pending.clear();
+                continue;
+            }
+            // Let go of bytes until what is held back could still be the start of a delimiter
+            while !self.delim.starts_with(&self.pending) {
+                out.push(self.pending.remove(0));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(delim: &[u8], stream: &[u8], chunk: usize) -> Vec<u8> {
+        let mut splitter = RecordSplitter::new(delim.to_vec());
+        let mut out = Vec::new();
+        for data in stream.chunks(chunk) {
+            splitter.apply(data, &mut out);
+        }
+        out
+    }
+
+    #[test]
+    fn delimiters_parse_as_hex_or_text() {
+        assert_eq!(parse_delim("0x0d0a"), Ok(b"\r\n".to_vec()));
+        assert_eq!(parse_delim(";"), Ok(b";".to_vec()));
+        assert_eq!(parse_delim("\\r"), Ok(b"\r".to_vec()));
+        // An odd number of digits isn't hex, so it is the text itself
+        assert_eq!(parse_delim("0x0"), Ok(b"0x0".to_vec()));
+        assert!(parse_delim("").is_err());
+    }
+
+    #[test]
+    fn splits_on_a_single_byte() {
+        assert_eq!(split(b";", b"a=1;b=2;c", 3), b"a=1\nb=2\nc");
+        assert_eq!(split(b"\x00", b"one\x00two\x00", 1), b"one\ntwo\n");
+    }
+
+    #[test]
+    fn a_two_byte_sentinel_across_reads() {
+        for chunk in 1..6 {
+            assert_eq!(split(b"|~", b"ab|~cd|x|~~|~", chunk), b"ab\ncd|x\n~\n");
+        }
+    }
+
+    #[test]
+    fn a_partial_sentinel_is_held_back() {
+        let mut splitter = RecordSplitter::new(b"END".to_vec());
+        let mut out = Vec::new();
+        splitter.apply(b"dataEN", &mut out);
+        assert_eq!(out, b"data");
+        splitter.apply(b"Dmore", &mut out);
+        assert_eq!(out, b"data\nmore");
+    }
+}
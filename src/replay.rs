@@ -0,0 +1,126 @@
+use std::io::{self, Read};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use serialport::{DataBits, Parity, StopBits};
+
+/// Wraps a reader and paces it to a fixed number of bytes per second
+/// Used to play back a capture at (roughly) the speed the device sent it
+pub struct Throttled<R> {
+    inner: R,
+    rate: u32,
+    start: Instant,
+    done: u64,
+}
+
+impl<R: Read> Throttled<R> {
+    pub fn new(inner: R, rate: u32) -> Self {
+        Throttled {
+            inner,
+            rate: rate.max(1),
+            start: Instant::now(),
+            done: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for Throttled<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let due = Duration::from_secs_f64(self.done as f64 / self.rate as f64);
+        let elapsed = self.start.elapsed();
+        if due > elapsed {
+            sleep(due - elapsed);
+        }
+        // Hand out small slices so the output trickles instead of bursting
+        let chunk = buf.len().min((self.rate as usize / 100).max(1));
+        let n = self.inner.read(&mut buf[..chunk])?;
+        self.done += n as u64;
+        Ok(n)
+    }
+}
+
+/// Bytes per second a UART sends at this baud rate and framing (start bit included)
+pub fn char_rate(baud_rate: u32, data_bits: DataBits, parity: Parity, stop_bits: StopBits) -> u32 {
+    let data = match data_bits {
+        DataBits::Five => 5,
+        DataBits::Six => 6,
+        DataBits::Seven => 7,
+        DataBits::Eight => 8,
+    };
+    let parity = match parity {
+        Parity::None => 0,
+        _ => 1,
+    };
+    let stop = match stop_bits {
+        StopBits::One => 1,
+        StopBits::Two => 2,
+    };
+    baud_rate / (1 + data + parity + stop)
+}
+
+/// Where in a capture the data received `at` after the start begins, from the `--timing` log
+/// recorded with it, an error if the log doesn't reach that far
+pub fn offset_at(log: &str, at: Duration) -> Result<u64, String> {
+    let mut elapsed = Duration::ZERO;
+    let mut offset = 0;
+    for (i, line) in log.lines().enumerate() {
+        let invalid = || format!("line {} of the timing log isn't `delta_us\\tbytes`", i + 1);
+        let (delta, bytes) = line.split_once('\t').ok_or_else(invalid)?;
+        elapsed += Duration::from_micros(delta.parse().map_err(|_| invalid())?);
+        if elapsed >= at {
+            return Ok(offset);
+        }
+        offset += bytes.split_whitespace().count() as u64;
+    }
+    Err(format!(
+        "the timing log only covers {}ms",
+        elapsed.as_millis()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn char_rate_counts_every_bit_of_a_character() {
+        let rate = |data, parity, stop| char_rate(115200, data, parity, stop);
+        assert_eq!(rate(DataBits::Eight, Parity::None, StopBits::One), 11520);
+        assert_eq!(rate(DataBits::Seven, Parity::Even, StopBits::One), 11520);
+        assert_eq!(rate(DataBits::Eight, Parity::Odd, StopBits::Two), 9600);
+    }
+
+    #[test]
+    fn throttled_passes_the_data_through_unchanged() {
+        let data: Vec<u8> = (0..=255).collect();
+        let mut replayed = Vec::new();
+        Throttled::new(&data[..], 1_000_000)
+            .read_to_end(&mut replayed)
+            .unwrap();
+        assert_eq!(replayed, data);
+    }
+
+    #[test]
+    fn the_timing_log_gives_the_offset_of_a_time() {
+        // 3 bytes at the start, 2 after 10ms, 4 more after another 20ms
+        let log = "0\t41 42 43\n10000\t44 45\n20000\t46 47 48 49\n";
+        let at = |ms| offset_at(log, Duration::from_millis(ms));
+        assert_eq!(at(0), Ok(0));
+        assert_eq!(at(5), Ok(3));
+        assert_eq!(at(10), Ok(3));
+        assert_eq!(at(11), Ok(5));
+        assert_eq!(at(30), Ok(5));
+        assert_eq!(at(31), Err(String::from("the timing log only covers 30ms")));
+    }
+
+    #[test]
+    fn a_broken_timing_log_says_where() {
+        let log = "0\t41\nnot a line\n";
+        assert_eq!(
+            offset_at(log, Duration::from_millis(1)),
+            Err(String::from(
+                "line 2 of the timing log isn't `delta_us\\tbytes`"
+            ))
+        );
+    }
+}
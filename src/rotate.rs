@@ -0,0 +1,150 @@
+use std::ffi::OsString;
+use std::fmt::{self, Write as _};
+use std::fs::File;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use chrono::{DateTime, TimeZone};
+
+/// Fill in the strftime style placeholders of an output file name, e.g. `capture-%Y%m%d-%H%M%S.txt`
+pub fn dated_name<Tz>(template: &str, now: &DateTime<Tz>) -> Result<String, String>
+where
+    Tz: TimeZone,
+    Tz::Offset: fmt::Display,
+{
+    let mut name = String::new();
+    write!(name, "{}", now.format(template))
+        .map_err(|_| format!("\"{}\" has an invalid % placeholder", template))?;
+    Ok(name)
+}
+
+/// Set by SIGHUP, the next write goes to a freshly opened file
+static REOPEN: AtomicBool = AtomicBool::new(false);
+
+/// Ask every `Rotating` file to reopen its path before writing again
+pub fn request_reopen() {
+    REOPEN.store(true, Ordering::Relaxed);
+}
+
+/// Reopen the output file on SIGHUP like other daemons do, for logrotate
+/// This replaces the stop-on-SIGHUP handler from ctrlc
+#[cfg(unix)]
+pub fn reopen_on_sighup() {
+    extern "C" fn on_hup(_: libc::c_int) {
+        // Only an atomic store, so this is safe to do in a signal handler
+        request_reopen();
+    }
+    let handler: extern "C" fn(libc::c_int) = on_hup;
+    unsafe {
+        libc::signal(libc::SIGHUP, handler as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn reopen_on_sighup() {
+    eprintln!("Warning: --rotate-on-signal needs SIGHUP, which this platform doesn't have");
+}
+
+/// The output file, reopened from its path whenever a reopen has been requested
+/// so a file renamed away by logrotate is let go and a new one is started
+pub struct Rotating {
+    path: OsString,
+    append: bool,
+    file: File,
+}
+
+impl Rotating {
+    pub fn open(path: &OsString, append: bool) -> io::Result<Self> {
+        Ok(Rotating {
+            path: path.clone(),
+            append,
+            file: crate::create_output(path, append)?,
+        })
+    }
+
+    /// Flush and close the current file, then open the path again
+    pub fn reopen(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        self.file = crate::create_output(&self.path, self.append)?;
+        Ok(())
+    }
+
+    fn reopen_if_requested(&mut self) -> io::Result<()> {
+        if REOPEN.swap(false, Ordering::Relaxed) {
+            self.reopen()?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for Rotating {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.reopen_if_requested()?;
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("oxterm-test-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn a_requested_reopen_writes_to_a_new_file_at_the_path() {
+        let path = temp_path("rotate.log");
+        let rotated = temp_path("rotate.log.1");
+        let mut file = Rotating::open(&path.clone().into_os_string(), false).unwrap();
+        file.write_all(b"before\n").unwrap();
+        // What logrotate does before sending SIGHUP
+        std::fs::rename(&path, &rotated).unwrap();
+        file.write_all(b"still old\n").unwrap();
+        request_reopen();
+        file.write_all(b"after\n").unwrap();
+        file.flush().unwrap();
+        assert_eq!(std::fs::read(&rotated).unwrap(), b"before\nstill old\n");
+        assert_eq!(std::fs::read(&path).unwrap(), b"after\n");
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&rotated).unwrap();
+    }
+
+    #[test]
+    fn reopening_truncates_unless_appending() {
+        let path = temp_path("truncate.log");
+        // Written to directly, a write through it could take the other test's reopen request
+        let mut file = Rotating::open(&path.clone().into_os_string(), false).unwrap();
+        file.file.write_all(b"old\n").unwrap();
+        file.reopen().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"");
+
+        std::fs::write(&path, b"kept\n").unwrap();
+        let mut file = Rotating::open(&path.clone().into_os_string(), true).unwrap();
+        file.reopen().unwrap();
+        file.file.write_all(b"more\n").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"kept\nmore\n");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn placeholders_are_filled_in_from_the_clock() {
+        let now = chrono::Utc.with_ymd_and_hms(2024, 3, 9, 7, 5, 1).unwrap();
+        assert_eq!(
+            dated_name("logs/capture-%Y%m%d-%H%M%S.txt", &now).unwrap(),
+            "logs/capture-20240309-070501.txt"
+        );
+        assert_eq!(dated_name("plain.txt", &now).unwrap(), "plain.txt");
+        assert_eq!(dated_name("100%%.txt", &now).unwrap(), "100%.txt");
+    }
+
+    #[test]
+    fn an_unknown_placeholder_is_an_error() {
+        let now = chrono::Utc.with_ymd_and_hms(2024, 3, 9, 7, 5, 1).unwrap();
+        assert!(dated_name("capture-%Q.txt", &now).is_err());
+    }
+}
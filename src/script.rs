@@ -0,0 +1,117 @@
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::thread::sleep;
+use std::time::Duration;
+
+/// The line ending put on every line sent to the port
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eol {
+    Lf,
+    Cr,
+    CrLf,
+    None,
+}
+
+impl Eol {
+    pub fn bytes(self) -> &'static [u8] {
+        match self {
+            Eol::Lf => b"\n",
+            Eol::Cr => b"\r",
+            Eol::CrLf => b"\r\n",
+            Eol::None => b"",
+        }
+    }
+}
+
+impl FromStr for Eol {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "lf" => Eol::Lf,
+            "cr" => Eol::Cr,
+            "crlf" => Eol::CrLf,
+            "none" => Eol::None,
+            _ => return Err("Output EOL should be one of lf, cr, crlf, none"),
+        })
+    }
+}
+
+/// Split a script into the lines to send, each ending in `eol`
+/// Lines starting with `#` are comments and are skipped, the file's own line endings are dropped
+pub fn parse_script(text: &[u8], eol: Eol) -> Vec<Vec<u8>> {
+    // Otherwise the split leaves an empty line after the final newline
+    let text = text.strip_suffix(b"\n").unwrap_or(text);
+    if text.is_empty() {
+        return Vec::new();
+    }
+    text.split(|&b| b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .filter(|line| !line.starts_with(b"#"))
+        .map(|line| [line, eol.bytes()].concat())
+        .collect()
+}
+
+/// Send the lines one at a time, waiting `delay` after each so the device keeps up
+pub fn send(mut port: impl Write, lines: &[Vec<u8>], delay: Duration) -> io::Result<()> {
+    for line in lines {
+        port.write_all(line)?;
+        port.flush()?;
+        sleep(delay);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn eols_parse() {
+        assert_eq!("CRLF".parse::<Eol>().map(Eol::bytes), Ok(&b"\r\n"[..]));
+        assert_eq!("none".parse::<Eol>().map(Eol::bytes), Ok(&b""[..]));
+        assert!("lfcr".parse::<Eol>().is_err());
+    }
+
+    #[test]
+    fn comments_are_skipped_and_the_eol_is_applied() {
+        let script = b"# set up\r\nATZ\r\n#ATE1\nAT+GMR\n\n";
+        assert_eq!(
+            parse_script(script, Eol::Cr),
+            [b"ATZ\r".to_vec(), b"AT+GMR\r".to_vec(), b"\r".to_vec()]
+        );
+        assert!(parse_script(b"", Eol::Lf).is_empty());
+        assert!(parse_script(b"\n", Eol::Lf).is_empty());
+    }
+
+    /// Remembers when each write arrived
+    #[derive(Default)]
+    struct Port {
+        writes: Vec<(Instant, Vec<u8>)>,
+    }
+
+    impl Write for &mut Port {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.writes.push((Instant::now(), buf.to_vec()));
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn lines_are_sent_one_delay_apart() {
+        let lines = parse_script(b"one\ntwo\nthree\n", Eol::CrLf);
+        let mut port = Port::default();
+        let delay = Duration::from_millis(30);
+        send(&mut port, &lines, delay).unwrap();
+        let sent: Vec<&[u8]> = port.writes.iter().map(|(_, b)| &b[..]).collect();
+        assert_eq!(sent, [&b"one\r\n"[..], b"two\r\n", b"three\r\n"]);
+        for pair in port.writes.windows(2) {
+            assert!(pair[1].0 - pair[0].0 >= delay);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::VecDeque;
+
+/// Received text split into lines, keeping at most `capacity` of them
+/// The last line is the one still being received
+/// The viewport is `offset` lines up from the bottom, 0 means it follows new data
+pub struct Scrollback {
+    lines: VecDeque<String>,
+    capacity: usize,
+    offset: usize,
+}
+
+impl Scrollback {
+    pub fn new(capacity: usize) -> Self {
+        Scrollback {
+            lines: VecDeque::from(vec![String::new()]),
+            capacity: capacity.max(1),
+            offset: 0,
+        }
+    }
+
+    pub fn push(&mut self, text: &str) {
+        for c in text.chars() {
+            match c {
+                '\n' => {
+                    self.lines.push_back(String::new());
+                    if self.lines.len() > self.capacity {
+                        self.lines.pop_front();
+                    }
+                    // Hold the viewport on the same lines unless it is following
+                    if self.offset > 0 {
+                        self.offset = (self.offset + 1).min(self.lines.len() - 1);
+                    }
+                }
+                '\r' => {}
+                c => self.lines.back_mut().unwrap().push(c),
+            }
+        }
+    }
+
+    /// Whether nothing has been received on the last line yet
+    pub fn at_line_start(&self) -> bool {
+        self.lines.back().unwrap().is_empty()
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Largest offset that still fills a viewport of `rows` lines
+    fn max_offset(&self, rows: usize) -> usize {
+        self.lines.len().saturating_sub(rows)
+    }
+
+    pub fn scroll_up(&mut self, n: usize, rows: usize) {
+        self.offset = (self.offset + n).min(self.max_offset(rows));
+    }
+
+    pub fn scroll_down(&mut self, n: usize) {
+        self.offset = self.offset.saturating_sub(n);
+    }
+
+    pub fn top(&mut self, rows: usize) {
+        self.offset = self.max_offset(rows);
+    }
+
+    pub fn bottom(&mut self) {
+        self.offset = 0;
+    }
+
+    /// The last `n` lines, leaving out the line being received if nothing has arrived on it
+    pub fn last(&self, n: usize) -> impl Iterator<Item = &String> {
+        let end = self.lines.len() - self.at_line_start() as usize;
+        self.lines.range(end.saturating_sub(n)..end)
+    }
+
+    /// The lines that fill a viewport of `rows` lines
+    pub fn visible(&self, rows: usize) -> impl Iterator<Item = &String> {
+        self.region(rows, 0)
+    }
+
+    /// The viewport of `rows` lines with up to `context` lines from above it, as text
+    pub fn render(&self, rows: usize, context: usize) -> String {
+        self.region(rows, context)
+            .map(|line| format!("{}\n", line))
+            .collect()
+    }
+
+    fn region(&self, rows: usize, context: usize) -> impl Iterator<Item = &String> {
+        let end = (self.lines.len() - self.offset).max(rows.min(self.lines.len()));
+        self.lines.range(end.saturating_sub(rows + context)..end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shown(screen: &Scrollback, rows: usize) -> Vec<&str> {
+        screen.visible(rows).map(String::as_str).collect()
+    }
+
+    #[test]
+    fn push_splits_lines_and_trims_to_capacity() {
+        let mut screen = Scrollback::new(3);
+        screen.push("a\nb\r\nc");
+        assert_eq!(shown(&screen, 5), ["a", "b", "c"]);
+        screen.push("c\nd");
+        assert_eq!(shown(&screen, 5), ["b", "cc", "d"]);
+        assert!(!screen.at_line_start());
+    }
+
+    #[test]
+    fn viewport_follows_new_data_at_the_bottom() {
+        let mut screen = Scrollback::new(100);
+        screen.push("1\n2\n3\n4");
+        assert_eq!(shown(&screen, 2), ["3", "4"]);
+        screen.push("\n5");
+        assert_eq!(shown(&screen, 2), ["4", "5"]);
+    }
+
+    #[test]
+    fn viewport_holds_when_scrolled_up() {
+        let mut screen = Scrollback::new(100);
+        screen.push("1\n2\n3\n4");
+        screen.scroll_up(1, 2);
+        assert_eq!(shown(&screen, 2), ["2", "3"]);
+        screen.push("\n5\n6");
+        assert_eq!(shown(&screen, 2), ["2", "3"]);
+        assert_eq!(screen.offset(), 3);
+        screen.scroll_down(1);
+        assert_eq!(shown(&screen, 2), ["3", "4"]);
+        screen.bottom();
+        assert_eq!(shown(&screen, 2), ["5", "6"]);
+    }
+
+    #[test]
+    fn scrolling_stops_at_the_top() {
+        let mut screen = Scrollback::new(100);
+        screen.push("1\n2\n3\n4");
+        screen.scroll_up(10, 2);
+        assert_eq!(shown(&screen, 2), ["1", "2"]);
+        screen.bottom();
+        screen.top(3);
+        assert_eq!(shown(&screen, 3), ["1", "2", "3"]);
+    }
+
+    #[test]
+    fn held_viewport_stays_inside_a_full_buffer() {
+        let mut screen = Scrollback::new(3);
+        screen.push("1\n2\n3");
+        screen.scroll_up(1, 2);
+        screen.push("\n4\n5");
+        assert!(screen.offset() <= 2);
+        assert_eq!(shown(&screen, 2), ["3", "4"]);
+    }
+
+    #[test]
+    fn last_takes_the_newest_lines_and_skips_an_empty_one_being_received() {
+        let mut screen = Scrollback::new(100);
+        screen.push("1\n2\n3\n");
+        let last = |screen: &Scrollback, n| screen.last(n).cloned().collect::<Vec<_>>();
+        assert_eq!(last(&screen, 2), ["2", "3"]);
+        assert_eq!(last(&screen, 10), ["1", "2", "3"]);
+        screen.push("4");
+        assert_eq!(last(&screen, 2), ["3", "4"]);
+        // Scrolling moves the viewport, not what was received last
+        screen.scroll_up(2, 2);
+        assert_eq!(last(&screen, 1), ["4"]);
+        assert!(last(&Scrollback::new(10), 3).is_empty());
+    }
+
+    #[test]
+    fn render_writes_the_viewport_with_context_from_above() {
+        let mut screen = Scrollback::new(100);
+        screen.push("1\n2\n3\n4\n5\n6");
+        assert_eq!(screen.render(2, 0), "5\n6\n");
+        assert_eq!(screen.render(2, 2), "3\n4\n5\n6\n");
+        // Scrolled up, the region moves with the viewport
+        screen.scroll_up(2, 2);
+        assert_eq!(screen.render(2, 1), "2\n3\n4\n");
+        // Context runs out at the top
+        assert_eq!(screen.render(2, 10), "1\n2\n3\n4\n");
+    }
+
+    #[test]
+    fn render_of_a_short_scrollback_has_every_line() {
+        let mut screen = Scrollback::new(100);
+        screen.push("only\nlines");
+        assert_eq!(screen.render(10, 5), "only\nlines\n");
+    }
+}
@@ -0,0 +1,164 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use serialport::SerialPort;
+
+/// The modem status lines a port can read, None where the driver can't tell
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Signals {
+    pub cts: Option<bool>,
+    pub dsr: Option<bool>,
+    pub ri: Option<bool>,
+    pub cd: Option<bool>,
+}
+
+impl Signals {
+    pub fn read(port: &mut dyn SerialPort) -> Self {
+        Signals {
+            cts: port.read_clear_to_send().ok(),
+            dsr: port.read_data_set_ready().ok(),
+            ri: port.read_ring_indicator().ok(),
+            cd: port.read_carrier_detect().ok(),
+        }
+    }
+
+    fn lines(&self) -> [(&'static str, Option<bool>); 4] {
+        [
+            ("CTS", self.cts),
+            ("DSR", self.dsr),
+            ("RI", self.ri),
+            ("CD", self.cd),
+        ]
+    }
+}
+
+/// The lines that differ between two readings, as `CTS low -> high`
+pub fn changes(before: &Signals, after: &Signals) -> Vec<String> {
+    before
+        .lines()
+        .iter()
+        .zip(after.lines().iter())
+        .filter(|(b, a)| b.1 != a.1)
+        .map(|((name, b), (_, a))| format!("{} {} -> {}", name, level(*b), level(*a)))
+        .collect()
+}
+
+/// Polls the modem lines every `interval` and reports only when one of them changes
+pub struct Monitor {
+    interval: Duration,
+    polled: Option<Instant>,
+    last: Option<Signals>,
+}
+
+impl Monitor {
+    pub fn new(interval: Duration) -> Self {
+        Monitor {
+            interval,
+            polled: None,
+            last: None,
+        }
+    }
+
+    /// Whether it is time to read the lines again
+    pub fn due(&self, now: Instant) -> bool {
+        self.polled
+            .is_none_or(|polled| now - polled >= self.interval)
+    }
+
+    /// Take a reading made at `now`, returns what to report: the starting state, then only changes
+    pub fn update(&mut self, signals: Signals, now: Instant) -> Option<String> {
+        self.polled = Some(now);
+        let report = match &self.last {
+            None => Some(signals.to_string()),
+            Some(last) => {
+                let changes = changes(last, &signals);
+                (!changes.is_empty()).then(|| changes.join(", "))
+            }
+        };
+        self.last = Some(signals);
+        report
+    }
+}
+
+fn level(state: Option<bool>) -> &'static str {
+    match state {
+        Some(true) => "high",
+        Some(false) => "low",
+        None => "?",
+    }
+}
+
+impl fmt::Display for Signals {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines: Vec<_> = self
+            .lines()
+            .iter()
+            .map(|(name, state)| format!("{} {}", name, level(*state)))
+            .collect();
+        write!(f, "{}", lines.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signals(cts: bool, dsr: bool, ri: bool, cd: bool) -> Signals {
+        Signals {
+            cts: Some(cts),
+            dsr: Some(dsr),
+            ri: Some(ri),
+            cd: Some(cd),
+        }
+    }
+
+    #[test]
+    fn only_transitions_are_reported_after_the_starting_state() {
+        let start = Instant::now();
+        let mut monitor = Monitor::new(Duration::from_millis(10));
+        let polls = [
+            signals(false, true, false, false),
+            signals(false, true, false, false),
+            signals(true, true, false, false),
+            signals(true, true, false, false),
+            signals(false, false, false, true),
+            signals(false, false, false, true),
+        ];
+        let reports: Vec<_> = polls
+            .iter()
+            .enumerate()
+            .map(|(i, s)| monitor.update(*s, start + Duration::from_millis(10 * i as u64)))
+            .collect();
+        assert_eq!(
+            reports,
+            [
+                Some(String::from("CTS low, DSR high, RI low, CD low")),
+                None,
+                Some(String::from("CTS low -> high")),
+                None,
+                Some(String::from(
+                    "CTS high -> low, DSR high -> low, CD low -> high"
+                )),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn polls_wait_for_the_interval() {
+        let start = Instant::now();
+        let mut monitor = Monitor::new(Duration::from_millis(100));
+        assert!(monitor.due(start));
+        monitor.update(Signals::default(), start);
+        assert!(!monitor.due(start + Duration::from_millis(99)));
+        assert!(monitor.due(start + Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn lines_a_driver_cant_read_are_unknown() {
+        let known = signals(true, false, false, false);
+        let unknown = Signals { cts: None, ..known };
+        assert_eq!(changes(&known, &unknown), ["CTS high -> ?"]);
+        assert_eq!(Signals::default().to_string(), "CTS ?, DSR ?, RI ?, CD ?");
+    }
+}
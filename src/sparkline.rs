@@ -0,0 +1,78 @@
+use std::collections::VecDeque;
+
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// The last few once a second throughput samples, oldest first
+pub struct History {
+    samples: VecDeque<f64>,
+    capacity: usize,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        History {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, sample: f64) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub fn render(&self) -> String {
+        render(self.samples.iter().copied())
+    }
+}
+
+/// One block character per sample, as tall as the sample is next to the largest one
+/// Nothing received is left blank so lulls stand out
+pub fn render(samples: impl Iterator<Item = f64> + Clone) -> String {
+    let max = samples.clone().fold(0.0, f64::max);
+    samples
+        .map(|sample| {
+            if sample <= 0.0 || max <= 0.0 {
+                ' '
+            } else {
+                let level = (sample / max * (BARS.len() - 1) as f64).round() as usize;
+                BARS[level.min(BARS.len() - 1)]
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_are_scaled_to_the_largest() {
+        let samples = [0.0, 1.0, 50.0, 100.0, 700.0, 350.0];
+        assert_eq!(render(samples.iter().copied()), " ▁▂▂█▅");
+        assert_eq!(render([7.0, 7.0].iter().copied()), "██");
+    }
+
+    #[test]
+    fn nothing_received_is_blank() {
+        assert_eq!(render([0.0, 0.0, 0.0].iter().copied()), "   ");
+        assert_eq!(render(std::iter::empty()), "");
+    }
+
+    #[test]
+    fn the_history_keeps_the_newest_samples() {
+        let mut history = History::new(3);
+        for sample in [8.0, 1.0, 2.0, 4.0, 8.0] {
+            history.push(sample);
+        }
+        assert_eq!(history.render(), "▃▅█");
+        let mut off = History::new(0);
+        off.push(1.0);
+        assert_eq!(off.render(), "");
+    }
+}
@@ -0,0 +1,220 @@
+use std::convert::TryInto;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection};
+
+use crate::{ConvertFrom, Encoder};
+
+/// How often the open transaction is committed so the rows show up for readers
+const COMMIT_EVERY: Duration = Duration::from_secs(1);
+
+/// Inserts one row per record into the `records` table, like `JsonlWriter` a record is a line
+/// or `group` decoded values. Rows are batched into a transaction that is committed every second
+pub struct SqliteWriter {
+    conn: Connection,
+    insert: String,
+    convert: ConvertFrom,
+    group: usize,
+    timestamp_format: String,
+    label: Option<String>,
+    carry: Vec<u8>,
+    committed: Instant,
+}
+
+fn sql_error(e: rusqlite::Error) -> io::Error {
+    io::Error::other(e)
+}
+
+impl SqliteWriter {
+    /// The value columns are named from `names`, or v1 to vN
+    pub fn open(
+        path: &Path,
+        convert: ConvertFrom,
+        group: usize,
+        names: &[String],
+        timestamp_format: String,
+        label: Option<String>,
+    ) -> io::Result<Self> {
+        let group = group.max(1);
+        let mut columns = vec![String::from("ts TEXT")];
+        if label.is_some() {
+            columns.push(String::from("label TEXT"));
+        }
+        columns.push(String::from("raw BLOB"));
+        if convert != ConvertFrom::NON {
+            for i in 0..group {
+                let name = names
+                    .get(i)
+                    .cloned()
+                    .unwrap_or_else(|| format!("v{}", i + 1));
+                columns.push(format!("\"{}\"", name.replace('"', "\"\"")));
+            }
+        }
+
+        let conn = Connection::open(path).map_err(sql_error)?;
+        conn.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS records ({}); BEGIN",
+            columns.join(", ")
+        ))
+        .map_err(sql_error)?;
+        let params = vec!["?"; columns.len()].join(", ");
+        Ok(SqliteWriter {
+            conn,
+            insert: format!("INSERT INTO records VALUES ({})", params),
+            convert,
+            group,
+            timestamp_format,
+            label,
+            carry: Vec::new(),
+            committed: Instant::now(),
+        })
+    }
+
+    fn record(&self, raw: &[u8], values: Vec<Value>) -> io::Result<()> {
+        let mut row = vec![Value::Text(
+            crate::timestamp(&self.timestamp_format).to_string(),
+        )];
+        if let Some(label) = &self.label {
+            row.push(Value::Text(label.clone()));
+        }
+        row.push(Value::Blob(raw.to_vec()));
+        row.extend(values);
+        let mut insert = self.conn.prepare_cached(&self.insert).map_err(sql_error)?;
+        insert.execute(params_from_iter(row)).map_err(sql_error)?;
+        Ok(())
+    }
+
+    fn commit(&mut self) -> io::Result<()> {
+        self.conn
+            .execute_batch("COMMIT; BEGIN")
+            .map_err(sql_error)?;
+        self.committed = Instant::now();
+        Ok(())
+    }
+}
+
+/// The single value held in `bytes` as an SQL number, `bytes` must be exactly `convert.width()` long
+fn sql_value(convert: ConvertFrom, bytes: &[u8]) -> Value {
+    use ConvertFrom::*;
+    match convert {
+        NON | HEX | BIN => Value::Integer(bytes[0].into()),
+        INT => Value::Integer(i32::from_le_bytes(bytes.try_into().unwrap()).into()),
+        SHR => Value::Integer(i16::from_le_bytes(bytes.try_into().unwrap()).into()),
+        UINT => Value::Integer(u32::from_le_bytes(bytes.try_into().unwrap()).into()),
+        USHR => Value::Integer(u16::from_le_bytes(bytes.try_into().unwrap()).into()),
+        FLT => Value::Real(f32::from_le_bytes(bytes.try_into().unwrap()).into()),
+    }
+}
+
+impl Encoder for SqliteWriter {
+    fn write(&mut self, data: &[u8], _out: &mut dyn Write) -> io::Result<()> {
+        self.carry.extend_from_slice(data);
+
+        let mut used = 0;
+        if self.convert == ConvertFrom::NON {
+            while let Some(i) = self.carry[used..].iter().position(|&b| b == b'\n') {
+                self.record(&self.carry[used..used + i], Vec::new())?;
+                used += i + 1;
+            }
+        } else {
+            let width = self.convert.width();
+            for raw in self.carry.chunks_exact(width * self.group) {
+                let values = raw
+                    .chunks_exact(width)
+                    .map(|bytes| sql_value(self.convert, bytes))
+                    .collect();
+                self.record(raw, values)?;
+                used += raw.len();
+            }
+        }
+        self.carry.drain(..used);
+
+        if self.committed.elapsed() >= COMMIT_EVERY {
+            self.commit()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for SqliteWriter {
+    fn drop(&mut self) {
+        if let Err(e) = self.conn.execute_batch("COMMIT") {
+            eprintln!("Could not save the last records to the database: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_db(name: &str) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("oxterm-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn lines_become_ts_and_raw_rows() {
+        let path = temp_db("lines.db");
+        let mut writer =
+            SqliteWriter::open(&path, ConvertFrom::NON, 1, &[], String::from("ts"), None).unwrap();
+        writer.write(b"one\ntw", &mut io::sink()).unwrap();
+        writer.write(b"o\npartial", &mut io::sink()).unwrap();
+        drop(writer);
+
+        let conn = Connection::open(&path).unwrap();
+        let mut select = conn.prepare("SELECT ts, raw FROM records").unwrap();
+        let rows: Vec<(String, Vec<u8>)> = select
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                (String::from("ts"), b"one".to_vec()),
+                (String::from("ts"), b"two".to_vec())
+            ]
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn values_go_in_named_columns() {
+        let path = temp_db("values.db");
+        let names = [String::from("x"), String::from("y")];
+        let mut writer = SqliteWriter::open(
+            &path,
+            ConvertFrom::SHR,
+            2,
+            &names,
+            String::from("ts"),
+            Some(String::from("imu")),
+        )
+        .unwrap();
+        let data: Vec<u8> = [1i16, -2, 3, -4, 5]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        writer.write(&data, &mut io::sink()).unwrap();
+        drop(writer);
+
+        let conn = Connection::open(&path).unwrap();
+        let mut select = conn.prepare("SELECT label, x, y FROM records").unwrap();
+        let rows: Vec<(String, i64, i64)> = select
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            rows,
+            [(String::from("imu"), 1, -2), (String::from("imu"), 3, -4)]
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
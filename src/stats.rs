@@ -0,0 +1,221 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Which bytes end words and lines, and which other bytes are counted
+#[derive(Debug, Clone)]
+pub struct Tokens {
+    pub word: u8,
+    pub line: u8,
+    pub counted: Vec<u8>,
+}
+
+impl Default for Tokens {
+    fn default() -> Self {
+        Tokens {
+            word: b' ',
+            line: b'\n',
+            counted: Vec::new(),
+        }
+    }
+}
+
+/// Running counts of what has been received since the last report
+/// `counted` goes with `Tokens::counted`
+#[derive(Debug, Default, Clone)]
+pub struct Counts {
+    pub words: usize,
+    pub bytes: usize,
+    pub lines: usize,
+    pub counted: Vec<(u8, usize)>,
+}
+
+/// Per second rates reported once a second by `Stats::tick`
+#[derive(Debug, Default, Clone)]
+pub struct Rates {
+    pub words: f64,
+    pub bytes: f64,
+    pub lines: f64,
+    pub counted: Vec<(u8, f64)>,
+}
+
+impl fmt::Display for Rates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "w{}, b{}, l{}", self.words, self.bytes, self.lines)?;
+        for (byte, rate) in &self.counted {
+            write!(f, ", {:#04x}={}", byte, rate)?;
+        }
+        Ok(())
+    }
+}
+
+/// Totals for the whole session, printed when it ends
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub totals: Counts,
+    pub duration: Duration,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.duration.as_secs_f64();
+        write!(
+            f,
+            "Received {} bytes, {} lines, {} words",
+            self.totals.bytes, self.totals.lines, self.totals.words,
+        )?;
+        for (byte, count) in &self.totals.counted {
+            write!(f, ", {} of {:#04x}", count, byte)?;
+        }
+        write!(
+            f,
+            " in {:.1}s ({:.0} B/s)",
+            secs,
+            self.totals.bytes as f64 / secs.max(f64::EPSILON),
+        )
+    }
+}
+
+pub struct Stats {
+    tokens: Tokens,
+    counts: Counts,
+    totals: Counts,
+    start: Instant,
+    stamp: Instant,
+}
+
+impl Stats {
+    pub fn new(start: Instant, tokens: &Tokens) -> Self {
+        let counts = Counts {
+            counted: tokens.counted.iter().map(|&b| (b, 0)).collect(),
+            ..Counts::default()
+        };
+        Stats {
+            tokens: tokens.clone(),
+            totals: counts.clone(),
+            counts,
+            start,
+            stamp: start,
+        }
+    }
+
+    /// Tally the bytes as they were read, and the counted bytes among them
+    pub fn count_bytes(&mut self, data: &[u8]) {
+        self.counts.bytes += data.len();
+        self.totals.bytes += data.len();
+        for ((byte, count), (_, total)) in self
+            .counts
+            .counted
+            .iter_mut()
+            .zip(self.totals.counted.iter_mut())
+        {
+            let n = data.iter().filter(|&b| b == byte).count();
+            *count += n;
+            *total += n;
+        }
+    }
+
+    /// Tally the words and lines in text data
+    pub fn count_text(&mut self, data: &[u8]) {
+        let words = data.iter().filter(|&&b| b == self.tokens.word).count();
+        let lines = data.iter().filter(|&&b| b == self.tokens.line).count();
+        self.counts.words += words;
+        self.totals.words += words;
+        self.counts.lines += lines;
+        self.totals.lines += lines;
+    }
+
+    /// Once at least a second has passed, turn the counts into rates and start counting again
+    pub fn tick(&mut self, now: Instant) -> Option<Rates> {
+        let time = now - self.stamp;
+        if time.as_secs() < 1 {
+            return None;
+        }
+        let secs = time.as_secs_f64();
+        let rates = Rates {
+            words: self.counts.words as f64 / secs,
+            bytes: self.counts.bytes as f64 / secs,
+            lines: self.counts.lines as f64 / secs,
+            counted: self
+                .counts
+                .counted
+                .iter_mut()
+                .map(|(byte, count)| (*byte, std::mem::take(count) as f64 / secs))
+                .collect(),
+        };
+        self.counts.words = 0;
+        self.counts.bytes = 0;
+        self.counts.lines = 0;
+        self.stamp = now;
+        Some(rates)
+    }
+
+    pub fn summary(&self, now: Instant) -> Summary {
+        Summary {
+            totals: self.totals.clone(),
+            duration: now - self.start,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &[u8] = b"a;b;c|d;e|f,g";
+
+    #[test]
+    fn custom_delimiters_and_counted_bytes_are_tallied() {
+        let start = Instant::now();
+        let tokens = Tokens {
+            word: b';',
+            line: b'|',
+            counted: vec![b',', b'e'],
+        };
+        let mut stats = Stats::new(start, &tokens);
+        stats.count_bytes(SAMPLE);
+        stats.count_text(SAMPLE);
+        let summary = stats.summary(start + Duration::from_secs(2));
+        assert_eq!(
+            (
+                summary.totals.bytes,
+                summary.totals.words,
+                summary.totals.lines
+            ),
+            (13, 3, 2)
+        );
+        assert_eq!(summary.totals.counted, [(b',', 1), (b'e', 1)]);
+        assert_eq!(
+            summary.to_string(),
+            "Received 13 bytes, 2 lines, 3 words, 1 of 0x2c, 1 of 0x65 in 2.0s (6 B/s)"
+        );
+    }
+
+    #[test]
+    fn the_defaults_are_spaces_and_newlines() {
+        let start = Instant::now();
+        let mut stats = Stats::new(start, &Tokens::default());
+        stats.count_text(b"one two\nthree;four\n");
+        let summary = stats.summary(start);
+        assert_eq!((summary.totals.words, summary.totals.lines), (1, 2));
+        assert!(summary.totals.counted.is_empty());
+    }
+
+    #[test]
+    fn rates_cover_the_second_since_the_last_tick_and_totals_keep_counting() {
+        let start = Instant::now();
+        let tokens = Tokens {
+            counted: vec![b','],
+            ..Tokens::default()
+        };
+        let mut stats = Stats::new(start, &tokens);
+        stats.count_bytes(b"1,2\n3,4\n");
+        stats.count_text(b"1,2\n3,4\n");
+        assert!(stats.tick(start + Duration::from_millis(999)).is_none());
+        let rates = stats.tick(start + Duration::from_secs(2)).unwrap();
+        assert_eq!(rates.to_string(), "w0, b4, l1, 0x2c=1");
+        stats.count_bytes(b",");
+        let rates = stats.tick(start + Duration::from_secs(3)).unwrap();
+        assert_eq!(rates.counted, [(b',', 1.0)]);
+        assert_eq!(stats.summary(start).totals.counted, [(b',', 3)]);
+    }
+}
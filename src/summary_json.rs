@@ -0,0 +1,33 @@
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, stderr, Write};
+
+use serde_json::Value;
+
+/// Write the summary as one JSON object to `path`, or to stderr for `-`
+pub fn write(path: &OsString, summary: &Value) -> io::Result<()> {
+    let mut out: Box<dyn Write> = if path == "-" {
+        Box::new(stderr())
+    } else {
+        Box::new(File::create(path)?)
+    };
+    serde_json::to_writer(&mut out, summary)?;
+    out.write_all(b"\n")?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_summary_is_one_line_of_json() {
+        let path =
+            std::env::temp_dir().join(format!("oxterm-test-{}-summary.json", std::process::id()));
+        let summary = serde_json::json!({ "bytes": 12, "failed": false, "alerts": null });
+        write(&path.clone().into_os_string(), &summary).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(text, "{\"alerts\":null,\"bytes\":12,\"failed\":false}\n");
+    }
+}
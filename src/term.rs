@@ -0,0 +1,235 @@
+use std::io::{self, stdin, stdout, Read, Write};
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+
+use crate::bell::{Bell, Flash};
+use crate::paste::{self, Brackets, Paced, Typed};
+use crate::terminal::TerminalGuard;
+use crate::Args;
+
+/// The first key of the escape, Ctrl-A like screen and picocom
+const ESCAPE: u8 = 0x01;
+
+/// What a typed byte amounts to once the Ctrl-A escape has been taken out
+#[derive(Debug, PartialEq, Eq)]
+pub enum Input {
+    Send(u8),
+    Quit,
+    None,
+}
+
+/// Spots Ctrl-A q (or Ctrl-A x) to quit, Ctrl-A Ctrl-A sends a Ctrl-A
+/// Anything else after Ctrl-A is sent as typed, with the Ctrl-A dropped
+#[derive(Default)]
+pub struct Escape {
+    armed: bool,
+}
+
+impl Escape {
+    pub fn feed(&mut self, byte: u8) -> Input {
+        if !std::mem::replace(&mut self.armed, false) {
+            if byte == ESCAPE {
+                self.armed = true;
+                return Input::None;
+            }
+            return Input::Send(byte);
+        }
+        match byte {
+            b'q' | b'Q' | b'x' | b'X' => Input::Quit,
+            byte => Input::Send(byte),
+        }
+    }
+}
+
+/// Send what was typed, or queue it behind the paste still being sent
+fn send(port: &mut impl Write, paced: &mut Paced, bytes: Vec<u8>) -> io::Result<()> {
+    if paced.is_empty() {
+        port.write_all(&bytes)
+    } else {
+        paced.type_ahead(&bytes, "");
+        Ok(())
+    }
+}
+
+/// Give the typed bytes to the port, and queue each line of a paste to be sent one every `--line-delay`
+/// Returns false once Ctrl-A q has been typed
+fn typed(
+    port: &mut impl Write,
+    escape: &mut Escape,
+    paced: &mut Paced,
+    typed: Typed,
+) -> io::Result<bool> {
+    match typed {
+        Typed::Keys(keys) => {
+            let mut bytes = Vec::with_capacity(keys.len());
+            let mut quit = false;
+            for byte in keys {
+                match escape.feed(byte) {
+                    Input::Send(byte) => bytes.push(byte),
+                    Input::Quit => {
+                        quit = true;
+                        break;
+                    }
+                    Input::None => {}
+                }
+            }
+            // The keys typed before Ctrl-A q are still sent, the paste waiting to go isn't
+            if !bytes.is_empty() {
+                send(port, paced, bytes)?;
+            }
+            if quit {
+                return Ok(false);
+            }
+        }
+        // Sent as is, a Ctrl-A in a paste is not an escape
+        Typed::Paste(text) => {
+            for line in paste::raw_lines(&text) {
+                paced.push(line.to_vec(), String::new());
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// Pass every typed byte straight to the port and every received byte straight to the screen
+/// With `--paste-safe` a paste is sent a line at a time instead
+pub fn run<P: Read + Write>(mut port: P, args: &Args) -> io::Result<()> {
+    eprintln!("Connected to {}, Ctrl-A q quits", args.port);
+    let mut guard = TerminalGuard::raw()?;
+    let mut brackets = None;
+    if args.paste_safe {
+        guard.bracketed_paste()?;
+        brackets = Some(Brackets::default());
+    }
+
+    // stdin has no timeout, so it is read on its own thread
+    let (sender, keys) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = [0; 64];
+        while let Ok(n) = stdin().read(&mut buf) {
+            if n == 0 || sender.send(buf[..n].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut escape = Escape::default();
+    let mut buf = vec![0; args.capacity];
+    let mut screen = stdout();
+    let mut flash = Flash::default();
+    let mut paced = Paced::new(args.line_delay, Instant::now());
+    while !crate::STOP.load(Ordering::Relaxed) {
+        flash.poll(Instant::now());
+        while let Ok(bytes) = keys.try_recv() {
+            let parts = match &mut brackets {
+                Some(brackets) => brackets.feed(&bytes),
+                None => vec![Typed::Keys(bytes)],
+            };
+            for part in parts {
+                if !typed(&mut port, &mut escape, &mut paced, part)? {
+                    return Ok(());
+                }
+            }
+        }
+        while let Some((bytes, _)) = paced.next(Instant::now()) {
+            port.write_all(&bytes)?;
+        }
+
+        match port.read(&mut buf[..args.read_size()]) {
+            Ok(0) => thread::sleep(crate::IDLE_SLEEP),
+            Ok(n) => {
+                if args.strip_parity {
+                    crate::bytemap::strip_parity(&mut buf[..n]);
+                }
+                let (n, rung) = args.bell.apply(&mut buf[..n]);
+                if rung > 0 && args.bell == Bell::Visual {
+                    flash.ring(Instant::now());
+                }
+                screen.write_all(&buf[..n])?;
+                screen.flush()?;
+            }
+            Err(ref e) if crate::severity(e) == crate::Severity::Recoverable => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(bytes: &[u8]) -> Vec<Input> {
+        let mut escape = Escape::default();
+        bytes.iter().map(|&b| escape.feed(b)).collect()
+    }
+
+    #[test]
+    fn ctrl_a_q_and_ctrl_a_x_quit() {
+        for quit in [b'q', b'Q', b'x', b'X'] {
+            assert_eq!(feed(&[ESCAPE, quit]), [Input::None, Input::Quit]);
+        }
+    }
+
+    #[test]
+    fn other_bytes_are_sent_as_typed() {
+        assert_eq!(
+            feed(b"q\x03\x1b[A"),
+            [
+                Input::Send(b'q'),
+                Input::Send(0x03),
+                Input::Send(0x1b),
+                Input::Send(b'['),
+                Input::Send(b'A')
+            ]
+        );
+    }
+
+    #[test]
+    fn ctrl_a_twice_sends_one_ctrl_a() {
+        assert_eq!(
+            feed(&[ESCAPE, ESCAPE, b'q']),
+            [Input::None, Input::Send(ESCAPE), Input::Send(b'q')]
+        );
+    }
+
+    #[test]
+    fn the_escape_only_covers_the_next_byte() {
+        assert_eq!(
+            feed(&[ESCAPE, b'a', b'q']),
+            [Input::None, Input::Send(b'a'), Input::Send(b'q')]
+        );
+    }
+
+    #[test]
+    fn a_paste_is_sent_a_line_at_a_time_after_what_was_typed() {
+        let mut port = crate::mock::MockPort::default();
+        let mut escape = Escape::default();
+        let start = Instant::now();
+        let delay = std::time::Duration::from_millis(100);
+        let mut paced = Paced::new(delay, start);
+        let mut brackets = Brackets::default();
+        let reads: [&[u8]; 3] = [b"x\x1b[200~AT\r\nATI\r\x01q\x1b[201~y", b"A", b"TZ"];
+        for read in reads {
+            for part in brackets.feed(read) {
+                assert!(typed(&mut port, &mut escape, &mut paced, part).unwrap());
+            }
+        }
+        // Typed after the paste, so it waits for it, all in one go
+        assert_eq!(port.state().written, b"x");
+        assert_eq!(paced.len(), 4);
+
+        let mut sent = Vec::new();
+        for step in 0..4 {
+            let (bytes, _) = paced.next(start + delay * step).unwrap();
+            sent.push(bytes);
+        }
+        assert_eq!(sent, [&b"AT\r\n"[..], b"ATI\r", b"\x01q", b"yATZ"]);
+
+        let part = Typed::Keys(vec![b'!', ESCAPE, b'q', b'?']);
+        assert!(!typed(&mut port, &mut escape, &mut paced, part).unwrap());
+        assert_eq!(port.state().written, b"x!");
+    }
+}
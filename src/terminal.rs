@@ -0,0 +1,107 @@
+use std::io::{self, stdout, Write};
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crossterm::event::{DisableBracketedPaste, EnableBracketedPaste};
+use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{cursor, execute, queue};
+
+/// Whether a guard currently has the terminal, for the panic hook
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static ALTERNATE: AtomicBool = AtomicBool::new(false);
+static PASTE: AtomicBool = AtomicBool::new(false);
+
+/// Puts the terminal in raw mode (and optionally the alternate screen) until dropped
+/// Dropping it, also while unwinding from a panic, puts the terminal back as it was
+pub struct TerminalGuard {
+    alternate: bool,
+}
+
+impl TerminalGuard {
+    /// Raw mode only, what was on screen stays there
+    pub fn raw() -> io::Result<Self> {
+        Self::enter(false)
+    }
+
+    /// Raw mode on the alternate screen, the shell's screen comes back on drop
+    pub fn fullscreen() -> io::Result<Self> {
+        Self::enter(true)
+    }
+
+    fn enter(alternate: bool) -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        // Made before entering the alternate screen so a failure there still restores raw mode
+        let guard = TerminalGuard { alternate };
+        ACTIVE.store(true, Ordering::Relaxed);
+        ALTERNATE.store(alternate, Ordering::Relaxed);
+        if alternate {
+            execute!(stdout(), EnterAlternateScreen)?;
+        }
+        Ok(guard)
+    }
+
+    /// Have the terminal mark pasted text, so it arrives as one paste event rather than as keys
+    pub fn bracketed_paste(&mut self) -> io::Result<()> {
+        PASTE.store(true, Ordering::Relaxed);
+        execute!(stdout(), EnableBracketedPaste)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        if ACTIVE.swap(false, Ordering::Relaxed) {
+            restore(&mut stdout(), self.alternate);
+        }
+    }
+}
+
+/// Queue the commands that undo what a guard did to the screen
+pub fn restore_commands(out: &mut impl Write, alternate: bool) -> io::Result<()> {
+    if PASTE.swap(false, Ordering::Relaxed) {
+        queue!(out, DisableBracketedPaste)?;
+    }
+    if alternate {
+        queue!(out, LeaveAlternateScreen)?;
+    }
+    queue!(out, cursor::Show)?;
+    out.flush()
+}
+
+fn restore(out: &mut impl Write, alternate: bool) {
+    let _ = restore_commands(out, alternate);
+    let _ = terminal::disable_raw_mode();
+}
+
+/// Put the terminal back before the panic message is printed, otherwise it lands on the
+/// alternate screen in raw mode and the shell is left unusable
+pub fn restore_on_panic() {
+    let default = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if ACTIVE.swap(false, Ordering::Relaxed) {
+            restore(&mut stdout(), ALTERNATE.load(Ordering::Relaxed));
+        }
+        default(info);
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn restored(alternate: bool) -> String {
+        let mut out = Vec::new();
+        restore_commands(&mut out, alternate).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    // One test, as the bracketed paste flag is shared
+    #[test]
+    fn restoring_undoes_what_the_guard_did() {
+        assert_eq!(restored(false), "\x1b[?25h");
+        assert_eq!(restored(true), "\x1b[?1049l\x1b[?25h");
+        PASTE.store(true, Ordering::Relaxed);
+        assert_eq!(restored(true), "\x1b[?2004l\x1b[?1049l\x1b[?25h");
+        // Bracketed paste is only turned off once
+        assert_eq!(restored(false), "\x1b[?25h");
+    }
+}
@@ -0,0 +1,85 @@
+use std::io::{self, Write};
+use std::time::Instant;
+
+/// Logs the gap before every byte (or every line) as `delta_us\tbytes`
+/// The bytes are written as space separated hex so the log survives binary data
+pub struct TimingLog<W> {
+    out: W,
+    lines: bool,
+    last: Instant,
+    pending: Vec<u8>,
+}
+
+impl<W: Write> TimingLog<W> {
+    pub fn new(out: W, lines: bool, start: Instant) -> Self {
+        TimingLog {
+            out,
+            lines,
+            last: start,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Record a chunk of data that was read at the instant `at`
+    pub fn record(&mut self, at: Instant, data: &[u8]) -> io::Result<()> {
+        if !self.lines {
+            for &byte in data {
+                self.entry(at, &[byte])?;
+            }
+            return Ok(());
+        }
+
+        let mut rest = data;
+        while let Some(i) = rest.iter().position(|&b| b == b'\n') {
+            let mut line = std::mem::take(&mut self.pending);
+            line.extend_from_slice(&rest[..=i]);
+            self.entry(at, &line)?;
+            rest = &rest[i + 1..];
+        }
+        self.pending.extend_from_slice(rest);
+        Ok(())
+    }
+
+    fn entry(&mut self, at: Instant, bytes: &[u8]) -> io::Result<()> {
+        let delta = at.saturating_duration_since(self.last);
+        self.last = at;
+        write!(self.out, "{}\t", delta.as_micros())?;
+        for (i, byte) in bytes.iter().enumerate() {
+            if i > 0 {
+                self.out.write_all(b" ")?;
+            }
+            write!(self.out, "{:02x}", byte)?;
+        }
+        self.out.write_all(b"\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn log(lines: bool, reads: &[(u64, &[u8])]) -> String {
+        let start = Instant::now();
+        let mut out = Vec::new();
+        let mut timing = TimingLog::new(&mut out, lines, start);
+        for &(at, data) in reads {
+            timing
+                .record(start + Duration::from_micros(at), data)
+                .unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn every_byte_gets_the_gap_since_the_one_before() {
+        let reads: &[(u64, &[u8])] = &[(100, b"A"), (350, b"BC"), (1350, b"\xff")];
+        assert_eq!(log(false, reads), "100\t41\n250\t42\n0\t43\n1000\tff\n");
+    }
+
+    #[test]
+    fn lines_are_timed_from_the_read_that_ends_them() {
+        let reads: &[(u64, &[u8])] = &[(10, b"ab"), (40, b"c\nd"), (90, b"\n"), (95, b"e")];
+        assert_eq!(log(true, reads), "40\t61 62 63 0a\n50\t64 0a\n");
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::VecDeque;
+
+use regex::bytes::Regex;
+
+/// Only lets through the lines from one matching the start pattern to one matching the stop pattern
+/// Without a start pattern everything up to the stop is let through
+/// The last `pretrigger` bytes before the start are kept and let through ahead of it
+pub struct Trigger {
+    start: Option<Regex>,
+    stop: Option<Regex>,
+    repeat: bool,
+    capturing: bool,
+    done: bool,
+    line: Vec<u8>,
+    pretrigger: usize,
+    before: VecDeque<u8>,
+}
+
+impl Trigger {
+    pub fn new(start: Option<Regex>, stop: Option<Regex>, repeat: bool, pretrigger: usize) -> Self {
+        Trigger {
+            capturing: start.is_none(),
+            start,
+            stop,
+            repeat,
+            done: false,
+            line: Vec::new(),
+            pretrigger,
+            before: VecDeque::with_capacity(pretrigger),
+        }
+    }
+
+    /// Append the captured whole lines of `data` to `out`
+    /// Returns true once the stop pattern has matched and there is nothing more to capture
+    pub fn feed(&mut self, data: &[u8], out: &mut Vec<u8>) -> bool {
+        for &b in data {
+            if self.done {
+                break;
+            }
+            self.line.push(b);
+            if b == b'\n' {
+                self.line_done(out);
+            }
+        }
+        self.done
+    }
+
+    fn line_done(&mut self, out: &mut Vec<u8>) {
+        // Match without the line ending so `$` works as expected
+        let text = self.line.strip_suffix(b"\n").unwrap_or(&self.line);
+        let text = text.strip_suffix(b"\r").unwrap_or(text);
+        let matches = |re: &Option<Regex>| re.as_ref().is_some_and(|re| re.is_match(text));
+        if !self.capturing && matches(&self.start) {
+            self.capturing = true;
+            out.extend(self.before.drain(..));
+        }
+        if self.capturing {
+            if matches(&self.stop) {
+                self.capturing = false;
+                // Without a start pattern there is nothing to wait for again
+                self.done = !self.repeat || self.start.is_none();
+            }
+            out.extend_from_slice(&self.line);
+        } else if self.pretrigger > 0 {
+            self.before.extend(&self.line);
+            let extra = self.before.len().saturating_sub(self.pretrigger);
+            self.before.drain(..extra);
+        }
+        self.line.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trigger(start: Option<&str>, stop: Option<&str>, repeat: bool) -> Trigger {
+        let re = |s: &str| Regex::new(s).unwrap();
+        Trigger::new(start.map(re), stop.map(re), repeat, 0)
+    }
+
+    fn run(trigger: &mut Trigger, chunks: &[&[u8]]) -> (Vec<u8>, bool) {
+        let mut out = Vec::new();
+        let mut done = false;
+        for chunk in chunks {
+            done = trigger.feed(chunk, &mut out);
+        }
+        (out, done)
+    }
+
+    #[test]
+    fn lines_before_the_start_are_discarded() {
+        let mut t = trigger(Some("^BEGIN$"), None, false);
+        let (out, done) = run(&mut t, &[b"noise\nmore\r\nBEG", b"IN\r\nkept\n"]);
+        assert_eq!(out, b"BEGIN\r\nkept\n");
+        assert!(!done);
+    }
+
+    #[test]
+    fn capture_runs_between_start_and_stop() {
+        let mut t = trigger(Some("BEGIN"), Some("END"), false);
+        let (out, done) = run(&mut t, &[b"a\nBEGIN\nb\nEND\nc\n"]);
+        assert_eq!(out, b"BEGIN\nb\nEND\n");
+        assert!(done);
+        // Nothing more once stopped
+        let (out, done) = run(&mut t, &[b"BEGIN\nd\n"]);
+        assert!(out.is_empty());
+        assert!(done);
+    }
+
+    #[test]
+    fn a_stop_without_a_start_ends_capture() {
+        let mut t = trigger(None, Some("END"), true);
+        let (out, done) = run(&mut t, &[b"a\nEND\nb\n"]);
+        assert_eq!(out, b"a\nEND\n");
+        assert!(done);
+    }
+
+    #[test]
+    fn repeat_waits_for_the_next_start() {
+        let mut t = trigger(Some("BEGIN"), Some("END"), true);
+        let (out, done) = run(&mut t, &[b"BEGIN\n1\nEND\nx\nBEGIN\n2\nEND\n"]);
+        assert_eq!(out, b"BEGIN\n1\nEND\nBEGIN\n2\nEND\n");
+        assert!(!done);
+    }
+
+    #[test]
+    fn partial_lines_wait_for_their_end() {
+        let mut t = trigger(None, None, false);
+        let (out, _) = run(&mut t, &[b"par"]);
+        assert!(out.is_empty());
+        let (out, _) = run(&mut t, &[b"tial\n"]);
+        assert_eq!(out, b"partial\n");
+    }
+
+    #[test]
+    fn the_bytes_before_the_start_are_let_through_first() {
+        let re = |s: &str| Some(Regex::new(s).unwrap());
+        let mut t = Trigger::new(re("FAULT"), re("END"), false, 7);
+        let (out, done) = run(&mut t, &[b"aaaa\nbbbb\ncc", b"cc\nFAULT\nx\nEND\ny\n"]);
+        // The last 7 bytes before it, even though that splits a line
+        assert_eq!(out, b"b\ncccc\nFAULT\nx\nEND\n");
+        assert!(done);
+    }
+
+    #[test]
+    fn the_pretrigger_window_is_shorter_when_little_came_first() {
+        let re = |s: &str| Some(Regex::new(s).unwrap());
+        let mut t = Trigger::new(re("GO"), None, false, 100);
+        let (out, _) = run(&mut t, &[b"one\nGO\n"]);
+        assert_eq!(out, b"one\nGO\n");
+    }
+}
@@ -0,0 +1,153 @@
+use std::fmt;
+
+use serialport::{DataBits, FlowControl, Parity, StopBits};
+
+use crate::Args;
+
+/// Something odd about the port settings, found before the port is opened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// Legal but uncommon, most likely a typo
+    Unusual(String),
+    /// The settings can't work together
+    Invalid(String),
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::Unusual(msg) => write!(f, "Warning: {}", msg),
+            Warning::Invalid(msg) => write!(f, "Error: {}", msg),
+        }
+    }
+}
+
+/// Check the data bits, parity, stop bits and flow characters make sense together
+pub fn validate_framing(args: &Args) -> Result<(), Vec<Warning>> {
+    let mut found = Vec::new();
+    let framing = args.framing();
+    let parity = args.parity != Parity::None;
+
+    match (args.data_bits, parity, args.stop_bits) {
+        (DataBits::Five, _, StopBits::Two) => found.push(Warning::Unusual(format!(
+            "{} usually means 1.5 stop bits on real hardware",
+            framing
+        ))),
+        (DataBits::Five | DataBits::Six, _, _) => found.push(Warning::Unusual(format!(
+            "{} is rarely used, did you mean 8N1?",
+            framing
+        ))),
+        (DataBits::Seven, false, StopBits::One) => found.push(Warning::Unusual(format!(
+            "{} is rarely used, did you mean 7E1 or 8N1?",
+            framing
+        ))),
+        (DataBits::Eight, true, StopBits::Two) => found.push(Warning::Unusual(format!(
+            "{} needs 12 bit frames which many UARTs don't support",
+            framing
+        ))),
+        _ => {}
+    }
+
+    if args.flow_control == FlowControl::Software {
+        let bits = match args.data_bits {
+            DataBits::Five => 5,
+            DataBits::Six => 6,
+            DataBits::Seven => 7,
+            DataBits::Eight => 8,
+        };
+        let (xon, xoff) = (args.xon.unwrap_or(0x11), args.xoff.unwrap_or(0x13));
+        if xon == xoff {
+            found.push(Warning::Invalid(format!(
+                "XON and XOFF are both {:#04x}",
+                xon
+            )));
+        }
+        for (name, c) in [("XON", xon), ("XOFF", xoff)] {
+            if u16::from(c) >= 1 << bits {
+                found.push(Warning::Invalid(format!(
+                    "{} {:#04x} can't be sent with {} data bits",
+                    name, c, bits
+                )));
+            }
+        }
+    }
+
+    if found.is_empty() {
+        Ok(())
+    } else {
+        Err(found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(data_bits: DataBits, parity: Parity, stop_bits: StopBits) -> Result<(), Vec<Warning>> {
+        validate_framing(&Args {
+            data_bits,
+            parity,
+            stop_bits,
+            ..Args::default()
+        })
+    }
+
+    #[test]
+    fn common_framings_pass() {
+        assert_eq!(check(DataBits::Eight, Parity::None, StopBits::One), Ok(()));
+        assert_eq!(check(DataBits::Seven, Parity::Even, StopBits::One), Ok(()));
+        assert_eq!(check(DataBits::Eight, Parity::Even, StopBits::One), Ok(()));
+    }
+
+    #[test]
+    fn uncommon_framings_are_unusual() {
+        let unusual = |result: Result<(), Vec<Warning>>| match result {
+            Err(warnings) => warnings.iter().all(|w| matches!(w, Warning::Unusual(_))),
+            Ok(()) => false,
+        };
+        assert!(unusual(check(DataBits::Seven, Parity::None, StopBits::One)));
+        assert!(unusual(check(DataBits::Six, Parity::None, StopBits::One)));
+        assert!(unusual(check(DataBits::Five, Parity::None, StopBits::Two)));
+        assert!(unusual(check(DataBits::Eight, Parity::Odd, StopBits::Two)));
+    }
+
+    #[test]
+    fn flow_characters_must_differ_and_fit_the_data_bits() {
+        let args = Args {
+            flow_control: FlowControl::Software,
+            xon: Some(0x01),
+            xoff: Some(0x01),
+            ..Args::default()
+        };
+        assert_eq!(
+            validate_framing(&args),
+            Err(vec![Warning::Invalid(String::from(
+                "XON and XOFF are both 0x01"
+            ))])
+        );
+
+        let args = Args {
+            flow_control: FlowControl::Software,
+            data_bits: DataBits::Seven,
+            parity: Parity::Even,
+            xoff: Some(0x93),
+            ..Args::default()
+        };
+        assert_eq!(
+            validate_framing(&args),
+            Err(vec![Warning::Invalid(String::from(
+                "XOFF 0x93 can't be sent with 7 data bits"
+            ))])
+        );
+    }
+
+    #[test]
+    fn flow_characters_are_only_checked_with_software_flow_control() {
+        let args = Args {
+            xon: Some(0x01),
+            xoff: Some(0x01),
+            ..Args::default()
+        };
+        assert_eq!(validate_framing(&args), Ok(()));
+    }
+}
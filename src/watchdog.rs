@@ -0,0 +1,131 @@
+use std::fmt;
+use std::str::FromStr;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use serialport::SerialPort;
+
+/// How long the reset line is held low
+const PULSE: Duration = Duration::from_millis(100);
+
+/// The modem control line wired to the device's reset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetLine {
+    Dtr,
+    Rts,
+}
+
+impl FromStr for ResetLine {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "dtr" => ResetLine::Dtr,
+            "rts" => ResetLine::Rts,
+            _ => return Err("Reset line should be DTR or RTS"),
+        })
+    }
+}
+
+impl fmt::Display for ResetLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ResetLine::Dtr => "DTR",
+            ResetLine::Rts => "RTS",
+        })
+    }
+}
+
+/// Decides when a silent device should be reset, and counts the resets
+pub struct Watchdog {
+    window: Duration,
+    last: Instant,
+    pub resets: usize,
+}
+
+impl Watchdog {
+    pub fn new(window: Duration, start: Instant) -> Self {
+        Watchdog {
+            window,
+            last: start,
+            resets: 0,
+        }
+    }
+
+    pub fn data(&mut self, now: Instant) {
+        self.last = now;
+    }
+
+    /// Whether nothing has arrived for the whole window at `now`, counting a reset if so
+    pub fn due(&mut self, now: Instant) -> bool {
+        if now - self.last < self.window {
+            return false;
+        }
+        self.resets += 1;
+        true
+    }
+
+    /// Start the window again once the reset is done, so the device gets time to boot
+    pub fn reset_done(&mut self, now: Instant) {
+        self.last = now;
+    }
+}
+
+/// Pull `line` low for a moment and let it go again, which resets most dev boards
+pub fn pulse(port: &mut dyn SerialPort, line: ResetLine) -> serialport::Result<()> {
+    let mut set = |level| match line {
+        ResetLine::Dtr => port.write_data_terminal_ready(level),
+        ResetLine::Rts => port.write_request_to_send(level),
+    };
+    set(false)?;
+    sleep(PULSE);
+    set(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockPort;
+
+    #[test]
+    fn reset_lines_parse() {
+        assert_eq!("dtr".parse(), Ok(ResetLine::Dtr));
+        assert_eq!("RTS".parse(), Ok(ResetLine::Rts));
+        assert!("cts".parse::<ResetLine>().is_err());
+    }
+
+    #[test]
+    fn due_once_the_data_stops_for_the_window() {
+        let start = Instant::now();
+        let ms = |ms| start + Duration::from_millis(ms);
+        let mut watchdog = Watchdog::new(Duration::from_millis(100), start);
+        assert!(!watchdog.due(ms(50)));
+        watchdog.data(ms(80));
+        assert!(!watchdog.due(ms(150)));
+        assert!(watchdog.due(ms(180)));
+        assert_eq!(watchdog.resets, 1);
+    }
+
+    #[test]
+    fn the_window_starts_again_after_a_reset() {
+        let start = Instant::now();
+        let ms = |ms| start + Duration::from_millis(ms);
+        let mut watchdog = Watchdog::new(Duration::from_millis(100), start);
+        assert!(watchdog.due(ms(100)));
+        watchdog.reset_done(ms(250));
+        assert!(!watchdog.due(ms(300)));
+        assert!(watchdog.due(ms(350)));
+        assert_eq!(watchdog.resets, 2);
+    }
+
+    #[test]
+    fn the_pulse_pulls_the_line_low_then_lets_it_go() {
+        let mut port = MockPort::default();
+        pulse(&mut port, ResetLine::Dtr).unwrap();
+        pulse(&mut port, ResetLine::Rts).unwrap();
+        assert_eq!(
+            port.state().lines,
+            [("DTR", false), ("DTR", true), ("RTS", false), ("RTS", true)]
+        );
+    }
+}
@@ -0,0 +1,465 @@
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{sync_channel, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Lines a client can fall behind by before it misses some
+pub const BACKLOG: usize = 1024;
+
+/// The longest message taken from a browser, anything bigger closes the connection
+const MAX_MESSAGE: u64 = 64 * 1024;
+
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+
+/// Served to a browser that asks for the page rather than the WebSocket
+const PAGE: &str = r#"<!doctype html>
+<meta charset="utf-8">
+<title>oxterm</title>
+<style>
+body { margin: 0; background: #111; color: #ddd; font: 13px monospace; }
+pre { margin: 0; padding: 8px; white-space: pre-wrap; }
+form { position: sticky; bottom: 0; display: flex; }
+input { flex: 1; font: inherit; }
+</style>
+<pre id="out"></pre>
+<form id="send" hidden><input id="line" autocomplete="off" placeholder="Send to the port"></form>
+<script>
+const out = document.getElementById("out");
+const send = document.getElementById("send");
+const line = document.getElementById("line");
+const ws = new WebSocket((location.protocol == "https:" ? "wss://" : "ws://") + location.host + "/");
+send.hidden = !DUPLEX;
+ws.onmessage = (e) => {
+  const bottom = innerHeight + scrollY >= document.body.scrollHeight - 4;
+  out.append(e.data + "\n");
+  while (out.childNodes.length > 5000) out.firstChild.remove();
+  if (bottom) scrollTo(0, document.body.scrollHeight);
+};
+ws.onclose = () => out.append("--- disconnected ---\n");
+send.onsubmit = (e) => { e.preventDefault(); ws.send(line.value); line.value = ""; };
+</script>
+"#;
+
+/// SHA-1 of `data`, only needed for the handshake
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let t = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (bytes, h) in digest.chunks_mut(4).zip(h) {
+        bytes.copy_from_slice(&h.to_be_bytes());
+    }
+    digest
+}
+
+/// The Sec-WebSocket-Accept answer to a client's Sec-WebSocket-Key
+pub fn accept_key(key: &str) -> String {
+    crate::b64::encode(&sha1(format!("{}{}", key.trim(), GUID).as_bytes()))
+}
+
+/// The value of a header in the head of an HTTP request, the name ignores case
+pub fn header<'a>(request: &'a str, name: &str) -> Option<&'a str> {
+    request.lines().skip(1).find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+    })
+}
+
+/// One unfragmented frame from the server, which is never masked
+pub fn frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        n if n < 126 => frame.push(n as u8),
+        n if n <= 0xffff => {
+            frame.push(126);
+            frame.extend_from_slice(&(n as u16).to_be_bytes());
+        }
+        n => {
+            frame.push(127);
+            frame.extend_from_slice(&(n as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// Read one frame from a client, giving its opcode and unmasked payload
+pub fn read_frame(r: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+    let mut head = [0; 2];
+    r.read_exact(&mut head)?;
+    let len = match head[1] & 0x7f {
+        126 => {
+            let mut len = [0; 2];
+            r.read_exact(&mut len)?;
+            u16::from_be_bytes(len) as u64
+        }
+        127 => {
+            let mut len = [0; 8];
+            r.read_exact(&mut len)?;
+            u64::from_be_bytes(len)
+        }
+        n => n as u64,
+    };
+    if len > MAX_MESSAGE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("a {} byte message is too long", len),
+        ));
+    }
+    let mut mask = [0; 4];
+    if head[1] & 0x80 != 0 {
+        r.read_exact(&mut mask)?;
+    }
+    let mut payload = vec![0; len as usize];
+    r.read_exact(&mut payload)?;
+    for (i, b) in payload.iter_mut().enumerate() {
+        *b ^= mask[i % 4];
+    }
+    Ok((head[0] & 0x0f, payload))
+}
+
+/// Read the head of an HTTP request, up to its blank line
+fn read_request(stream: &mut impl Read) -> io::Result<String> {
+    let mut request = Vec::new();
+    let mut byte = [0];
+    while !request.ends_with(b"\r\n\r\n") {
+        if request.len() > 8192 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the request is too long",
+            ));
+        }
+        stream.read_exact(&mut byte)?;
+        request.push(byte[0]);
+    }
+    Ok(String::from_utf8_lossy(&request).into_owned())
+}
+
+struct Client {
+    addr: SocketAddr,
+    frames: SyncSender<Arc<Vec<u8>>>,
+    behind: bool,
+}
+
+type Clients = Arc<Mutex<Vec<Client>>>;
+type PortWriter = Arc<Mutex<Box<dyn Write + Send>>>;
+
+/// Sends every line written to it to all the connected WebSocket clients as a text message
+/// A client that falls more than BACKLOG lines behind misses lines rather than holding up the rest
+pub struct Broadcaster {
+    clients: Clients,
+    carry: Vec<u8>,
+}
+
+impl Broadcaster {
+    /// Listen on `addr`, what the clients send is written to `port` followed by `eol` when it is given
+    pub fn listen(
+        addr: &str,
+        port: Option<Box<dyn Write + Send>>,
+        eol: &'static [u8],
+    ) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        eprintln!("Serving on http://{}", listener.local_addr()?);
+        let clients: Clients = Arc::default();
+        let port: Option<PortWriter> = port.map(|port| Arc::new(Mutex::new(port)));
+        let accepting = clients.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let clients = accepting.clone();
+                let port = port.clone();
+                thread::spawn(move || serve(stream, clients, port, eol));
+            }
+        });
+        Ok(Broadcaster {
+            clients,
+            carry: Vec::new(),
+        })
+    }
+
+    fn broadcast(clients: &Clients, line: &[u8]) {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let frame = Arc::new(frame(OP_TEXT, String::from_utf8_lossy(line).as_bytes()));
+        clients
+            .lock()
+            .unwrap()
+            .retain_mut(|client| match client.frames.try_send(frame.clone()) {
+                Ok(()) => {
+                    client.behind = false;
+                    true
+                }
+                Err(TrySendError::Full(_)) => {
+                    if !std::mem::replace(&mut client.behind, true) {
+                        eprintln!(
+                            "WebSocket client {} is falling behind, dropping lines",
+                            client.addr
+                        );
+                    }
+                    true
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            });
+    }
+}
+
+impl Write for Broadcaster {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.carry.extend_from_slice(buf);
+        let mut used = 0;
+        while let Some(i) = self.carry[used..].iter().position(|&b| b == b'\n') {
+            Broadcaster::broadcast(&self.clients, &self.carry[used..used + i]);
+            used += i + 1;
+        }
+        self.carry.drain(..used);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Answer one connection, with the page or, if it asks to upgrade, as a WebSocket client
+fn serve(mut stream: TcpStream, clients: Clients, port: Option<PortWriter>, eol: &[u8]) {
+    let addr = match stream.peer_addr() {
+        Ok(addr) => addr,
+        Err(_) => return,
+    };
+    let request = match read_request(&mut stream) {
+        Ok(request) => request,
+        Err(_) => return,
+    };
+    let key = match header(&request, "Sec-WebSocket-Key") {
+        Some(key) => key,
+        None => {
+            let page = PAGE.replace("DUPLEX", &port.is_some().to_string());
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                page.len(),
+                page
+            );
+            return;
+        }
+    };
+    let accept = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)
+    );
+    let mut writer = match stream.write_all(accept.as_bytes()).and(stream.try_clone()) {
+        Ok(writer) => writer,
+        Err(_) => return,
+    };
+
+    let (frames, queued) = sync_channel::<Arc<Vec<u8>>>(BACKLOG);
+    clients.lock().unwrap().push(Client {
+        addr,
+        frames,
+        behind: false,
+    });
+    eprintln!("WebSocket client {} connected", addr);
+    let sending = thread::spawn(move || {
+        for frame in queued {
+            if writer.write_all(&frame).is_err() {
+                break;
+            }
+        }
+        writer
+    });
+
+    loop {
+        match read_frame(&mut stream) {
+            Ok((OP_TEXT | OP_BINARY | OP_CONTINUATION, message)) => {
+                if let Some(port) = &port {
+                    let mut port = port.lock().unwrap();
+                    if let Err(e) = port.write_all(&message).and(port.write_all(eol)) {
+                        eprintln!("Could not send a WebSocket message to the port: {}", e);
+                    }
+                }
+            }
+            Ok((OP_CLOSE, _)) | Err(_) => break,
+            // Pings and pongs, browsers don't need an answer
+            Ok(_) => {}
+        }
+    }
+
+    // Dropping its sender ends the sending thread, then the socket is all ours again
+    clients.lock().unwrap().retain(|client| client.addr != addr);
+    if let Ok(mut writer) = sending.join() {
+        let _ = writer.write_all(&frame(OP_CLOSE, &[]));
+    }
+    let _ = stream.shutdown(Shutdown::Both);
+    eprintln!("WebSocket client {} disconnected", addr);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_split_across_writes_are_sent_whole() {
+        let (frames, queued) = sync_channel(BACKLOG);
+        let clients: Clients = Arc::new(Mutex::new(vec![Client {
+            addr: "127.0.0.1:1".parse().unwrap(),
+            frames,
+            behind: false,
+        }]));
+        let mut broadcaster = Broadcaster {
+            clients,
+            carry: Vec::new(),
+        };
+        for chunk in b"first\r\nsec".chunks(3).chain([&b"ond\nthi"[..]]) {
+            broadcaster.write_all(chunk).unwrap();
+        }
+        let sent: Vec<Vec<u8>> = queued.try_iter().map(|f| f.to_vec()).collect();
+        assert_eq!(sent, [frame(OP_TEXT, b"first"), frame(OP_TEXT, b"second")]);
+        assert_eq!(broadcaster.carry, b"thi");
+    }
+
+    const KEY: &str = "dGhlIHNhbXBsZSBub25jZQ==";
+
+    /// A broadcaster listening on a free local port, and its address
+    fn listening(port: Option<Box<dyn Write + Send>>) -> (Broadcaster, String) {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+        (Broadcaster::listen(&addr, port, b"\r\n").unwrap(), addr)
+    }
+
+    /// A masked frame, as clients have to send them
+    fn client_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = [0x12, 0x34, 0x56, 0x78];
+        let mut frame = vec![0x80 | opcode, 0x80 | payload.len() as u8];
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        frame
+    }
+
+    fn wait_for(what: impl Fn() -> bool) {
+        let start = std::time::Instant::now();
+        while !what() {
+            assert!(start.elapsed().as_secs() < 5, "timed out");
+            thread::sleep(std::time::Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn the_accept_key_matches_the_rfc_example() {
+        assert_eq!(accept_key(KEY), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+        assert_eq!(sha1(b"abc")[..4], [0xa9, 0x99, 0x3e, 0x36]);
+        let request = "GET / HTTP/1.1\r\nHost: x\r\nsec-websocket-key:  abc \r\n\r\n";
+        assert_eq!(header(request, "Sec-WebSocket-Key"), Some("abc"));
+        assert_eq!(header(request, "Upgrade"), None);
+    }
+
+    #[test]
+    fn frames_round_trip_with_long_lengths() {
+        for len in [0, 125, 126, 65535] {
+            let payload = vec![b'x'; len];
+            let (opcode, got) = read_frame(&mut &frame(OP_TEXT, &payload)[..]).unwrap();
+            assert_eq!((opcode, got.len()), (OP_TEXT, len));
+        }
+        let long = frame(OP_TEXT, &vec![b'x'; 70000]);
+        assert_eq!(long[1], 127);
+        assert!(read_frame(&mut &long[..]).is_err());
+        let (opcode, got) = read_frame(&mut &client_frame(OP_TEXT, b"hi")[..]).unwrap();
+        assert_eq!((opcode, &got[..]), (OP_TEXT, &b"hi"[..]));
+    }
+
+    #[test]
+    fn a_connected_client_gets_each_line_and_can_write_to_the_port() {
+        let port = crate::mock::MockPort::default();
+        let (mut broadcaster, addr) = listening(Some(Box::new(port.clone())));
+        let mut client = TcpStream::connect(&addr).unwrap();
+        client
+            .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+            .unwrap();
+        write!(
+            client,
+            "GET / HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            addr, KEY
+        )
+        .unwrap();
+        let response = read_request(&mut client).unwrap();
+        assert!(response.starts_with("HTTP/1.1 101"), "{}", response);
+        assert_eq!(
+            header(&response, "Sec-WebSocket-Accept"),
+            Some("s3pPLMBiTxaQ9kYGzzhZRbK+xOo=")
+        );
+        wait_for(|| broadcaster.clients.lock().unwrap().len() == 1);
+
+        broadcaster.write_all(b"first\r\nsec").unwrap();
+        broadcaster.write_all(b"ond\n").unwrap();
+        assert_eq!(
+            read_frame(&mut client).unwrap(),
+            (OP_TEXT, b"first".to_vec())
+        );
+        assert_eq!(
+            read_frame(&mut client).unwrap(),
+            (OP_TEXT, b"second".to_vec())
+        );
+
+        client.write_all(&client_frame(OP_TEXT, b"AT")).unwrap();
+        wait_for(|| port.state().written == b"AT\r\n");
+
+        client.write_all(&client_frame(OP_CLOSE, &[])).unwrap();
+        assert_eq!(read_frame(&mut client).unwrap().0, OP_CLOSE);
+        wait_for(|| broadcaster.clients.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn a_plain_request_gets_the_page() {
+        let (_broadcaster, addr) = listening(None);
+        let mut client = TcpStream::connect(&addr).unwrap();
+        write!(client, "GET / HTTP/1.1\r\nHost: {}\r\n\r\n", addr).unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("send.hidden = !false;"));
+    }
+}
@@ -0,0 +1,590 @@
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// A file in the temp directory that is only used by one test
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("oxterm-cli-{}-{}", std::process::id(), name))
+}
+
+/// Run oxterm without any OXTERM_ settings from the environment
+fn oxterm(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_oxterm"))
+        .env_clear()
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn stats_go_to_stderr_and_stdout_only_has_the_data() {
+    let path = temp_path("stderr.bin");
+    let data = b"line one\nline two\n\x00\xff";
+    std::fs::write(&path, data).unwrap();
+    let output = oxterm(&[
+        "--replay",
+        path.to_str().unwrap(),
+        "--stats",
+        "--histogram",
+        "--buffer-stats",
+    ]);
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, data);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Received 20 bytes, 2 lines"), "{}", stderr);
+}
+
+#[test]
+fn a_zero_capacity_is_an_argument_error() {
+    for args in [
+        &["-C", "0"][..],
+        &["--capacity", "0", "--max-read-size", "16"],
+    ] {
+        let output = oxterm(&[&["--replay", "/dev/null"][..], args].concat());
+        assert_eq!(output.status.code(), Some(1), "{:?}", output);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.starts_with("Invalid --capacity: "), "{}", stderr);
+        assert!(!stderr.contains("panicked"), "{}", stderr);
+    }
+}
+
+#[test]
+fn values_that_dont_parse_are_argument_errors() {
+    let mut cases: Vec<&[&str]> = vec![
+        &["--max-read-size", "1k"],
+        &["--max-read-size", "0"],
+        &["--replay-rate", "fast"],
+        &["--group", "-2"],
+        &["--watchdog", "soon"],
+        &["--decimate", "x"],
+        &["--decimate-lines", "x"],
+    ];
+    if cfg!(feature = "regex") {
+        cases.extend([&["--start-trigger", "("][..], &["--ack-pattern", "["]]);
+    }
+    for args in cases {
+        let output = oxterm(&[&["--replay", "/dev/null"][..], args].concat());
+        assert_eq!(output.status.code(), Some(1), "{:?} {:?}", args, output);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.starts_with(&format!("Invalid {}: ", args[0])),
+            "{}",
+            stderr
+        );
+        assert!(!stderr.contains("panicked"), "{}", stderr);
+    }
+}
+
+#[test]
+fn bad_times_are_argument_errors() {
+    for name in ["--duration", "--wait-timeout"] {
+        for value in ["-1", "NaN", "inf"] {
+            let output = oxterm(&["--replay", "/dev/null", name, value]);
+            assert_eq!(output.status.code(), Some(1), "{:?}", output);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            assert!(
+                stderr.starts_with(&format!("Invalid {}: ", name)),
+                "{}",
+                stderr
+            );
+        }
+    }
+}
+
+/// A pseudo terminal standing in for a serial device, the slave is the port oxterm opens
+#[cfg(unix)]
+struct Pty {
+    master: std::fs::File,
+    // Held open so the master doesn't see a hang up between oxterm's opens
+    _slave: std::fs::File,
+    port: String,
+}
+
+#[cfg(unix)]
+impl Pty {
+    fn open() -> Pty {
+        use std::os::unix::io::FromRawFd;
+        let (mut master, mut slave) = (0, 0);
+        let opened = unsafe {
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                std::ptr::null(),
+            )
+        };
+        assert_eq!(opened, 0);
+        let port = std::fs::read_link(format!("/proc/self/fd/{}", slave)).unwrap();
+        unsafe {
+            Pty {
+                master: std::fs::File::from_raw_fd(master),
+                _slave: std::fs::File::from_raw_fd(slave),
+                port: port.to_string_lossy().into_owned(),
+            }
+        }
+    }
+
+    /// Read from the device side until `expected` has arrived, failing after a few seconds
+    fn expect(&mut self, expected: &[u8]) {
+        use std::io::Read;
+        use std::os::unix::io::AsRawFd;
+        let mut got = Vec::new();
+        let mut buf = [0; 256];
+        while !got.ends_with(expected) {
+            let mut poll = libc::pollfd {
+                fd: self.master.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            let ready = unsafe { libc::poll(&mut poll, 1, 5000) };
+            assert!(
+                ready > 0,
+                "timed out, got {:?}",
+                String::from_utf8_lossy(&got)
+            );
+            let n = self.master.read(&mut buf).unwrap();
+            assert!(n > 0, "got {:?}", String::from_utf8_lossy(&got));
+            got.extend_from_slice(&buf[..n]);
+        }
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn once_sends_the_init_string_and_prints_the_reply() {
+    use std::io::Write;
+    let mut pty = Pty::open();
+    let child = Command::new(env!("CARGO_BIN_EXE_oxterm"))
+        .env_clear()
+        .args(["-p", &pty.port, "--send-init", "ping\\n", "--once"])
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    pty.expect(b"ping\n");
+    pty.master.write_all(b"pong\nnot this\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"pong\n");
+}
+
+#[test]
+fn dry_run_prints_the_resolved_settings() {
+    let output = oxterm(&[
+        "-p",
+        "/dev/ttyNOTHERE",
+        "-b",
+        "9600",
+        "-P",
+        "even",
+        "-m",
+        "lines",
+        "-c",
+        "HEX",
+        "--max-lines",
+        "10",
+        "--dry-run",
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        [
+            "/dev/ttyNOTHERE | 9600 | 8E1 | lines | HEX",
+            "Port: /dev/ttyNOTHERE",
+            "Baud rate: 9600",
+            "Framing: 8E1",
+            "Flow control: None",
+            "Timeout: 0ms",
+            "Mode: lines",
+            "Convert: HEX",
+            "Capacity: 64",
+            "Read size: 64",
+            "Max bytes: none",
+            "Max lines: 10",
+            "Duration: none",
+            "Idle timeout: none",
+        ]
+    );
+
+    let output = oxterm(&[
+        "-p",
+        "/dev/ttyNOTHERE",
+        "-m",
+        "file",
+        "-o",
+        "capture.txt",
+        "--append",
+        "--dry-run",
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(
+        stdout.lines().last(),
+        Some("Output file: capture.txt (appended to)")
+    );
+}
+
+#[test]
+fn append_is_a_flag_and_output_file_is_given_once() {
+    let path = temp_path("append-flag.txt");
+    std::fs::write(&path, "old\n").unwrap();
+    let output = oxterm(&[
+        "--replay",
+        "/dev/null",
+        "-m",
+        "file",
+        "-o",
+        path.to_str().unwrap(),
+        "--append",
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    // Nothing was added, but with --append nothing was lost either
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "old\n");
+
+    let output = oxterm(&["--replay", "/dev/null", "-o", "a.txt", "-o", "b.txt"]);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--output-file only once"), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+
+    let output = oxterm(&[
+        "--replay",
+        "/dev/null",
+        "-m",
+        "file",
+        "-o",
+        path.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn a_port_that_cant_be_opened_exits_with_status_2() {
+    let output = oxterm(&["-p", "/dev/ttyNOTHERE"]);
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.starts_with("Could not open /dev/ttyNOTHERE at 115200 baud"),
+        "{}",
+        stderr
+    );
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+}
+
+#[test]
+fn version_prints_the_build_info() {
+    for flag in ["-V", "--version"] {
+        let output = oxterm(&[flag]);
+        assert!(output.status.success(), "{:?}", output);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            stdout.starts_with(&format!("oxterm {}", env!("CARGO_PKG_VERSION"))),
+            "{}",
+            stdout
+        );
+        assert!(stdout.contains("\ntarget: "), "{}", stdout);
+        assert!(stdout.contains("\nfeatures: "), "{}", stdout);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn sigterm_flushes_the_output_file_and_exits_0() {
+    use std::io::Write;
+    let mut pty = Pty::open();
+    let path = temp_path("sigterm.log");
+    let child = Command::new(env!("CARGO_BIN_EXE_oxterm"))
+        .env_clear()
+        .args(["-p", &pty.port, "--send-init", "ready\\n", "-m", "file"])
+        .args(["--output-file", path.to_str().unwrap()])
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    pty.expect(b"ready\n");
+    pty.master.write_all(b"buffered line\n").unwrap();
+    // Give it time to read the line into its output buffer
+    std::thread::sleep(std::time::Duration::from_millis(300));
+    assert_eq!(
+        unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGTERM) },
+        0
+    );
+    let output = child.wait_with_output().unwrap();
+    let log = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(log, b"buffered line\n");
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn a_full_disk_is_reported_once_and_exits_with_status_4() {
+    let path = temp_path("full.bin");
+    std::fs::write(&path, "line\n".repeat(100_000)).unwrap();
+    let output = oxterm(&[
+        "--replay",
+        path.to_str().unwrap(),
+        "-m",
+        "file",
+        "--output-file",
+        "/dev/full",
+    ]);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(output.status.code(), Some(4), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.matches("Stopped writing, the disk is full").count(),
+        1,
+        "{}",
+        stderr
+    );
+    assert_eq!(stderr.matches("Stopped").count(), 1, "{}", stderr);
+}
+
+#[test]
+fn a_closed_pipe_exits_0_quietly() {
+    let path = temp_path("pipe.bin");
+    std::fs::write(&path, "line\n".repeat(1_000_000)).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_oxterm"))
+        .env_clear()
+        .args(["--replay", path.to_str().unwrap()])
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Like `| head` going away
+    drop(child.stdout.take());
+    let output = child.wait_with_output().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("Stopped"), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn a_saved_profile_is_loaded_under_the_command_line() {
+    let home = temp_path("home");
+    let profile = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_oxterm"))
+            .env_clear()
+            .env("HOME", &home)
+            .args(["-p", "/dev/ttyNOTHERE", "--dry-run"])
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let saved = profile(&["--save-profile", "bench", "-b", "9600", "-P", "even"]);
+    assert!(saved.status.success(), "{:?}", saved);
+    let file = home.join(".config/oxterm/profiles/bench");
+    assert_eq!(
+        std::fs::read_to_string(&file).unwrap(),
+        "-p\n/dev/ttyNOTHERE\n--dry-run\n-b\n9600\n-P\neven\n"
+    );
+    let loaded = profile(&["--profile", "bench", "-b", "19200"]);
+    std::fs::remove_dir_all(&home).unwrap();
+    assert!(loaded.status.success(), "{:?}", loaded);
+    let stdout = String::from_utf8_lossy(&loaded.stdout);
+    assert_eq!(
+        stdout.lines().next(),
+        Some("/dev/ttyNOTHERE | 19200 | 8E1 | stdout | NON")
+    );
+}
+
+#[test]
+fn oxterm_variables_are_used_under_the_command_line() {
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_oxterm"))
+            .env_clear()
+            .env("OXTERM_BAUD", "57600")
+            .env("OXTERM_PARITY", "odd")
+            .args(["-p", "/dev/ttyNOTHERE", "--dry-run"])
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let output = run(&[]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(
+        stdout.lines().next(),
+        Some("/dev/ttyNOTHERE | 57600 | 8O1 | stdout | NON")
+    );
+    let output = run(&["-b", "9600"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(
+        stdout.lines().next(),
+        Some("/dev/ttyNOTHERE | 9600 | 8O1 | stdout | NON")
+    );
+    assert!(output.stderr.is_empty(), "{:?}", output);
+}
+
+#[cfg(unix)]
+#[test]
+fn an_idle_timeout_without_data_has_its_own_exit_status() {
+    let pty = Pty::open();
+    let output = oxterm(&["-p", &pty.port, "-q", "--idle-timeout", "200"]);
+    assert_eq!(output.status.code(), Some(5), "{:?}", output);
+    let output = oxterm(&[
+        "-p",
+        &pty.port,
+        "-q",
+        "--idle-timeout",
+        "200",
+        "--timeout-exit-code",
+        "9",
+    ]);
+    assert_eq!(output.status.code(), Some(9), "{:?}", output);
+}
+
+#[cfg(unix)]
+#[test]
+fn an_idle_timeout_after_data_is_a_success() {
+    use std::io::Write;
+    let mut pty = Pty::open();
+    let child = Command::new(env!("CARGO_BIN_EXE_oxterm"))
+        .env_clear()
+        .args(["-p", &pty.port, "-q", "--idle-timeout", "500"])
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Sent once the port is open, data waiting before it is set up can be dropped
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    pty.master.write_all(b"hello\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(0), "{:?}", output);
+    assert_eq!(output.stdout, b"hello\n");
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn a_source_that_fails_to_read_exits_with_the_read_status() {
+    // A directory opens on Linux but can't be read
+    let output = oxterm(&["-r", &std::env::temp_dir().to_string_lossy()]);
+    assert_eq!(output.status.code(), Some(3), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Is a directory"), "{}", stderr);
+}
+
+#[test]
+fn a_replay_can_start_part_way_into_the_file() {
+    let path = temp_path("seek.bin");
+    std::fs::write(&path, b"first line\nsecond line\n").unwrap();
+    let file = path.to_string_lossy().into_owned();
+    let output = oxterm(&["-r", &file, "-q", "--replay-seek-bytes", "11"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"second line\n");
+
+    let output = oxterm(&["-r", &file, "-q", "--replay-seek-bytes", "100"]);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("it is only 23 bytes long"), "{}", stderr);
+}
+
+#[test]
+fn a_missing_replay_file_is_an_open_failure() {
+    let path = temp_path("missing.bin");
+    let output = oxterm(&["-r", &path.to_string_lossy()]);
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("Could not open "), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+}
+
+#[cfg(unix)]
+#[test]
+fn the_open_delay_comes_before_the_init_string() {
+    use std::io::Write;
+    let mut pty = Pty::open();
+    let start = std::time::Instant::now();
+    let child = Command::new(env!("CARGO_BIN_EXE_oxterm"))
+        .env_clear()
+        .args(["-p", &pty.port, "--open-delay", "400"])
+        .args(["--send-init", "ping\\n", "--once"])
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    pty.expect(b"ping\n");
+    assert!(
+        start.elapsed() >= std::time::Duration::from_millis(400),
+        "sent after {:?}",
+        start.elapsed()
+    );
+    pty.master.write_all(b"pong\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(output.stdout, b"pong\n");
+}
+
+#[test]
+fn struct_records_are_decoded_once_the_size_matches_the_format() {
+    let data = temp_path("struct.bin");
+    let spec = temp_path("struct.txt");
+    std::fs::write(&data, b"\x01\x02\x00\x03\x04\x05\x00\x06").unwrap();
+    std::fs::write(&spec, "u8 a; u16 b; u8 c").unwrap();
+    let run = |size: &str| {
+        oxterm(&[
+            "--replay",
+            data.to_str().unwrap(),
+            "--format",
+            spec.to_str().unwrap(),
+            "--struct",
+            size,
+        ])
+    };
+    let matching = run("4");
+    let padded = run("6");
+    let alone = oxterm(&["--replay", data.to_str().unwrap(), "--struct", "4"]);
+    std::fs::remove_file(&data).unwrap();
+    std::fs::remove_file(&spec).unwrap();
+
+    assert!(matching.status.success(), "{:?}", matching);
+    assert_eq!(matching.stdout, b"a=1 b=2 c=3\na=4 b=5 c=6\n");
+    assert_eq!(padded.status.code(), Some(1), "{:?}", padded);
+    let stderr = String::from_utf8_lossy(&padded.stderr);
+    assert!(stderr.contains("not the 6 of --struct"), "{}", stderr);
+    assert!(padded.stdout.is_empty());
+    assert_eq!(alone.status.code(), Some(1), "{:?}", alone);
+    let stderr = String::from_utf8_lossy(&alone.stderr);
+    assert!(stderr.contains("give that too"), "{}", stderr);
+}
+
+#[cfg(unix)]
+#[test]
+fn bells_alone_keep_the_idle_timeout_from_running_out() {
+    use std::io::Write;
+    let mut pty = Pty::open();
+    let child = Command::new(env!("CARGO_BIN_EXE_oxterm"))
+        .env_clear()
+        .args([
+            "-p",
+            &pty.port,
+            "--idle-timeout",
+            "300",
+            "--count-byte",
+            "7",
+        ])
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    for _ in 0..10 {
+        pty.master.write_all(b"\x07").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    let output = child.wait_with_output().unwrap();
+    // Without data an idle timeout exits 5, the bells are data even though they aren't shown
+    assert_eq!(output.status.code(), Some(0), "{:?}", output);
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Received 10 bytes"), "{}", stderr);
+    assert!(stderr.contains("10 of 0x07"), "{}", stderr);
+}